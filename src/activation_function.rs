@@ -1,29 +1,108 @@
+use expr::Expr;
+use float::Float;
+use interval::Interval;
+use kernels;
 use std::fmt::Debug;
 use std::f64::consts::PI;
 
 pub trait ActivationFunction: Clone + Debug + Send + Sized + PartialEq + Eq {
     fn formula_gnuplot(&self, x: String) -> String;
     fn name(&self) -> String;
-    fn calculate(&self, x: f64) -> f64;
+    fn calculate<F: Float>(&self, x: F) -> F;
+
+    /// Applies the activation function to all values of `xs` in place.
+    ///
+    /// Implementations may use vectorized approximations (see the `kernels` module), so the
+    /// results can differ slightly from `calculate`.
+    fn calculate_slice<F: Float>(&self, xs: &mut [F]) {
+        for x in xs.iter_mut() {
+            *x = self.calculate(*x);
+        }
+    }
+
+    /// The derivative of the activation function at `x`.
+    ///
+    /// The default implementation uses a central difference. Implementations should override
+    /// it with the exact derivative where possible.
+    fn derivative<F: Float>(&self, x: F) -> F {
+        let h = F::from_f64(F::EPSILON.cbrt()) * (F::one() + x.abs());
+        (self.calculate(x + h) - self.calculate(x - h)) / (F::from_f64(2.0) * h)
+    }
+
+    /// Bounds on `calculate(x)` for all `x` within `interval`.
+    ///
+    /// The default implementation returns the entire range of values, which is always correct
+    /// but useless. Implementations should override it with tight bounds.
+    fn calculate_interval<F: Float>(&self, _interval: Interval<F>) -> Interval<F> {
+        Interval::entire()
+    }
+
+    /// The activation function applied to `x`, as a symbolic expression.
+    ///
+    /// The default implementation returns a call of a function named `name()`, which cannot be
+    /// evaluated or exported. Implementations should override it.
+    fn expr(&self, x: Expr) -> Expr {
+        Expr::Call(self.name(), Box::new(x))
+    }
 }
 
 #[inline(always)]
-fn bipolar_debug_check(x: f64) -> f64 {
-    debug_assert!(x >= -1.0 && x <= 1.0);
+fn bipolar_debug_check<F: Float>(x: F) -> F {
+    // NaN passes, so that it can be reported as a non-finite output.
+    debug_assert!(!(x < -F::one() || x > F::one()));
     x
 }
 
 /// Clips the value of `x` into the range [-1, 1].
-fn bipolar_clip(x: f64) -> f64 {
-    if x > 1.0 {
-        1.0
-    } else if x < -1.0 {
-        -1.0
+fn bipolar_clip<F: Float>(x: F) -> F {
+    if x > F::one() {
+        F::one()
+    } else if x < -F::one() {
+        -F::one()
+    } else {
+        x
+    }
+}
+
+/// `max(min, x)`, but NaN is passed through.
+fn at_least<F: Float>(x: F, min: F) -> F {
+    if x < min {
+        min
+    } else {
+        x
+    }
+}
+
+/// `min(max, x)`, but NaN is passed through.
+fn at_most<F: Float>(x: F, max: F) -> F {
+    if x > max {
+        max
+    } else {
+        x
+    }
+}
+
+/// The Heaviside step function, see `Function::Step`. NaN is passed through.
+fn step<F: Float>(x: F) -> F {
+    if x >= F::zero() {
+        F::one()
+    } else if x < F::zero() {
+        F::zero()
     } else {
         x
     }
 }
 
+/// `max(0, 1 - |x|)`, but NaN is passed through.
+fn hat<F: Float>(x: F) -> F {
+    at_least(F::one() - x.abs(), F::zero())
+}
+
+/// The fractional part of `x`, within [0, 1).
+fn fract<F: Float>(x: F) -> F {
+    x - x.floor()
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum GeometricActivationFunction {
@@ -37,27 +116,448 @@ pub enum GeometricActivationFunction {
     Sine,
     Cosine,
     Constant1,
+    Tanh,
+    /// `max(0, x)`.
+    Relu,
+    /// `x` for positive `x`, `0.01 * x` otherwise.
+    LeakyRelu,
+    /// `x` for positive `x`, `exp(x) - 1` otherwise.
+    Elu,
+    /// `ln(1 + exp(x))`, a smooth version of `Relu`.
+    Softplus,
+    /// 1 for `x >= 0`, 0 otherwise.
+    Step,
+    BipolarStep,
+    /// Rises from 0 to 1 within each period of length 1.
+    Sawtooth,
+    BipolarSawtooth,
+    /// Peaks with 1 at integers and falls linearly to 0 in between, like a linear `Cosine`.
+    Triangle,
+    BipolarTriangle,
+    /// 1 in the first half of each period of length 1, 0 in the second half.
+    SquareWave,
+    BipolarSquareWave,
+    /// `max(0, 1 - |x|)`.
+    Hat,
+    BipolarHat,
+    /// `1 / x`, which is infinite at 0.
+    Inverse,
+    Square,
+    Cube,
+    /// The natural logarithm, with `x` clipped to at least 1e-7.
+    Log,
+    /// The exponential function, with `x` clipped into [-60, 60].
+    Exp,
+    /// `sin(pi * x) / (pi * x)`, which is 1 at 0.
+    Sinc,
 }
 
 impl ActivationFunction for GeometricActivationFunction {
-    fn calculate(&self, x: f64) -> f64 {
+    fn calculate<F: Float>(&self, x: F) -> F {
+        let c = F::from_f64;
         match *self {
             GeometricActivationFunction::Linear => x,
             GeometricActivationFunction::LinearBipolarClipped => {
                 bipolar_debug_check(bipolar_clip(x))
             }
-            GeometricActivationFunction::LinearClipped => x.min(1.0).max(0.0),
+            GeometricActivationFunction::LinearClipped => x.min(F::one()).max(F::zero()),
             GeometricActivationFunction::Absolute => x.abs(),
-            GeometricActivationFunction::Gaussian => (-((x * 2.5).powi(2))).exp(),
+            GeometricActivationFunction::Gaussian => (-((x * c(2.5)).powi(2))).exp(),
+            GeometricActivationFunction::BipolarGaussian => {
+                bipolar_debug_check(c(2.0) * (-((x * c(2.5)).powi(2))).exp() - F::one())
+            }
+            GeometricActivationFunction::BipolarSigmoid => {
+                bipolar_debug_check((c(2.0) / (F::one() + (c(-4.9) * x).exp())) - F::one())
+            }
+            GeometricActivationFunction::Sine => bipolar_debug_check((c(2.0 * PI) * x).sin()),
+            GeometricActivationFunction::Cosine => bipolar_debug_check((c(2.0 * PI) * x).cos()),
+            GeometricActivationFunction::Constant1 => F::one(),
+            GeometricActivationFunction::Tanh => bipolar_debug_check(x.tanh()),
+            GeometricActivationFunction::Relu => at_least(x, F::zero()),
+            GeometricActivationFunction::LeakyRelu => (c(0.01) * x).max(x),
+            GeometricActivationFunction::Elu => {
+                if x > F::zero() {
+                    x
+                } else {
+                    x.exp() - F::one()
+                }
+            }
+            GeometricActivationFunction::Softplus => {
+                // avoids the overflow of `exp(x)` for large `x`.
+                F::zero().max(x) + (F::one() + (-x.abs()).exp()).ln()
+            }
+            GeometricActivationFunction::Step => step(x),
+            GeometricActivationFunction::BipolarStep => c(2.0) * step(x) - F::one(),
+            GeometricActivationFunction::Sawtooth => fract(x),
+            GeometricActivationFunction::BipolarSawtooth => c(2.0) * fract(x) - F::one(),
+            GeometricActivationFunction::Triangle => {
+                F::one() - c(2.0) * (x - (x + c(0.5)).floor()).abs()
+            }
+            GeometricActivationFunction::BipolarTriangle => {
+                bipolar_debug_check(F::one() - c(4.0) * (x - (x + c(0.5)).floor()).abs())
+            }
+            GeometricActivationFunction::SquareWave => F::one() - step(fract(x) - c(0.5)),
+            GeometricActivationFunction::BipolarSquareWave => {
+                F::one() - c(2.0) * step(fract(x) - c(0.5))
+            }
+            GeometricActivationFunction::Hat => hat(x),
+            GeometricActivationFunction::BipolarHat => c(2.0) * hat(x) - F::one(),
+            GeometricActivationFunction::Inverse => F::one() / x,
+            GeometricActivationFunction::Square => x.powi(2),
+            GeometricActivationFunction::Cube => x.powi(3),
+            GeometricActivationFunction::Log => at_least(x, c(1e-7)).ln(),
+            GeometricActivationFunction::Exp => at_least(at_most(x, c(60.0)), c(-60.0)).exp(),
+            GeometricActivationFunction::Sinc => {
+                if x == F::zero() {
+                    F::one()
+                } else {
+                    (c(PI) * x).sin() / (c(PI) * x)
+                }
+            }
+        }
+    }
+
+    fn calculate_slice<F: Float>(&self, xs: &mut [F]) {
+        let c = F::from_f64;
+        match *self {
+            GeometricActivationFunction::Linear => {}
+            GeometricActivationFunction::LinearBipolarClipped => {
+                kernels::map_lanes(xs, bipolar_clip)
+            }
+            GeometricActivationFunction::LinearClipped => {
+                kernels::map_lanes(xs, |x| x.min(F::one()).max(F::zero()))
+            }
+            GeometricActivationFunction::Absolute => kernels::map_lanes(xs, |x| x.abs()),
+            GeometricActivationFunction::Gaussian => {
+                kernels::map_lanes(xs, |x| kernels::exp(-((x * c(2.5)) * (x * c(2.5)))))
+            }
+            GeometricActivationFunction::BipolarGaussian => kernels::map_lanes(xs, |x| {
+                c(2.0) * kernels::exp(-((x * c(2.5)) * (x * c(2.5)))) - F::one()
+            }),
+            GeometricActivationFunction::BipolarSigmoid => kernels::map_lanes(xs, |x| {
+                c(2.0) / (F::one() + kernels::exp(c(-4.9) * x)) - F::one()
+            }),
+            GeometricActivationFunction::Sine => {
+                kernels::map_lanes(xs, |x| kernels::sin(c(2.0 * PI) * x))
+            }
+            GeometricActivationFunction::Cosine => {
+                kernels::map_lanes(xs, |x| kernels::cos(c(2.0 * PI) * x))
+            }
+            GeometricActivationFunction::Constant1 => kernels::map_lanes(xs, |_| F::one()),
+            GeometricActivationFunction::Tanh => kernels::map_lanes(xs, |x| {
+                // saturates to 1 and -1, as `exp` yields infinity and 0.
+                F::one() - c(2.0) / (kernels::exp(c(2.0) * x) + F::one())
+            }),
+            GeometricActivationFunction::Relu => {
+                kernels::map_lanes(xs, |x| at_least(x, F::zero()))
+            }
+            GeometricActivationFunction::LeakyRelu => {
+                kernels::map_lanes(xs, |x| (c(0.01) * x).max(x))
+            }
+            GeometricActivationFunction::Elu => kernels::map_lanes(xs, |x| {
+                if x > F::zero() {
+                    x
+                } else {
+                    kernels::exp(x) - F::one()
+                }
+            }),
+            GeometricActivationFunction::Softplus => kernels::map_lanes(xs, |x| {
+                F::zero().max(x) + (F::one() + kernels::exp(-x.abs())).ln()
+            }),
+            GeometricActivationFunction::Step => kernels::map_lanes(xs, step),
+            GeometricActivationFunction::BipolarStep => {
+                kernels::map_lanes(xs, |x| c(2.0) * step(x) - F::one())
+            }
+            GeometricActivationFunction::Sawtooth => kernels::map_lanes(xs, fract),
+            GeometricActivationFunction::BipolarSawtooth => {
+                kernels::map_lanes(xs, |x| c(2.0) * fract(x) - F::one())
+            }
+            GeometricActivationFunction::Triangle => kernels::map_lanes(xs, |x| {
+                F::one() - c(2.0) * (x - (x + c(0.5)).floor()).abs()
+            }),
+            GeometricActivationFunction::BipolarTriangle => kernels::map_lanes(xs, |x| {
+                F::one() - c(4.0) * (x - (x + c(0.5)).floor()).abs()
+            }),
+            GeometricActivationFunction::SquareWave => {
+                kernels::map_lanes(xs, |x| F::one() - step(fract(x) - c(0.5)))
+            }
+            GeometricActivationFunction::BipolarSquareWave => {
+                kernels::map_lanes(xs, |x| F::one() - c(2.0) * step(fract(x) - c(0.5)))
+            }
+            GeometricActivationFunction::Hat => kernels::map_lanes(xs, hat),
+            GeometricActivationFunction::BipolarHat => {
+                kernels::map_lanes(xs, |x| c(2.0) * hat(x) - F::one())
+            }
+            GeometricActivationFunction::Inverse => kernels::map_lanes(xs, |x| F::one() / x),
+            GeometricActivationFunction::Square => kernels::map_lanes(xs, |x| x * x),
+            GeometricActivationFunction::Cube => kernels::map_lanes(xs, |x| x * x * x),
+            GeometricActivationFunction::Log => {
+                kernels::map_lanes(xs, |x| at_least(x, c(1e-7)).ln())
+            }
+            GeometricActivationFunction::Exp => kernels::map_lanes(xs, |x| {
+                kernels::exp(at_least(at_most(x, c(60.0)), c(-60.0)))
+            }),
+            GeometricActivationFunction::Sinc => kernels::map_lanes(xs, |x| {
+                if x == F::zero() {
+                    F::one()
+                } else {
+                    kernels::sin(c(PI) * x) / (c(PI) * x)
+                }
+            }),
+        }
+    }
+
+    fn derivative<F: Float>(&self, x: F) -> F {
+        let c = F::from_f64;
+        // 1 within [min, max], 0 outside.
+        let within = |min: F, max: F| if x >= min && x <= max { F::one() } else { F::zero() };
+        // falling after integers, rising before them.
+        let triangle_slope = || {
+            if x - (x + c(0.5)).floor() > F::zero() {
+                -F::one()
+            } else {
+                F::one()
+            }
+        };
+        let hat_slope = || {
+            if x.abs() >= F::one() {
+                F::zero()
+            } else if x > F::zero() {
+                -F::one()
+            } else {
+                F::one()
+            }
+        };
+        match *self {
+            GeometricActivationFunction::Linear => F::one(),
+            GeometricActivationFunction::LinearBipolarClipped => within(-F::one(), F::one()),
+            GeometricActivationFunction::LinearClipped => within(F::zero(), F::one()),
+            GeometricActivationFunction::Absolute => {
+                if x > F::zero() {
+                    F::one()
+                } else if x < F::zero() {
+                    -F::one()
+                } else {
+                    F::zero()
+                }
+            }
+            GeometricActivationFunction::Gaussian => {
+                c(-12.5) * x * (-((x * c(2.5)).powi(2))).exp()
+            }
             GeometricActivationFunction::BipolarGaussian => {
-                bipolar_debug_check(2.0 * (-((x * 2.5).powi(2))).exp() - 1.0)
+                c(-25.0) * x * (-((x * c(2.5)).powi(2))).exp()
             }
             GeometricActivationFunction::BipolarSigmoid => {
-                bipolar_debug_check((2.0 / (1.0 + (-4.9 * x).exp())) - 1.0)
+                // the bipolar sigmoid is tanh(2.45 * x).
+                let y = self.calculate(x);
+                c(2.45) * (F::one() - y * y)
+            }
+            GeometricActivationFunction::Sine => c(2.0 * PI) * (c(2.0 * PI) * x).cos(),
+            GeometricActivationFunction::Cosine => -c(2.0 * PI) * (c(2.0 * PI) * x).sin(),
+            GeometricActivationFunction::Constant1 => F::zero(),
+            GeometricActivationFunction::Tanh => {
+                let y = x.tanh();
+                F::one() - y * y
+            }
+            GeometricActivationFunction::Relu => {
+                if x > F::zero() {
+                    F::one()
+                } else {
+                    F::zero()
+                }
+            }
+            GeometricActivationFunction::LeakyRelu => {
+                if x > F::zero() {
+                    F::one()
+                } else {
+                    c(0.01)
+                }
+            }
+            GeometricActivationFunction::Elu => {
+                if x > F::zero() {
+                    F::one()
+                } else {
+                    x.exp()
+                }
+            }
+            GeometricActivationFunction::Softplus => F::one() / (F::one() + (-x).exp()),
+            GeometricActivationFunction::Step
+            | GeometricActivationFunction::BipolarStep
+            | GeometricActivationFunction::SquareWave
+            | GeometricActivationFunction::BipolarSquareWave => F::zero(),
+            GeometricActivationFunction::Sawtooth => F::one(),
+            GeometricActivationFunction::BipolarSawtooth => c(2.0),
+            GeometricActivationFunction::Triangle => c(2.0) * triangle_slope(),
+            GeometricActivationFunction::BipolarTriangle => c(4.0) * triangle_slope(),
+            GeometricActivationFunction::Hat => hat_slope(),
+            GeometricActivationFunction::BipolarHat => c(2.0) * hat_slope(),
+            GeometricActivationFunction::Inverse => -F::one() / (x * x),
+            GeometricActivationFunction::Square => c(2.0) * x,
+            GeometricActivationFunction::Cube => c(3.0) * x * x,
+            GeometricActivationFunction::Log => {
+                if x >= c(1e-7) {
+                    F::one() / x
+                } else {
+                    F::zero()
+                }
+            }
+            GeometricActivationFunction::Exp => {
+                // `within(..) * x.exp()` would be `0 * inf` for large `x`.
+                if x >= c(-60.0) && x <= c(60.0) {
+                    x.exp()
+                } else {
+                    F::zero()
+                }
+            }
+            GeometricActivationFunction::Sinc => {
+                if x == F::zero() {
+                    F::zero()
+                } else {
+                    ((c(PI) * x).cos() - self.calculate(x)) / x
+                }
+            }
+        }
+    }
+
+    fn calculate_interval<F: Float>(&self, x: Interval<F>) -> Interval<F> {
+        let c = F::from_f64;
+        let f = |x| self.calculate(x);
+        match *self {
+            GeometricActivationFunction::Linear
+            | GeometricActivationFunction::LinearBipolarClipped
+            | GeometricActivationFunction::LinearClipped
+            | GeometricActivationFunction::BipolarSigmoid
+            | GeometricActivationFunction::Tanh
+            | GeometricActivationFunction::Relu
+            | GeometricActivationFunction::LeakyRelu
+            | GeometricActivationFunction::Elu
+            | GeometricActivationFunction::Softplus
+            | GeometricActivationFunction::Step
+            | GeometricActivationFunction::BipolarStep
+            | GeometricActivationFunction::Cube
+            | GeometricActivationFunction::Log
+            | GeometricActivationFunction::Exp => x.map_increasing(f),
+            GeometricActivationFunction::Absolute => x.abs(),
+            GeometricActivationFunction::Square => {
+                let a = x.abs();
+                Interval::new(a.lo.powi(2), a.hi.powi(2))
+            }
+            GeometricActivationFunction::Gaussian
+            | GeometricActivationFunction::BipolarGaussian
+            | GeometricActivationFunction::Hat
+            | GeometricActivationFunction::BipolarHat => {
+                // decreasing in |x|.
+                let a = x.abs();
+                Interval::new(f(a.hi), f(a.lo))
+            }
+            GeometricActivationFunction::Sine => x.map_periodic(f, c(0.25), -F::one(), F::one()),
+            GeometricActivationFunction::Cosine => {
+                x.map_periodic(f, F::zero(), -F::one(), F::one())
+            }
+            GeometricActivationFunction::Constant1 => Interval::point(F::one()),
+            GeometricActivationFunction::Sawtooth
+            | GeometricActivationFunction::BipolarSawtooth => {
+                // increasing within each period.
+                if x.lo.floor() == x.hi.floor() {
+                    x.map_increasing(f)
+                } else {
+                    Interval::new(f(F::zero()), F::one())
+                }
+            }
+            GeometricActivationFunction::Triangle => {
+                x.map_periodic(f, F::zero(), F::zero(), F::one())
+            }
+            GeometricActivationFunction::BipolarTriangle => {
+                x.map_periodic(f, F::zero(), -F::one(), F::one())
+            }
+            GeometricActivationFunction::SquareWave
+            | GeometricActivationFunction::BipolarSquareWave => {
+                // constant within each half period.
+                if (c(2.0) * x.lo).floor() == (c(2.0) * x.hi).floor() {
+                    Interval::point(f(x.lo))
+                } else {
+                    Interval::new(f(c(0.5)), f(F::zero()))
+                }
+            }
+            GeometricActivationFunction::Inverse => {
+                // decreasing on both sides of the pole at 0.
+                if x.lo > F::zero() || x.hi < F::zero() {
+                    Interval::new(f(x.hi), f(x.lo))
+                } else {
+                    Interval::entire()
+                }
+            }
+            GeometricActivationFunction::Sinc => {
+                // even and decreasing on [0, 1]. Beyond 1, the values lie within
+                // [-0.2173, 0.1284] (the first trough and the second peak).
+                let a = x.abs();
+                if a.hi <= F::one() {
+                    Interval::new(f(a.hi).min(f(a.lo)), f(a.lo).max(f(a.hi)))
+                } else {
+                    Interval::new(c(-0.2173), f(a.lo).max(c(0.1284)))
+                }
+            }
+        }
+    }
+
+    fn expr(&self, x: Expr) -> Expr {
+        let c = Expr::Const;
+        match *self {
+            GeometricActivationFunction::Linear => x,
+            GeometricActivationFunction::LinearBipolarClipped => c(-1.0).max(c(1.0).min(x)),
+            GeometricActivationFunction::LinearClipped => c(0.0).max(c(1.0).min(x)),
+            GeometricActivationFunction::Absolute => x.abs(),
+            GeometricActivationFunction::Gaussian => (-(x * c(2.5)).powi(2)).exp(),
+            GeometricActivationFunction::BipolarGaussian => {
+                c(2.0) * (-(x * c(2.5)).powi(2)).exp() - c(1.0)
+            }
+            GeometricActivationFunction::BipolarSigmoid => {
+                c(2.0) / (c(1.0) + (c(-4.9) * x).exp()) - c(1.0)
+            }
+            GeometricActivationFunction::Sine => (c(2.0 * PI) * x).sin(),
+            GeometricActivationFunction::Cosine => (c(2.0 * PI) * x).cos(),
+            GeometricActivationFunction::Constant1 => c(1.0),
+            GeometricActivationFunction::Tanh => x.tanh(),
+            GeometricActivationFunction::Relu => c(0.0).max(x),
+            GeometricActivationFunction::LeakyRelu => (c(0.01) * x.clone()).max(x),
+            GeometricActivationFunction::Elu => {
+                c(0.0).max(x.clone()) + c(0.0).min(x.min(c(0.0)).exp() - c(1.0))
+            }
+            GeometricActivationFunction::Softplus => {
+                c(0.0).max(x.clone()) + (c(1.0) + (-x.abs()).exp()).log()
+            }
+            GeometricActivationFunction::Step => x.step(),
+            GeometricActivationFunction::BipolarStep => c(2.0) * x.step() - c(1.0),
+            GeometricActivationFunction::Sawtooth => x.clone() - x.floor(),
+            GeometricActivationFunction::BipolarSawtooth => {
+                c(2.0) * (x.clone() - x.floor()) - c(1.0)
+            }
+            GeometricActivationFunction::Triangle => {
+                c(1.0) - c(2.0) * (x.clone() - (x + c(0.5)).floor()).abs()
+            }
+            GeometricActivationFunction::BipolarTriangle => {
+                c(1.0) - c(4.0) * (x.clone() - (x + c(0.5)).floor()).abs()
+            }
+            GeometricActivationFunction::SquareWave => {
+                c(1.0) - (x.clone() - x.floor() - c(0.5)).step()
+            }
+            GeometricActivationFunction::BipolarSquareWave => {
+                c(1.0) - c(2.0) * (x.clone() - x.floor() - c(0.5)).step()
+            }
+            GeometricActivationFunction::Hat => c(0.0).max(c(1.0) - x.abs()),
+            GeometricActivationFunction::BipolarHat => {
+                c(2.0) * c(0.0).max(c(1.0) - x.abs()) - c(1.0)
+            }
+            GeometricActivationFunction::Inverse => c(1.0) / x,
+            GeometricActivationFunction::Square => x.powi(2),
+            GeometricActivationFunction::Cube => x.powi(3),
+            GeometricActivationFunction::Log => c(1e-7).max(x).log(),
+            GeometricActivationFunction::Exp => c(-60.0).max(c(60.0).min(x)).exp(),
+            GeometricActivationFunction::Sinc => {
+                // `at_zero` is 1 at 0 and 0 elsewhere, which avoids the division by zero.
+                let at_zero = (-x.clone().abs()).step();
+                (c(PI) * x.clone()).sin() / (c(PI) * x + at_zero.clone()) + at_zero
             }
-            GeometricActivationFunction::Sine => bipolar_debug_check((2.0 * PI * x).sin()),
-            GeometricActivationFunction::Cosine => bipolar_debug_check(2.0 * PI * x.cos()),
-            GeometricActivationFunction::Constant1 => 1.0,
         }
     }
 
@@ -76,9 +576,46 @@ impl ActivationFunction for GeometricActivationFunction {
             GeometricActivationFunction::BipolarSigmoid => {
                 format!("2.0 / (1.0 + exp(-4.9 * ({}))) - 1.0", x)
             }
-            GeometricActivationFunction::Sine => format!("sin({})", x),
-            GeometricActivationFunction::Cosine => format!("cos({})", x),
+            GeometricActivationFunction::Sine => format!("sin(2.0 * pi * ({}))", x),
+            GeometricActivationFunction::Cosine => format!("cos(2.0 * pi * ({}))", x),
             GeometricActivationFunction::Constant1 => format!("1.0"),
+            GeometricActivationFunction::Tanh => format!("tanh({})", x),
+            GeometricActivationFunction::Relu => format!("max(0.0, {})", x),
+            GeometricActivationFunction::LeakyRelu => format!("max(0.01 * ({0}), {0})", x),
+            GeometricActivationFunction::Elu => {
+                format!("(({0}) > 0.0 ? ({0}) : exp({0}) - 1.0)", x)
+            }
+            GeometricActivationFunction::Softplus => format!("log(1.0 + exp({}))", x),
+            GeometricActivationFunction::Step => format!("(({}) >= 0.0 ? 1.0 : 0.0)", x),
+            GeometricActivationFunction::BipolarStep => format!("(({}) >= 0.0 ? 1.0 : -1.0)", x),
+            GeometricActivationFunction::Sawtooth => format!("(({0}) - floor({0}))", x),
+            GeometricActivationFunction::BipolarSawtooth => {
+                format!("2.0 * (({0}) - floor({0})) - 1.0", x)
+            }
+            GeometricActivationFunction::Triangle => {
+                format!("1.0 - 2.0 * abs(({0}) - floor(({0}) + 0.5))", x)
+            }
+            GeometricActivationFunction::BipolarTriangle => {
+                format!("1.0 - 4.0 * abs(({0}) - floor(({0}) + 0.5))", x)
+            }
+            GeometricActivationFunction::SquareWave => {
+                format!("(({0}) - floor({0}) < 0.5 ? 1.0 : 0.0)", x)
+            }
+            GeometricActivationFunction::BipolarSquareWave => {
+                format!("(({0}) - floor({0}) < 0.5 ? 1.0 : -1.0)", x)
+            }
+            GeometricActivationFunction::Hat => format!("max(0.0, 1.0 - abs({}))", x),
+            GeometricActivationFunction::BipolarHat => {
+                format!("2.0 * max(0.0, 1.0 - abs({})) - 1.0", x)
+            }
+            GeometricActivationFunction::Inverse => format!("1.0 / ({})", x),
+            GeometricActivationFunction::Square => format!("({})**2.0", x),
+            GeometricActivationFunction::Cube => format!("({})**3.0", x),
+            GeometricActivationFunction::Log => format!("log(max(1.0e-7, {}))", x),
+            GeometricActivationFunction::Exp => format!("exp(max(-60.0, min(60.0, {})))", x),
+            GeometricActivationFunction::Sinc => {
+                format!("(({0}) == 0.0 ? 1.0 : sin(pi * ({0})) / (pi * ({0})))", x)
+            }
         }
     }
 
@@ -94,6 +631,27 @@ impl ActivationFunction for GeometricActivationFunction {
             GeometricActivationFunction::Sine => "Sine",
             GeometricActivationFunction::Cosine => "Consine",
             GeometricActivationFunction::Constant1 => "1.0",
+            GeometricActivationFunction::Tanh => "Tanh",
+            GeometricActivationFunction::Relu => "Relu",
+            GeometricActivationFunction::LeakyRelu => "LeakyRelu",
+            GeometricActivationFunction::Elu => "Elu",
+            GeometricActivationFunction::Softplus => "Softplus",
+            GeometricActivationFunction::Step => "Step",
+            GeometricActivationFunction::BipolarStep => "BipolarStep",
+            GeometricActivationFunction::Sawtooth => "Sawtooth",
+            GeometricActivationFunction::BipolarSawtooth => "BipolarSawtooth",
+            GeometricActivationFunction::Triangle => "Triangle",
+            GeometricActivationFunction::BipolarTriangle => "BipolarTriangle",
+            GeometricActivationFunction::SquareWave => "SquareWave",
+            GeometricActivationFunction::BipolarSquareWave => "BipolarSquareWave",
+            GeometricActivationFunction::Hat => "Hat",
+            GeometricActivationFunction::BipolarHat => "BipolarHat",
+            GeometricActivationFunction::Inverse => "Inverse",
+            GeometricActivationFunction::Square => "Square",
+            GeometricActivationFunction::Cube => "Cube",
+            GeometricActivationFunction::Log => "Log",
+            GeometricActivationFunction::Exp => "Exp",
+            GeometricActivationFunction::Sinc => "Sinc",
         }.to_string()
     }
 }
@@ -168,3 +726,297 @@ fn test_constant1() {
     assert_eq!(1.0, GeometricActivationFunction::Constant1.calculate(-1.0));
     assert_eq!(1.0, GeometricActivationFunction::Constant1.calculate(1.0));
 }
+
+#[test]
+fn test_f32_matches_f64() {
+    let functions = [
+        GeometricActivationFunction::Linear,
+        GeometricActivationFunction::LinearBipolarClipped,
+        GeometricActivationFunction::LinearClipped,
+        GeometricActivationFunction::Absolute,
+        GeometricActivationFunction::Gaussian,
+        GeometricActivationFunction::BipolarGaussian,
+        GeometricActivationFunction::BipolarSigmoid,
+        GeometricActivationFunction::Sine,
+        GeometricActivationFunction::Cosine,
+        GeometricActivationFunction::Constant1,
+    ];
+    for f in functions.iter() {
+        for &x in &[-1.5f64, -0.3, 0.0, 0.2, 0.9] {
+            let expected = f.calculate(x);
+            let actual = f.calculate(x as f32) as f64;
+            assert!((expected - actual).abs() < 1e-6, "{}", f.name());
+        }
+    }
+}
+
+#[test]
+fn test_cosine_is_bipolar() {
+    assert_eq!(1.0, GeometricActivationFunction::Cosine.calculate(0.0));
+    assert!((GeometricActivationFunction::Cosine.calculate(0.5) + 1.0).abs() < 1e-12);
+    assert!(GeometricActivationFunction::Cosine.calculate(0.25).abs() < 1e-12);
+}
+
+#[test]
+fn test_periodic_formulas() {
+    assert_eq!(
+        "sin(2.0 * pi * (x))",
+        GeometricActivationFunction::Sine.formula_gnuplot("x".to_string())
+    );
+    assert_eq!(
+        "cos(2.0 * pi * (x))",
+        GeometricActivationFunction::Cosine.formula_gnuplot("x".to_string())
+    );
+}
+
+#[cfg(test)]
+pub const ALL_FUNCTIONS: [GeometricActivationFunction; 31] = [
+    GeometricActivationFunction::Linear,
+    GeometricActivationFunction::LinearBipolarClipped,
+    GeometricActivationFunction::LinearClipped,
+    GeometricActivationFunction::Absolute,
+    GeometricActivationFunction::Gaussian,
+    GeometricActivationFunction::BipolarGaussian,
+    GeometricActivationFunction::BipolarSigmoid,
+    GeometricActivationFunction::Sine,
+    GeometricActivationFunction::Cosine,
+    GeometricActivationFunction::Constant1,
+    GeometricActivationFunction::Tanh,
+    GeometricActivationFunction::Relu,
+    GeometricActivationFunction::LeakyRelu,
+    GeometricActivationFunction::Elu,
+    GeometricActivationFunction::Softplus,
+    GeometricActivationFunction::Step,
+    GeometricActivationFunction::BipolarStep,
+    GeometricActivationFunction::Sawtooth,
+    GeometricActivationFunction::BipolarSawtooth,
+    GeometricActivationFunction::Triangle,
+    GeometricActivationFunction::BipolarTriangle,
+    GeometricActivationFunction::SquareWave,
+    GeometricActivationFunction::BipolarSquareWave,
+    GeometricActivationFunction::Hat,
+    GeometricActivationFunction::BipolarHat,
+    GeometricActivationFunction::Inverse,
+    GeometricActivationFunction::Square,
+    GeometricActivationFunction::Cube,
+    GeometricActivationFunction::Log,
+    GeometricActivationFunction::Exp,
+    GeometricActivationFunction::Sinc,
+];
+
+#[test]
+fn test_calculate_slice_matches_calculate() {
+    let functions = ALL_FUNCTIONS;
+    let mut xs: Vec<f64> = (0..1001).map(|i| -5.0 + 0.01 * (i as f64)).collect();
+    // the clipped arguments of `Exp` and `Log`, and the saturation of `Tanh`.
+    xs.extend([-800.0, -61.0, -20.0, 1e-9, 20.0, 61.0, 800.0].iter().cloned());
+    for f in functions.iter() {
+        // relative to the result, as `Exp` grows large.
+        let tolerance = |expected: f64, tolerance: f64| tolerance * expected.abs().max(1.0);
+
+        let mut ys = xs.clone();
+        f.calculate_slice(&mut ys);
+        for (&x, &y) in xs.iter().zip(ys.iter()) {
+            let expected = f.calculate(x);
+            assert!(
+                expected == y || (expected - y).abs() < tolerance(expected, 1e-13),
+                "{}({})",
+                f.name(),
+                x
+            );
+        }
+
+        let mut ys: Vec<f32> = xs.iter().map(|&x| x as f32).collect();
+        f.calculate_slice(&mut ys);
+        for (&x, &y) in xs.iter().zip(ys.iter()) {
+            // compared in single precision, as the rounding of `x` matters at jumps and poles,
+            // and for the periodic functions of large `x`.
+            let expected = f.calculate(x as f32) as f64;
+            assert!(
+                expected == y as f64 || (expected - y as f64).abs() < tolerance(expected, 1e-5),
+                "{}({})",
+                f.name(),
+                x
+            );
+        }
+    }
+}
+
+#[test]
+fn test_nan_is_passed_through() {
+    use self::GeometricActivationFunction as G;
+    // the functions added after `Constant1`, which used to clip NaN to a number.
+    let functions = ALL_FUNCTIONS.iter().skip_while(|&&f| f != G::Tanh);
+    for &f in functions {
+        assert!(f.calculate(f64::NAN).is_nan(), "{}", f.name());
+        assert!(f.calculate(f32::NAN).is_nan(), "{}", f.name());
+        let mut xs = [0.5, f64::NAN, -0.5];
+        f.calculate_slice(&mut xs);
+        assert!(xs[1].is_nan() && !xs[0].is_nan(), "{}", f.name());
+        let mut xs = [f32::NAN; 9];
+        f.calculate_slice(&mut xs);
+        assert!(xs.iter().all(|x| x.is_nan()), "{}", f.name());
+    }
+}
+
+#[test]
+fn test_exp_derivative_outside_clipping() {
+    use self::GeometricActivationFunction as G;
+    assert_eq!(0.0, G::Exp.derivative(800.0));
+    assert_eq!(0.0, G::Exp.derivative(-800.0));
+    assert_eq!(0.0, G::Exp.derivative(f64::INFINITY));
+    assert_eq!(0.0f32, G::Exp.derivative(100.0f32));
+    assert_eq!((60.0f64).exp(), G::Exp.derivative(60.0));
+}
+
+#[test]
+fn test_derivative_matches_finite_difference() {
+    // A plain `ActivationFunction` without an exact derivative.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct Numeric(GeometricActivationFunction);
+
+    impl ActivationFunction for Numeric {
+        fn formula_gnuplot(&self, x: String) -> String {
+            self.0.formula_gnuplot(x)
+        }
+        fn name(&self) -> String {
+            self.0.name()
+        }
+        fn calculate<F: Float>(&self, x: F) -> F {
+            self.0.calculate(x)
+        }
+    }
+
+    let xs: Vec<f64> = (0..400).map(|i| -2.0 + 0.01 * (i as f64)).collect();
+    for &f in ALL_FUNCTIONS.iter() {
+        let numeric = Numeric(f);
+        for &x in xs.iter() {
+            // skip the kinks of the clipped and absolute functions.
+            if (x.abs() - 1.0).abs() < 0.05 || x.abs() < 0.05 {
+                continue;
+            }
+            // and the kinks and jumps of the waves and the poles, where the central difference
+            // is inaccurate.
+            let skip = match f {
+                GeometricActivationFunction::Sawtooth
+                | GeometricActivationFunction::BipolarSawtooth
+                | GeometricActivationFunction::Triangle
+                | GeometricActivationFunction::BipolarTriangle
+                | GeometricActivationFunction::SquareWave
+                | GeometricActivationFunction::BipolarSquareWave => {
+                    ((2.0 * x).round() - 2.0 * x).abs() < 0.1
+                }
+                GeometricActivationFunction::Inverse | GeometricActivationFunction::Log => {
+                    x.abs() < 0.3
+                }
+                _ => false,
+            };
+            if skip {
+                continue;
+            }
+            let expected = numeric.derivative(x);
+            assert!((f.derivative(x) - expected).abs() < 1e-6, "{}'({})", f.name(), x);
+            let expected = numeric.derivative(x as f32);
+            assert!(
+                (f.derivative(x as f32) - expected).abs() < 2e-2,
+                "{}'({})",
+                f.name(),
+                x
+            );
+        }
+    }
+}
+
+#[test]
+fn test_calculate_interval_contains_samples() {
+    let intervals = [
+        (-1.0, 1.0),
+        (-0.3, -0.1),
+        (0.05, 0.2),
+        (0.2, 0.3),
+        (0.7, 0.8),
+        (-2.0, 5.0),
+        (0.5, 0.5),
+        (-0.6, 0.4),
+    ];
+    for &f in ALL_FUNCTIONS.iter() {
+        for &(lo, hi) in intervals.iter() {
+            let bounds = f.calculate_interval(Interval::new(lo, hi));
+            let mut min = f64::INFINITY;
+            let mut max = f64::NEG_INFINITY;
+            for i in 0..=1000 {
+                let y = f.calculate(lo + (hi - lo) * (i as f64) / 1000.0);
+                assert!(bounds.contains(y), "{}({}, {}) = {}", f.name(), lo, hi, y);
+                min = min.min(y);
+                max = max.max(y);
+            }
+            // the bounds are tight, unless the function is unbounded (or has no bounds at all).
+            if bounds.lo.is_finite() {
+                assert!(min - bounds.lo < 1e-2, "{}({}, {})", f.name(), lo, hi);
+            }
+            if bounds.hi.is_finite() {
+                assert!(bounds.hi - max < 1e-2, "{}({}, {})", f.name(), lo, hi);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_expr_matches_calculate() {
+    for &f in ALL_FUNCTIONS.iter() {
+        let expr = f.expr(Expr::Input(0));
+        for &x in [-1.5, -0.7, -0.2, 0.0, 0.1, 0.45, 1.0, 3.0].iter() {
+            assert_eq!(f.calculate(x), expr.eval(&[x], &[]), "{}({})", f.name(), x);
+        }
+    }
+}
+
+#[test]
+fn test_output_ranges() {
+    use self::GeometricActivationFunction as G;
+    let inf = f64::INFINITY;
+    let ranges = [
+        (G::Tanh, -1.0, 1.0),
+        (G::Relu, 0.0, inf),
+        (G::LeakyRelu, -inf, inf),
+        (G::Elu, -1.0, inf),
+        (G::Softplus, 0.0, inf),
+        (G::Step, 0.0, 1.0),
+        (G::BipolarStep, -1.0, 1.0),
+        (G::Sawtooth, 0.0, 1.0),
+        (G::BipolarSawtooth, -1.0, 1.0),
+        (G::Triangle, 0.0, 1.0),
+        (G::BipolarTriangle, -1.0, 1.0),
+        (G::SquareWave, 0.0, 1.0),
+        (G::BipolarSquareWave, -1.0, 1.0),
+        (G::Hat, 0.0, 1.0),
+        (G::BipolarHat, -1.0, 1.0),
+        (G::Inverse, -inf, inf),
+        (G::Square, 0.0, inf),
+        (G::Cube, -inf, inf),
+        (G::Log, (1e-7f64).ln(), inf),
+        (G::Exp, 0.0, (60.0f64).exp()),
+        (G::Sinc, -0.22, 1.0),
+    ];
+    let xs: Vec<f64> = (0..=2000).map(|i| -10.0 + 0.01 * (i as f64)).collect();
+    for &(f, min, max) in ranges.iter() {
+        let mut ys: Vec<f64> = xs.iter().map(|&x| f.calculate(x)).collect();
+        ys.extend([-1e9, 1e9].iter().map(|&x| f.calculate(x)));
+        for &y in ys.iter() {
+            assert!(y >= min && y <= max, "{} = {} not in [{}, {}]", f.name(), y, min, max);
+        }
+        // the bounded functions reach their bounds.
+        let reached = |bound: f64| ys.iter().any(|&y| (y - bound).abs() < 0.05);
+        assert!(!min.is_finite() || reached(min), "{}", f.name());
+        assert!(!max.is_finite() || reached(max), "{}", f.name());
+    }
+
+    assert_eq!(1.0, G::Sinc.calculate(0.0));
+    assert_eq!(0.0, G::Relu.calculate(-2.0));
+    assert_eq!(-0.02, G::LeakyRelu.calculate(-2.0));
+    assert_eq!(0.5, G::Sawtooth.calculate(2.5));
+    assert_eq!(0.0, G::Triangle.calculate(-0.5));
+    assert_eq!(1.0, G::SquareWave.calculate(1.25));
+    assert_eq!(0.0, G::SquareWave.calculate(1.75));
+    assert_eq!(0.25, G::Inverse.calculate(4.0));
+}
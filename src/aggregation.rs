@@ -0,0 +1,264 @@
+use expr::Expr;
+use float::Float;
+use interval::Interval;
+use std::cmp::Ordering;
+
+/// Determines how a node combines its weighted incoming signals into the value which is passed
+/// to its activation function.
+///
+/// All aggregations of a single value return that value. A node without incoming links
+/// aggregates to 0.0 (or to its input value for input and bias nodes), whatever its aggregation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Aggregation {
+    #[default]
+    Sum,
+    Product,
+    Max,
+    Min,
+    Mean,
+    Median,
+    /// The value with the largest absolute value (keeping its sign).
+    MaxAbs,
+}
+
+impl Aggregation {
+    /// All aggregations, e.g. to choose one at random when mutating a node.
+    pub const ALL: [Aggregation; 7] = [
+        Aggregation::Sum,
+        Aggregation::Product,
+        Aggregation::Max,
+        Aggregation::Min,
+        Aggregation::Mean,
+        Aggregation::Median,
+        Aggregation::MaxAbs,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Aggregation::Sum => "sum",
+            Aggregation::Product => "product",
+            Aggregation::Max => "max",
+            Aggregation::Min => "min",
+            Aggregation::Mean => "mean",
+            Aggregation::Median => "median",
+            Aggregation::MaxAbs => "maxabs",
+        }
+    }
+
+    /// Aggregates `values`, which are evaluated from left to right. Returns 0.0 if there are no
+    /// values.
+    pub fn aggregate<F, I>(&self, values: I) -> F
+    where
+        F: Float,
+        I: IntoIterator<Item = F>,
+    {
+        let mut values = values.into_iter();
+        let first = match values.next() {
+            Some(first) => first,
+            None => return F::zero(),
+        };
+        match *self {
+            Aggregation::Sum => values.fold(first, |a, b| a + b),
+            Aggregation::Product => values.fold(first, |a, b| a * b),
+            Aggregation::Max => values.fold(first, |a, b| a.max(b)),
+            Aggregation::Min => values.fold(first, |a, b| a.min(b)),
+            Aggregation::Mean => {
+                let (sum, count) = values.fold((first, 1), |(sum, count), b| (sum + b, count + 1));
+                sum / F::from_f64(count as f64)
+            }
+            Aggregation::Median => {
+                let mut sorted: Vec<F> = Some(first).into_iter().chain(values).collect();
+                sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+                let n = sorted.len();
+                if n % 2 == 1 {
+                    sorted[n / 2]
+                } else {
+                    (sorted[n / 2 - 1] + sorted[n / 2]) / F::from_f64(2.0)
+                }
+            }
+            Aggregation::MaxAbs => values.fold(first, |a, b| if b.abs() > a.abs() { b } else { a }),
+        }
+    }
+
+    /// Stores the partial derivative of `aggregate(values)` with respect to each value in
+    /// `partials`.
+    ///
+    /// Aggregations which select one of the values (`Max`, `Min`, `MaxAbs` and `Median`) have
+    /// the derivative 1.0 with respect to the selected value and 0.0 with respect to all others.
+    pub fn partials<F: Float>(&self, values: &[F], partials: &mut [F]) {
+        assert!(values.len() == partials.len());
+        let n = values.len();
+        for partial in partials.iter_mut() {
+            *partial = F::zero();
+        }
+        if n == 0 {
+            return;
+        }
+        match *self {
+            Aggregation::Sum => {
+                for partial in partials.iter_mut() {
+                    *partial = F::one();
+                }
+            }
+            Aggregation::Product => {
+                for (k, partial) in partials.iter_mut().enumerate() {
+                    *partial = values
+                        .iter()
+                        .enumerate()
+                        .filter(|&(j, _)| j != k)
+                        .fold(F::one(), |p, (_, &v)| p * v);
+                }
+            }
+            Aggregation::Mean => {
+                for partial in partials.iter_mut() {
+                    *partial = F::one() / F::from_f64(n as f64);
+                }
+            }
+            Aggregation::Max | Aggregation::Min | Aggregation::MaxAbs => {
+                let selected = self.aggregate(values.iter().cloned());
+                if let Some(k) = values.iter().position(|&v| v == selected) {
+                    partials[k] = F::one();
+                }
+            }
+            Aggregation::Median => {
+                let mut order: Vec<usize> = (0..n).collect();
+                order
+                    .sort_by(|&a, &b| values[a].partial_cmp(&values[b]).unwrap_or(Ordering::Equal));
+                if n % 2 == 1 {
+                    partials[order[n / 2]] = F::one();
+                } else {
+                    let half = F::from_f64(0.5);
+                    partials[order[n / 2 - 1]] += half;
+                    partials[order[n / 2]] += half;
+                }
+            }
+        }
+    }
+
+    /// Bounds on `aggregate` for all values within `values`.
+    pub fn aggregate_interval<F: Float>(&self, values: &[Interval<F>]) -> Interval<F> {
+        if values.is_empty() {
+            return Interval::point(F::zero());
+        }
+        let los = || values.iter().map(|v| v.lo);
+        let his = || values.iter().map(|v| v.hi);
+        match *self {
+            Aggregation::Sum => values[1..].iter().fold(values[0], |a, &b| a + b),
+            Aggregation::Product => values[1..].iter().fold(values[0], |a, &b| a * b),
+            // monotonically increasing in each value.
+            Aggregation::Max | Aggregation::Min | Aggregation::Mean | Aggregation::Median => {
+                Interval::new(self.aggregate(los()), self.aggregate(his()))
+            }
+            // one of the values.
+            Aggregation::MaxAbs => values[1..].iter().fold(values[0], |a, b| a.hull(b)),
+        }
+    }
+
+    /// The aggregation of `values` as an expression.
+    pub fn expr(&self, values: Vec<Expr>) -> Expr {
+        let fold = |f: &dyn Fn(Expr, Expr) -> Expr| {
+            let mut values = values.clone().into_iter();
+            let first = values.next().unwrap_or(Expr::Const(0.0));
+            values.fold(first, f)
+        };
+        match *self {
+            Aggregation::Sum => fold(&|a, b| a + b),
+            Aggregation::Product => fold(&|a, b| a * b),
+            Aggregation::Max => fold(&|a, b| a.max(b)),
+            Aggregation::Min => fold(&|a, b| a.min(b)),
+            Aggregation::Mean if values.is_empty() => Expr::Const(0.0),
+            Aggregation::Mean => fold(&|a, b| a + b) / Expr::Const(values.len() as f64),
+            Aggregation::Median | Aggregation::MaxAbs => match values.len() {
+                0 => Expr::Const(0.0),
+                1 => values.into_iter().next().unwrap(),
+                _ => Expr::Aggregate(*self, values),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Aggregation;
+    use expr::Expr;
+    use interval::Interval;
+
+    #[test]
+    fn test_aggregate() {
+        let values = [2.0, -3.0, 0.5, 1.0];
+        let aggregate = |aggregation: Aggregation| aggregation.aggregate(values.iter().cloned());
+        assert_eq!(0.5, aggregate(Aggregation::Sum));
+        assert_eq!(-3.0, aggregate(Aggregation::Product));
+        assert_eq!(2.0, aggregate(Aggregation::Max));
+        assert_eq!(-3.0, aggregate(Aggregation::Min));
+        assert_eq!(0.125, aggregate(Aggregation::Mean));
+        assert_eq!(0.75, aggregate(Aggregation::Median));
+        assert_eq!(-3.0, aggregate(Aggregation::MaxAbs));
+        assert_eq!(0.5, Aggregation::Median.aggregate(vec![2.0, -3.0, 0.5]));
+
+        for &aggregation in Aggregation::ALL.iter() {
+            assert_eq!(0.0, aggregation.aggregate(Vec::<f64>::new()));
+            assert_eq!(-1.5, aggregation.aggregate(vec![-1.5]));
+        }
+    }
+
+    #[test]
+    fn test_partials_match_finite_difference() {
+        let h = 1e-6;
+        for values in [vec![2.0, -3.0, 0.5, 1.0], vec![0.3, -0.2, 0.9]].iter() {
+            for &aggregation in Aggregation::ALL.iter() {
+                let mut partials = vec![0.0f64; values.len()];
+                aggregation.partials(values, &mut partials);
+                for k in 0..values.len() {
+                    let mut plus = values.clone();
+                    let mut minus = values.clone();
+                    plus[k] += h;
+                    minus[k] -= h;
+                    let expected =
+                        (aggregation.aggregate(plus) - aggregation.aggregate(minus)) / (2.0 * h);
+                    assert!((expected - partials[k]).abs() < 1e-6, "{:?}", aggregation);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_aggregate_interval_contains_samples() {
+        let intervals = [
+            Interval::new(-1.0, 0.5),
+            Interval::new(0.25, 2.0),
+            Interval::new(-3.0, -2.0),
+        ];
+        for &aggregation in Aggregation::ALL.iter() {
+            let bounds = aggregation.aggregate_interval(&intervals);
+            for i in 0..=10 {
+                for j in 0..=10 {
+                    for k in 0..=10 {
+                        let at = |interval: &Interval, t: usize| {
+                            interval.lo + (interval.hi - interval.lo) * (t as f64) / 10.0
+                        };
+                        let value = aggregation.aggregate(vec![
+                            at(&intervals[0], i),
+                            at(&intervals[1], j),
+                            at(&intervals[2], k),
+                        ]);
+                        assert!(bounds.contains(value), "{:?} {}", aggregation, value);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_expr_matches_aggregate() {
+        let values = [2.0, -3.0, 0.5, 1.0];
+        let exprs: Vec<Expr> = (0..values.len()).map(Expr::Input).collect();
+        for &aggregation in Aggregation::ALL.iter() {
+            assert_eq!(
+                aggregation.aggregate(values.iter().cloned()),
+                aggregation.expr(exprs.clone()).eval(&values, &[])
+            );
+        }
+    }
+}
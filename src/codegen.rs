@@ -0,0 +1,985 @@
+//! Generation of standalone source code from a CPPN.
+//!
+//! The network is turned into a `CppnExpr` and written as straight-line code: one statement per
+//! shared subexpression, in topological order, followed by the outputs. Activation functions
+//! are inlined, so the generated code has no dependencies on this crate.
+
+use aggregation::Aggregation;
+use cppn::{CppnGraph, CppnNodeType};
+use expr::{CppnExpr, Expr, Function};
+use std::fmt::Debug;
+
+/// The syntax of a target language.
+///
+/// The arithmetic operators `+`, `-`, `*`, `/` and unary `-` are written the same way in all
+/// supported languages. Everything else is written as a function call, so the arguments passed
+/// to the methods below never need additional parentheses.
+pub trait Language {
+    fn constant(&self, c: f64) -> String;
+    fn input(&self, n: usize) -> String;
+
+    fn var(&self, n: usize) -> String {
+        format!("t{}", n)
+    }
+
+    fn apply(&self, function: Function, x: &str) -> String;
+    fn min(&self, a: &str, b: &str) -> String;
+    fn max(&self, a: &str, b: &str) -> String;
+    fn powi(&self, x: &str, n: i32) -> String;
+
+    /// A call of a function which is not known to the generator (see `Expr::Call`). It has to
+    /// be provided along with the generated code.
+    fn call(&self, name: &str, x: &str) -> String {
+        format!("{}({})", name, x)
+    }
+
+    /// An aggregation without closed form (see `Expr::Aggregate`). The default implementation
+    /// writes a call of a function named after the aggregation (e.g. `median(a, b, c)`), which
+    /// has to be provided along with the generated code.
+    fn aggregate(&self, aggregation: Aggregation, values: &[String]) -> String {
+        format!("{}({})", aggregation.name(), values.join(", "))
+    }
+
+    /// Writes a function named `name` which evaluates `expr`.
+    fn function(&self, name: &str, expr: &CppnExpr) -> String;
+}
+
+/// Writes `expr` as an expression of `language`.
+pub fn write_expr<T: Language + ?Sized>(language: &T, expr: &Expr) -> String {
+    let operand = |a: &Expr, min_precedence: u8| {
+        if precedence(a) < min_precedence {
+            format!("({})", write_expr(language, a))
+        } else {
+            write_expr(language, a)
+        }
+    };
+    // Right operands of equal precedence are parenthesized, as the operators are evaluated from
+    // left to right.
+    let binary = |a: &Expr, op: &str, b: &Expr, precedence: u8| {
+        format!(
+            "{} {} {}",
+            operand(a, precedence),
+            op,
+            operand(b, precedence + 1)
+        )
+    };
+    let arg = |a: &Expr| write_expr(language, a);
+
+    match *expr {
+        Expr::Const(c) => language.constant(c),
+        Expr::Input(n) => language.input(n),
+        Expr::Var(n) => language.var(n),
+        // `- -x` is parenthesized to avoid `--`.
+        Expr::Neg(ref a) => format!("-{}", operand(a, 4)),
+        Expr::Add(ref a, ref b) => binary(a, "+", b, 1),
+        Expr::Sub(ref a, ref b) => binary(a, "-", b, 1),
+        Expr::Mul(ref a, ref b) => binary(a, "*", b, 2),
+        Expr::Div(ref a, ref b) => binary(a, "/", b, 2),
+        Expr::Min(ref a, ref b) => language.min(&arg(a), &arg(b)),
+        Expr::Max(ref a, ref b) => language.max(&arg(a), &arg(b)),
+        Expr::Powi(ref a, n) => language.powi(&arg(a), n),
+        Expr::Apply(function, ref a) => language.apply(function, &arg(a)),
+        Expr::Aggregate(aggregation, ref values) => {
+            let values: Vec<String> = values.iter().map(arg).collect();
+            language.aggregate(aggregation, &values)
+        }
+        Expr::Call(ref name, ref a) => language.call(name, &arg(a)),
+    }
+}
+
+fn precedence(expr: &Expr) -> u8 {
+    match *expr {
+        Expr::Add(..) | Expr::Sub(..) => 1,
+        Expr::Mul(..) | Expr::Div(..) => 2,
+        Expr::Neg(_) => 3,
+        Expr::Const(c) if c.is_sign_negative() => 3,
+        _ => 4,
+    }
+}
+
+/// The aggregations without closed form used in `expr` (see `Expr::Aggregate`), with their
+/// number of values, in order of first use.
+fn aggregates(expr: &CppnExpr) -> Vec<(Aggregation, usize)> {
+    fn visit(expr: &Expr, found: &mut Vec<(Aggregation, usize)>) {
+        if let Expr::Aggregate(aggregation, ref values) = *expr {
+            if !found.contains(&(aggregation, values.len())) {
+                found.push((aggregation, values.len()));
+            }
+        }
+        for child in expr.children() {
+            visit(child, found);
+        }
+    }
+    let mut found = Vec::new();
+    for expr in expr.vars().iter().chain(expr.outputs().iter()) {
+        visit(expr, &mut found);
+    }
+    found
+}
+
+/// Writes a function named `name` in `language` which evaluates `graph`.
+///
+/// The network is simplified (see `CppnExpr::simplify`) and bias nodes are fed with the default
+/// bias value of 1.0. Use `Language::function` to export a `CppnExpr` directly.
+pub fn export<T, N, L, EXTID>(language: &T, graph: &CppnGraph<N, L, EXTID>, name: &str) -> String
+where
+    T: Language + ?Sized,
+    N: CppnNodeType,
+    L: Copy + Debug + Send + Sized + Into<f64>,
+    EXTID: Copy + Debug + Send + Sized + Ord,
+{
+    language.function(name, &CppnExpr::new(graph).simplify())
+}
+
+/// Generates `fn name(inputs: &[f64; N]) -> [f64; M]`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Rust;
+
+impl Language for Rust {
+    fn constant(&self, c: f64) -> String {
+        if c.is_nan() {
+            "f64::NAN".to_string()
+        } else if c == f64::INFINITY {
+            "f64::INFINITY".to_string()
+        } else if c == f64::NEG_INFINITY {
+            "f64::NEG_INFINITY".to_string()
+        } else {
+            format!("{:?}", c)
+        }
+    }
+
+    fn input(&self, n: usize) -> String {
+        format!("inputs[{}]", n)
+    }
+
+    fn apply(&self, function: Function, x: &str) -> String {
+        match function {
+            Function::Log => format!("f64::ln({})", x),
+            Function::Step => format!("(if {} >= 0.0 {{ 1.0 }} else {{ 0.0 }})", x),
+            _ => format!("f64::{}({})", function.name(), x),
+        }
+    }
+
+    fn min(&self, a: &str, b: &str) -> String {
+        format!("f64::min({}, {})", a, b)
+    }
+
+    fn max(&self, a: &str, b: &str) -> String {
+        format!("f64::max({}, {})", a, b)
+    }
+
+    fn powi(&self, x: &str, n: i32) -> String {
+        format!("f64::powi({}, {})", x, n)
+    }
+
+    fn aggregate(&self, aggregation: Aggregation, values: &[String]) -> String {
+        let values = values.join(", ");
+        match aggregation {
+            Aggregation::Median => format!(
+                concat!(
+                    "{{ let mut v = [{}]; ",
+                    "v.sort_by(|a, b| a.partial_cmp(b).unwrap_or(::std::cmp::Ordering::Equal)); ",
+                    "let n = v.len(); ",
+                    "if n % 2 == 1 {{ v[n / 2] }} else {{ (v[n / 2 - 1] + v[n / 2]) / 2.0 }} }}"
+                ),
+                values
+            ),
+            Aggregation::MaxAbs => format!(
+                concat!(
+                    "{{ let v = [{}]; ",
+                    "v[1..].iter().fold(v[0], |a: f64, &b| ",
+                    "if b.abs() > a.abs() {{ b }} else {{ a }}) }}"
+                ),
+                values
+            ),
+            _ => format!("{}({})", aggregation.name(), values),
+        }
+    }
+
+    fn function(&self, name: &str, expr: &CppnExpr) -> String {
+        let mut code = format!(
+            "pub fn {}(inputs: &[f64; {}]) -> [f64; {}] {{\n",
+            name,
+            expr.input_count(),
+            expr.outputs().len()
+        );
+        for (n, var) in expr.vars().iter().enumerate() {
+            code += &format!("    let {} = {};\n", self.var(n), write_expr(self, var));
+        }
+        code += "    [\n";
+        for output in expr.outputs().iter() {
+            code += &format!("        {},\n", write_expr(self, output));
+        }
+        code += "    ]\n}\n";
+        code
+    }
+}
+
+/// Generates `void name(const double inputs[N], double outputs[M])`, using `math.h`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct C;
+
+impl Language for C {
+    fn constant(&self, c: f64) -> String {
+        if c.is_nan() {
+            "NAN".to_string()
+        } else if c == f64::INFINITY {
+            "INFINITY".to_string()
+        } else if c == f64::NEG_INFINITY {
+            "-INFINITY".to_string()
+        } else {
+            format!("{:?}", c)
+        }
+    }
+
+    fn input(&self, n: usize) -> String {
+        format!("inputs[{}]", n)
+    }
+
+    fn apply(&self, function: Function, x: &str) -> String {
+        let name = match function {
+            Function::Abs => "fabs",
+            Function::Step => return format!("({} >= 0.0 ? 1.0 : 0.0)", x),
+            _ => function.name(),
+        };
+        format!("{}({})", name, x)
+    }
+
+    fn min(&self, a: &str, b: &str) -> String {
+        format!("fmin({}, {})", a, b)
+    }
+
+    fn max(&self, a: &str, b: &str) -> String {
+        format!("fmax({}, {})", a, b)
+    }
+
+    fn powi(&self, x: &str, n: i32) -> String {
+        format!("pow({}, {})", x, n)
+    }
+
+    /// Calls a helper function written by `function`, which takes the values as an array.
+    fn aggregate(&self, aggregation: Aggregation, values: &[String]) -> String {
+        format!(
+            "{}((double[]){{{}}}, {})",
+            aggregation.name(),
+            values.join(", "),
+            values.len()
+        )
+    }
+
+    fn function(&self, name: &str, expr: &CppnExpr) -> String {
+        let mut code = "#include <math.h>\n\n".to_string();
+        let mut helpers: Vec<Aggregation> = Vec::new();
+        for (aggregation, _) in aggregates(expr) {
+            if !helpers.contains(&aggregation) {
+                helpers.push(aggregation);
+                code += &c_aggregate_helper(aggregation);
+            }
+        }
+        code += &format!(
+            "void {}(const double inputs[{}], double outputs[{}]) {{\n",
+            name,
+            expr.input_count(),
+            expr.outputs().len()
+        );
+        for (n, var) in expr.vars().iter().enumerate() {
+            code += &format!(
+                "    const double {} = {};\n",
+                self.var(n),
+                write_expr(self, var)
+            );
+        }
+        for (n, output) in expr.outputs().iter().enumerate() {
+            code += &format!("    outputs[{}] = {};\n", n, write_expr(self, output));
+        }
+        code += "}\n";
+        code
+    }
+}
+
+/// A C function which computes `aggregation` of an array of `n` values (sorting it in place).
+fn c_aggregate_helper(aggregation: Aggregation) -> String {
+    match aggregation {
+        Aggregation::Median => concat!(
+            "static double median(double *v, int n) {\n",
+            "    for (int i = 1; i < n; i++) {\n",
+            "        for (int j = i; j > 0 && v[j - 1] > v[j]; j--) {\n",
+            "            const double t = v[j];\n",
+            "            v[j] = v[j - 1];\n",
+            "            v[j - 1] = t;\n",
+            "        }\n",
+            "    }\n",
+            "    return n % 2 == 1 ? v[n / 2] : (v[n / 2 - 1] + v[n / 2]) / 2.0;\n",
+            "}\n\n"
+        )
+        .to_string(),
+        Aggregation::MaxAbs => concat!(
+            "static double maxabs(const double *v, int n) {\n",
+            "    double m = v[0];\n",
+            "    for (int i = 1; i < n; i++) {\n",
+            "        if (fabs(v[i]) > fabs(m)) {\n",
+            "            m = v[i];\n",
+            "        }\n",
+            "    }\n",
+            "    return m;\n",
+            "}\n\n"
+        )
+        .to_string(),
+        _ => unreachable!("{:?} has a closed form", aggregation),
+    }
+}
+
+/// Generates a NumPy function `def name(inputs)`.
+///
+/// `inputs` is a sequence of N arrays (or scalars) which are broadcast against each other, so
+/// the network is evaluated for all elements at once. Returns a list of M arrays of the
+/// broadcast shape.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Python;
+
+impl Language for Python {
+    fn constant(&self, c: f64) -> String {
+        if c.is_nan() {
+            "np.nan".to_string()
+        } else if c == f64::INFINITY {
+            "np.inf".to_string()
+        } else if c == f64::NEG_INFINITY {
+            "-np.inf".to_string()
+        } else {
+            format!("{:?}", c)
+        }
+    }
+
+    fn input(&self, n: usize) -> String {
+        format!("inputs[{}]", n)
+    }
+
+    fn apply(&self, function: Function, x: &str) -> String {
+        match function {
+            Function::Step => format!("np.heaviside({}, 1.0)", x),
+            _ => format!("np.{}({})", function.name(), x),
+        }
+    }
+
+    fn min(&self, a: &str, b: &str) -> String {
+        format!("np.minimum({}, {})", a, b)
+    }
+
+    fn max(&self, a: &str, b: &str) -> String {
+        format!("np.maximum({}, {})", a, b)
+    }
+
+    fn powi(&self, x: &str, n: i32) -> String {
+        format!("np.power({}, {})", x, n)
+    }
+
+    fn aggregate(&self, aggregation: Aggregation, values: &[String]) -> String {
+        let stacked = format!("np.stack(np.broadcast_arrays({}))", values.join(", "));
+        match aggregation {
+            Aggregation::Median => format!("np.median({}, axis=0)", stacked),
+            Aggregation::MaxAbs => format!(
+                "(lambda v: np.take_along_axis(v, np.abs(v).argmax(axis=0)[None], axis=0)[0])({})",
+                stacked
+            ),
+            _ => format!("{}({})", aggregation.name(), values.join(", ")),
+        }
+    }
+
+    fn function(&self, name: &str, expr: &CppnExpr) -> String {
+        let mut code = format!(
+            "import numpy as np\n\n\ndef {}(inputs):\n    assert len(inputs) == {}\n",
+            name,
+            expr.input_count()
+        );
+        for (n, var) in expr.vars().iter().enumerate() {
+            code += &format!("    {} = {}\n", self.var(n), write_expr(self, var));
+        }
+        code += "    return np.broadcast_arrays(\n";
+        for output in expr.outputs().iter() {
+            code += &format!("        {},\n", write_expr(self, output));
+        }
+        code += "    )\n";
+        code
+    }
+}
+
+/// The inputs of a CPPN which draws a 2D pattern, as available in a fragment shader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderInput {
+    /// The x coordinate of the point `p`.
+    X,
+    /// The y coordinate of the point `p`.
+    Y,
+    /// The distance of `p` from the origin.
+    D,
+    /// The constant 1.0.
+    Bias,
+    /// The `time` argument, for animated patterns.
+    Time,
+}
+
+impl ShaderInput {
+    fn variable(&self) -> &'static str {
+        match *self {
+            ShaderInput::X => "p.x",
+            ShaderInput::Y => "p.y",
+            ShaderInput::D => "length(p)",
+            ShaderInput::Bias => "1.0",
+            ShaderInput::Time => "time",
+        }
+    }
+}
+
+/// Shaders have no literals for NaN and infinity, so they are written as the bit patterns of
+/// the single precision values, converted with `to_float`.
+fn shader_constant(c: f64, to_float: &str) -> String {
+    if c.is_nan() {
+        format!("{}(0x7fc00000u)", to_float)
+    } else if c == f64::INFINITY {
+        format!("{}(0x7f800000u)", to_float)
+    } else if c == f64::NEG_INFINITY {
+        format!("-{}(0x7f800000u)", to_float)
+    } else {
+        format!("{:?}", c)
+    }
+}
+
+/// `pow` is undefined for negative bases in shaders, so integer powers are written as
+/// products.
+fn shader_powi(x: &str, n: i32) -> String {
+    let factors = vec![format!("({})", x); n.unsigned_abs() as usize];
+    let product = if factors.is_empty() {
+        "1.0".to_string()
+    } else {
+        factors.join(" * ")
+    };
+    if n < 0 {
+        format!("(1.0 / ({}))", product)
+    } else {
+        format!("({})", product)
+    }
+}
+
+fn shader_apply(function: Function, x: &str) -> String {
+    match function {
+        Function::Step => format!("step(0.0, {})", x),
+        _ => format!("{}({})", function.name(), x),
+    }
+}
+
+/// Shader functions only take arrays of a fixed size, so there is one helper for each number
+/// of values. They are named like `median3`.
+fn shader_aggregate_name(aggregation: Aggregation, n: usize) -> String {
+    format!("{}{}", aggregation.name(), n)
+}
+
+/// The value of `median` for `n` values sorted into the array `v`.
+fn shader_median(n: usize) -> String {
+    if n % 2 == 1 {
+        format!("v[{}]", n / 2)
+    } else {
+        format!("(v[{}] + v[{}]) / 2.0", n / 2 - 1, n / 2)
+    }
+}
+
+/// A GLSL function which computes `aggregation` of an array of `n` values.
+fn glsl_aggregate_helper(aggregation: Aggregation, n: usize) -> String {
+    let name = shader_aggregate_name(aggregation, n);
+    match aggregation {
+        Aggregation::Median => format!(
+            concat!(
+                "float {name}(float v[{n}]) {{\n",
+                "    for (int i = 1; i < {n}; i++) {{\n",
+                "        for (int j = i; j > 0 && v[j - 1] > v[j]; j--) {{\n",
+                "            float t = v[j];\n",
+                "            v[j] = v[j - 1];\n",
+                "            v[j - 1] = t;\n",
+                "        }}\n",
+                "    }}\n",
+                "    return {median};\n",
+                "}}\n\n"
+            ),
+            name = name,
+            n = n,
+            median = shader_median(n)
+        ),
+        Aggregation::MaxAbs => format!(
+            concat!(
+                "float {name}(float v[{n}]) {{\n",
+                "    float m = v[0];\n",
+                "    for (int i = 1; i < {n}; i++) {{\n",
+                "        if (abs(v[i]) > abs(m)) {{\n",
+                "            m = v[i];\n",
+                "        }}\n",
+                "    }}\n",
+                "    return m;\n",
+                "}}\n\n"
+            ),
+            name = name,
+            n = n
+        ),
+        _ => unreachable!("{:?} has a closed form", aggregation),
+    }
+}
+
+/// A WGSL function which computes `aggregation` of an array of `n` values.
+fn wgsl_aggregate_helper(aggregation: Aggregation, n: usize) -> String {
+    let name = shader_aggregate_name(aggregation, n);
+    match aggregation {
+        Aggregation::Median => format!(
+            concat!(
+                "fn {name}(values: array<f32, {n}>) -> f32 {{\n",
+                "    var v = values;\n",
+                "    for (var i = 1; i < {n}; i++) {{\n",
+                "        for (var j = i; j > 0 && v[j - 1] > v[j]; j--) {{\n",
+                "            let t = v[j];\n",
+                "            v[j] = v[j - 1];\n",
+                "            v[j - 1] = t;\n",
+                "        }}\n",
+                "    }}\n",
+                "    return {median};\n",
+                "}}\n\n"
+            ),
+            name = name,
+            n = n,
+            median = shader_median(n)
+        ),
+        Aggregation::MaxAbs => format!(
+            concat!(
+                "fn {name}(values: array<f32, {n}>) -> f32 {{\n",
+                "    var v = values;\n",
+                "    var m = v[0];\n",
+                "    for (var i = 1; i < {n}; i++) {{\n",
+                "        if (abs(v[i]) > abs(m)) {{\n",
+                "            m = v[i];\n",
+                "        }}\n",
+                "    }}\n",
+                "    return m;\n",
+                "}}\n\n"
+            ),
+            name = name,
+            n = n
+        ),
+        _ => unreachable!("{:?} has a closed form", aggregation),
+    }
+}
+
+fn shader_outputs(expr: &CppnExpr, language: &dyn Language) -> Vec<String> {
+    assert!(
+        expr.outputs().len() <= 4,
+        "a shader can only return up to 4 outputs, but the CPPN has {}",
+        expr.outputs().len()
+    );
+    let mut outputs: Vec<String> = expr
+        .outputs()
+        .iter()
+        .map(|output| write_expr(language, output))
+        .collect();
+    outputs.resize(4, "0.0".to_string());
+    outputs
+}
+
+/// Generates a GLSL function `vec4 name(vec2 p, float time)` which evaluates a CPPN with the
+/// given `inputs` at point `p`.
+///
+/// The outputs are returned in the components of the result, unused components are 0.0.
+/// Requires GLSL 3.30 or GLSL ES 3.00.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Glsl {
+    inputs: Vec<ShaderInput>,
+}
+
+impl Glsl {
+    /// `inputs` determines the value of each input of the CPPN.
+    pub fn new(inputs: &[ShaderInput]) -> Self {
+        Glsl {
+            inputs: inputs.to_vec(),
+        }
+    }
+}
+
+impl Language for Glsl {
+    fn constant(&self, c: f64) -> String {
+        shader_constant(c, "uintBitsToFloat")
+    }
+
+    fn input(&self, n: usize) -> String {
+        self.inputs[n].variable().to_string()
+    }
+
+    fn apply(&self, function: Function, x: &str) -> String {
+        shader_apply(function, x)
+    }
+
+    fn min(&self, a: &str, b: &str) -> String {
+        format!("min({}, {})", a, b)
+    }
+
+    fn max(&self, a: &str, b: &str) -> String {
+        format!("max({}, {})", a, b)
+    }
+
+    fn powi(&self, x: &str, n: i32) -> String {
+        shader_powi(x, n)
+    }
+
+    /// Calls a helper function written by `function`.
+    fn aggregate(&self, aggregation: Aggregation, values: &[String]) -> String {
+        format!(
+            "{}(float[{}]({}))",
+            shader_aggregate_name(aggregation, values.len()),
+            values.len(),
+            values.join(", ")
+        )
+    }
+
+    fn function(&self, name: &str, expr: &CppnExpr) -> String {
+        assert!(expr.input_count() == self.inputs.len());
+        let mut code = String::new();
+        for (aggregation, n) in aggregates(expr) {
+            code += &glsl_aggregate_helper(aggregation, n);
+        }
+        code += &format!("vec4 {}(vec2 p, float time) {{\n", name);
+        for (n, var) in expr.vars().iter().enumerate() {
+            code += &format!("    float {} = {};\n", self.var(n), write_expr(self, var));
+        }
+        code += &format!(
+            "    return vec4({});\n}}\n",
+            shader_outputs(expr, self).join(", ")
+        );
+        code
+    }
+}
+
+/// Generates a WGSL function `fn name(p: vec2<f32>, time: f32) -> vec4<f32>`, like `Glsl`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Wgsl {
+    inputs: Vec<ShaderInput>,
+}
+
+impl Wgsl {
+    /// `inputs` determines the value of each input of the CPPN.
+    pub fn new(inputs: &[ShaderInput]) -> Self {
+        Wgsl {
+            inputs: inputs.to_vec(),
+        }
+    }
+}
+
+impl Language for Wgsl {
+    fn constant(&self, c: f64) -> String {
+        shader_constant(c, "bitcast<f32>")
+    }
+
+    fn input(&self, n: usize) -> String {
+        self.inputs[n].variable().to_string()
+    }
+
+    fn apply(&self, function: Function, x: &str) -> String {
+        shader_apply(function, x)
+    }
+
+    fn min(&self, a: &str, b: &str) -> String {
+        format!("min({}, {})", a, b)
+    }
+
+    fn max(&self, a: &str, b: &str) -> String {
+        format!("max({}, {})", a, b)
+    }
+
+    fn powi(&self, x: &str, n: i32) -> String {
+        shader_powi(x, n)
+    }
+
+    /// Calls a helper function written by `function`.
+    fn aggregate(&self, aggregation: Aggregation, values: &[String]) -> String {
+        format!(
+            "{}(array<f32, {}>({}))",
+            shader_aggregate_name(aggregation, values.len()),
+            values.len(),
+            values.join(", ")
+        )
+    }
+
+    fn function(&self, name: &str, expr: &CppnExpr) -> String {
+        assert!(expr.input_count() == self.inputs.len());
+        let mut code = String::new();
+        for (aggregation, n) in aggregates(expr) {
+            code += &wgsl_aggregate_helper(aggregation, n);
+        }
+        code += &format!("fn {}(p: vec2<f32>, time: f32) -> vec4<f32> {{\n", name);
+        for (n, var) in expr.vars().iter().enumerate() {
+            code += &format!("    let {} = {};\n", self.var(n), write_expr(self, var));
+        }
+        code += &format!(
+            "    return vec4<f32>({});\n}}\n",
+            shader_outputs(expr, self).join(", ")
+        );
+        code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export, write_expr, Glsl, Python, Rust, ShaderInput, Wgsl, C};
+    use activation_function::GeometricActivationFunction as AF;
+    use acyclic_network::ExternalId;
+    use aggregation::Aggregation;
+    use cppn::{CppnGraph, CppnNode};
+    use expr::Expr;
+
+    fn graph() -> CppnGraph<CppnNode<AF>, f64, ExternalId> {
+        let mut g = CppnGraph::new();
+        let x = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let y = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let b = g.add_node(CppnNode::bias(AF::Linear), ExternalId(3));
+        let h = g.add_node(CppnNode::hidden(AF::Gaussian), ExternalId(4));
+        let o1 = g.add_node(CppnNode::output(AF::Sine), ExternalId(5));
+        let o2 = g.add_node(CppnNode::output(AF::LinearClipped), ExternalId(6));
+        g.add_link(x, h, 0.5, ExternalId(1));
+        g.add_link(y, h, -1.0, ExternalId(2));
+        g.add_link(b, h, 0.25, ExternalId(3));
+        g.add_link(h, o1, 2.0, ExternalId(4));
+        g.add_link(h, o2, 1.0, ExternalId(5));
+        g.add_link(x, o2, -3.0, ExternalId(6));
+        g
+    }
+
+    #[test]
+    fn test_operators() {
+        let x = Expr::Input(0);
+        let c = Expr::Const;
+        assert_eq!(
+            "-(-inputs[0]) - -2.5 * (inputs[0] + 1.0)",
+            write_expr(&C, &(-(-x.clone()) - c(-2.5) * (x.clone() + c(1.0))))
+        );
+        assert_eq!(
+            "f64::powi(-(inputs[0] * 2.5), 2)",
+            write_expr(&Rust, &(-(x.clone() * c(2.5))).powi(2))
+        );
+        assert_eq!(
+            "1.0 / inputs[0] / 2.0",
+            write_expr(&Python, &(c(1.0) / x.clone() / c(2.0)))
+        );
+        assert_eq!(
+            "1.0 / (inputs[0] / 2.0)",
+            write_expr(&Python, &(c(1.0) / (x / c(2.0))))
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        let x = Expr::Input(0);
+        let wave = (x.clone() - x.clone().floor()).step() + x.clone().log().tanh();
+        assert_eq!(
+            concat!(
+                "(if inputs[0] - f64::floor(inputs[0]) >= 0.0 { 1.0 } else { 0.0 }) + ",
+                "f64::tanh(f64::ln(inputs[0]))"
+            ),
+            write_expr(&Rust, &wave)
+        );
+        assert_eq!(
+            "(inputs[0] - floor(inputs[0]) >= 0.0 ? 1.0 : 0.0) + tanh(log(inputs[0]))",
+            write_expr(&C, &wave)
+        );
+        assert_eq!(
+            "np.heaviside(inputs[0] - np.floor(inputs[0]), 1.0) + np.tanh(np.log(inputs[0]))",
+            write_expr(&Python, &wave)
+        );
+        assert_eq!(
+            "step(0.0, p.x - floor(p.x)) + tanh(log(p.x))",
+            write_expr(&Glsl::new(&[ShaderInput::X]), &wave)
+        );
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let values = vec![Expr::Input(0), Expr::Input(1) * Expr::Const(2.0), Expr::Const(0.5)];
+        let median = Expr::Aggregate(Aggregation::Median, values.clone());
+        let max_abs = Expr::Aggregate(Aggregation::MaxAbs, values);
+        assert_eq!(
+            concat!(
+                "{ let mut v = [inputs[0], inputs[1] * 2.0, 0.5]; ",
+                "v.sort_by(|a, b| a.partial_cmp(b).unwrap_or(::std::cmp::Ordering::Equal)); ",
+                "let n = v.len(); ",
+                "if n % 2 == 1 { v[n / 2] } else { (v[n / 2 - 1] + v[n / 2]) / 2.0 } }"
+            ),
+            write_expr(&Rust, &median)
+        );
+        assert_eq!(
+            concat!(
+                "{ let v = [inputs[0], inputs[1] * 2.0, 0.5]; ",
+                "v[1..].iter().fold(v[0], |a: f64, &b| if b.abs() > a.abs() { b } else { a }) }"
+            ),
+            write_expr(&Rust, &max_abs)
+        );
+        assert_eq!(
+            "np.median(np.stack(np.broadcast_arrays(inputs[0], inputs[1] * 2.0, 0.5)), axis=0)",
+            write_expr(&Python, &median)
+        );
+        assert_eq!(
+            "maxabs((double[]){inputs[0], inputs[1] * 2.0, 0.5}, 3)",
+            write_expr(&C, &max_abs)
+        );
+        assert_eq!(
+            "median3(float[3](p.x, p.y * 2.0, 0.5))",
+            write_expr(&Glsl::new(&[ShaderInput::X, ShaderInput::Y]), &median)
+        );
+        assert_eq!(
+            "maxabs3(array<f32, 3>(p.x, p.y * 2.0, 0.5))",
+            write_expr(&Wgsl::new(&[ShaderInput::X, ShaderInput::Y]), &max_abs)
+        );
+    }
+
+    #[test]
+    fn test_aggregate_helpers() {
+        let mut g = CppnGraph::new();
+        let x = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let y = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let o1 = g.add_node(
+            CppnNode::output(AF::Linear).with_aggregation(Aggregation::Median),
+            ExternalId(3),
+        );
+        let o2 = g.add_node(
+            CppnNode::output(AF::Linear).with_aggregation(Aggregation::MaxAbs),
+            ExternalId(4),
+        );
+        g.add_link(x, o1, 1.0, ExternalId(1));
+        g.add_link(y, o1, -1.0, ExternalId(2));
+        g.add_link(x, o2, 2.0, ExternalId(3));
+        g.add_link(y, o2, 0.5, ExternalId(4));
+
+        let c = export(&C, &g, "cppn");
+        assert!(c.contains("static double median(double *v, int n) {"));
+        assert!(c.contains("static double maxabs(const double *v, int n) {"));
+        assert!(c.contains("outputs[0] = median((double[]){inputs[0], -inputs[1]}, 2);"));
+
+        let inputs = [ShaderInput::X, ShaderInput::Y];
+        let glsl = export(&Glsl::new(&inputs), &g, "pattern");
+        assert!(glsl.starts_with("float median2(float v[2]) {\n"));
+        assert!(glsl.contains("    return (v[0] + v[1]) / 2.0;\n"));
+        assert!(glsl.contains("float maxabs2(float v[2]) {\n"));
+        assert!(glsl.contains("median2(float[2](p.x, -p.y))"));
+
+        let wgsl = export(&Wgsl::new(&inputs), &g, "pattern");
+        assert!(wgsl.starts_with("fn median2(values: array<f32, 2>) -> f32 {\n"));
+        assert!(wgsl.contains("fn maxabs2(values: array<f32, 2>) -> f32 {\n"));
+        assert!(wgsl.contains("maxabs2(array<f32, 2>(2.0 * p.x, 0.5 * p.y))"));
+    }
+
+    #[test]
+    fn test_rust() {
+        assert_eq!(
+            "pub fn cppn(inputs: &[f64; 2]) -> [f64; 2] {
+    let t0 = f64::exp(-f64::powi((0.5 * inputs[0] - inputs[1] + 0.25) * 2.5, 2));
+    [
+        f64::sin(6.283185307179586 * (2.0 * t0)),
+        f64::max(0.0, f64::min(1.0, -3.0 * inputs[0] + t0)),
+    ]
+}
+",
+            export(&Rust, &graph(), "cppn")
+        );
+    }
+
+    #[test]
+    fn test_c() {
+        assert_eq!(
+            "#include <math.h>
+
+void cppn(const double inputs[2], double outputs[2]) {
+    const double t0 = exp(-pow((0.5 * inputs[0] - inputs[1] + 0.25) * 2.5, 2));
+    outputs[0] = sin(6.283185307179586 * (2.0 * t0));
+    outputs[1] = fmax(0.0, fmin(1.0, -3.0 * inputs[0] + t0));
+}
+",
+            export(&C, &graph(), "cppn")
+        );
+    }
+
+    #[test]
+    fn test_python() {
+        assert_eq!(
+            "import numpy as np
+
+
+def cppn(inputs):
+    assert len(inputs) == 2
+    t0 = np.exp(-np.power((0.5 * inputs[0] - inputs[1] + 0.25) * 2.5, 2))
+    return np.broadcast_arrays(
+        np.sin(6.283185307179586 * (2.0 * t0)),
+        np.maximum(0.0, np.minimum(1.0, -3.0 * inputs[0] + t0)),
+    )
+",
+            export(&Python, &graph(), "cppn")
+        );
+    }
+
+    #[test]
+    fn test_glsl() {
+        let glsl = Glsl::new(&[ShaderInput::X, ShaderInput::D]);
+        let code = export(&glsl, &graph(), "pattern");
+        assert_eq!(
+            vec![
+                "vec4 pattern(vec2 p, float time) {",
+                concat!(
+                    "    float t0 = exp(-(((0.5 * p.x - length(p) + 0.25) * 2.5) * ",
+                    "((0.5 * p.x - length(p) + 0.25) * 2.5)));"
+                ),
+                concat!(
+                    "    return vec4(sin(6.283185307179586 * (2.0 * t0)), ",
+                    "max(0.0, min(1.0, -3.0 * p.x + t0)), 0.0, 0.0);"
+                ),
+                "}",
+            ],
+            code.lines().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_wgsl() {
+        let wgsl = Wgsl::new(&[ShaderInput::Time, ShaderInput::Y]);
+        let code = export(&wgsl, &graph(), "pattern");
+        assert_eq!(
+            vec![
+                "fn pattern(p: vec2<f32>, time: f32) -> vec4<f32> {",
+                concat!(
+                    "    let t0 = exp(-(((0.5 * time - p.y + 0.25) * 2.5) * ",
+                    "((0.5 * time - p.y + 0.25) * 2.5)));"
+                ),
+                concat!(
+                    "    return vec4<f32>(sin(6.283185307179586 * (2.0 * t0)), ",
+                    "max(0.0, min(1.0, -3.0 * time + t0)), 0.0, 0.0);"
+                ),
+                "}",
+            ],
+            code.lines().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "a shader can only return up to 4 outputs, but the CPPN has 5")]
+    fn test_shader_too_many_outputs() {
+        let mut g = CppnGraph::new();
+        let x = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        for i in 0..5 {
+            let o = g.add_node(CppnNode::output(AF::Linear), ExternalId(2 + i));
+            g.add_link(x, o, 1.0, ExternalId(i));
+        }
+        export(&Glsl::new(&[ShaderInput::X]), &g, "pattern");
+    }
+
+    #[test]
+    fn test_shader_constants() {
+        let wgsl = Wgsl::new(&[ShaderInput::Bias]);
+        assert_eq!(
+            "-bitcast<f32>(0x7f800000u) * 1.0",
+            write_expr(&wgsl, &(Expr::Const(f64::NEG_INFINITY) * Expr::Input(0)))
+        );
+        let glsl = Glsl::new(&[ShaderInput::X]);
+        assert_eq!(
+            "(1.0 / ((p.x) * (p.x) * (p.x)))",
+            write_expr(&glsl, &Expr::Input(0).powi(-3))
+        );
+    }
+}
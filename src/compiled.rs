@@ -0,0 +1,1149 @@
+use acyclic_network::LinkIndex;
+use aggregation::Aggregation;
+use cppn::{CppnError, CppnGraph, CppnNodeIndex, CppnNodeType};
+use float::Float;
+use interval::Interval;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::mem;
+use std::sync::Mutex;
+
+/// The error of activation functions relative to the magnitude of their argument and result,
+/// in units of the machine epsilon, assumed by `CompiledCppn::process_interval`. The
+/// approximations of `kernels` stay below `1e-14` (45 epsilon) for `f64` and `1e-6`
+/// (9 epsilon) for `f32`.
+pub const INTERVAL_TOLERANCE: f64 = 256.0;
+
+/// An active link of a `CompiledCppn`, referring to nodes by their index in the graph.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CompiledLink<F = f64>
+where
+    F: Float,
+{
+    pub source: usize,
+    pub target: usize,
+    pub weight: F,
+}
+
+/// The part of a `CompiledCppn` that a subset of its outputs depends on. Created by
+/// `CompiledCppn::output_cone` and evaluated by `CompiledCppn::process_cone`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputCone {
+    outputs: Vec<usize>,
+    steps: Vec<usize>,
+}
+
+impl OutputCone {
+    /// The requested outputs, as indices into `CompiledCppn::outputs()`.
+    pub fn outputs(&self) -> &[usize] {
+        &self.outputs
+    }
+
+    /// The positions in evaluation order (see `CompiledCppn::order()`) of all nodes which have
+    /// to be evaluated, in ascending order.
+    pub fn steps(&self) -> &[usize] {
+        &self.steps
+    }
+}
+
+/// Scratch buffers used to evaluate a `CompiledCppn`.
+///
+/// The buffers are not tied to a particular network. They grow as needed and can be reused for
+/// networks of different sizes, so a single set of buffers avoids reallocations when evaluating
+/// many graphs in turn.
+#[derive(Clone, Debug, Default)]
+pub struct EvalBuffers<F = f64>
+where
+    F: Float,
+{
+    incoming: Vec<F>,
+    outgoing: Vec<F>,
+    batch: Vec<F>,
+    tangents: Vec<F>,
+    partials: Vec<F>,
+}
+
+impl<F> EvalBuffers<F>
+where
+    F: Float,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resizes the signal buffers to hold `node_count` nodes.
+    pub fn prepare(&mut self, node_count: usize) {
+        self.incoming.clear();
+        self.incoming.resize(node_count, F::zero());
+        self.outgoing.clear();
+        self.outgoing.resize(node_count, F::zero());
+    }
+
+    /// The sum of all weighted input signals of each node.
+    pub fn incoming(&self) -> &[F] {
+        &self.incoming
+    }
+
+    /// The output of the activation function of each node.
+    pub fn outgoing(&self) -> &[F] {
+        &self.outgoing
+    }
+
+    /// The derivatives of the output of each node with respect to each input, after
+    /// `CompiledCppn::process_jacobian_with`.
+    pub fn tangents(&self) -> &[F] {
+        &self.tangents
+    }
+}
+
+/// A pool of `EvalBuffers`, which can be shared between threads.
+#[derive(Debug, Default)]
+pub struct BufferPool<F = f64>
+where
+    F: Float,
+{
+    buffers: Mutex<Vec<EvalBuffers<F>>>,
+}
+
+impl<F> BufferPool<F>
+where
+    F: Float,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes a set of buffers out of the pool, or creates a new one if the pool is empty.
+    pub fn acquire(&self) -> EvalBuffers<F> {
+        self.buffers.lock().unwrap().pop().unwrap_or_default()
+    }
+
+    /// Returns `buffers` to the pool.
+    pub fn release(&self, buffers: EvalBuffers<F>) {
+        self.buffers.lock().unwrap().push(buffers);
+    }
+
+    /// Returns the number of buffers currently held by the pool.
+    pub fn len(&self) -> usize {
+        self.buffers.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A CPPN flattened into topological order.
+///
+/// The active links are stored in one contiguous array, grouped by their target node, in
+/// evaluation order. The incoming links of the `i`-th node of `order()` are
+/// `links()[link_offsets[i]..link_offsets[i + 1]]`. An evaluation is a single linear pass over
+/// the nodes, without any graph traversal.
+///
+/// Nodes keep the index they have in the `CppnGraph`, so signal arrays are indexed by
+/// `CppnNodeIndex::index()`.
+///
+/// Signals and link weights are of type `F`, which defaults to `f64`.
+///
+/// A node with an aggregation other than `Aggregation::Sum` combines the weighted signals of
+/// its incoming links with that aggregation. The result is added to the input value of input and
+/// bias nodes, so that the summed input of every node without incoming links is unaffected.
+#[derive(Clone, Debug)]
+pub struct CompiledCppn<N, F = f64>
+where
+    N: CppnNodeType,
+    F: Float,
+{
+    node_types: Vec<N>,
+    aggregations: Vec<Aggregation>,
+    order: Vec<usize>,
+    link_offsets: Vec<usize>,
+    links: Vec<CompiledLink<F>>,
+    // The index of each of `links` in the graph.
+    link_indices: Vec<LinkIndex>,
+    inputs: Vec<usize>,
+    outputs: Vec<usize>,
+    biases: Vec<usize>,
+    bias_value: F,
+}
+
+impl<N> CompiledCppn<N>
+where
+    N: CppnNodeType,
+{
+    /// Compiles `graph` for evaluation with `f64` signals.
+    pub fn new<L, EXTID>(graph: &CppnGraph<N, L, EXTID>) -> Self
+    where
+        L: Copy + Debug + Send + Sized + Into<f64>,
+        EXTID: Copy + Debug + Send + Sized + Ord,
+    {
+        Self::compile(graph)
+    }
+}
+
+impl<N, F> CompiledCppn<N, F>
+where
+    N: CppnNodeType,
+    F: Float,
+{
+    /// Compiles `graph` for evaluation with signals of type `F`.
+    pub fn compile<L, EXTID>(graph: &CppnGraph<N, L, EXTID>) -> Self
+    where
+        L: Copy + Debug + Send + Sized + Into<f64>,
+        EXTID: Copy + Debug + Send + Sized + Ord,
+    {
+        let node_count = graph.nodes().len();
+        let mut node_types = Vec::with_capacity(node_count);
+        let mut aggregations = Vec::with_capacity(node_count);
+        let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
+        let mut biases = Vec::new();
+        let mut incoming_links: Vec<Vec<(CompiledLink<F>, LinkIndex)>> =
+            vec![Vec::new(); node_count];
+        let mut in_degree = vec![0usize; node_count];
+
+        graph.each_node_with_index(|node, index| {
+            node_types.push(node.node_type().clone());
+            aggregations.push(node.node_type().aggregation());
+            if node.node_type().is_input_node() {
+                inputs.push(index.index());
+            }
+            if node.node_type().is_output_node() {
+                outputs.push(index.index());
+            }
+            if node.node_type().is_bias_node() {
+                biases.push(index.index());
+            }
+            for (link_index, link) in graph.link_iter_for_node(index) {
+                if !link.is_active() {
+                    continue;
+                }
+                let target = link.target_node_index().index();
+                let compiled_link = CompiledLink {
+                    source: index.index(),
+                    target,
+                    weight: F::from_f64(link.weight().into()),
+                };
+                incoming_links[target].push((compiled_link, link_index));
+                in_degree[target] += 1;
+            }
+        });
+
+        // Kahn's algorithm. Only active links impose an ordering.
+        let mut queue: VecDeque<usize> = (0..node_count).filter(|&i| in_degree[i] == 0).collect();
+        let mut order = Vec::with_capacity(node_count);
+        while let Some(node) = queue.pop_front() {
+            order.push(node);
+            graph.each_active_forward_link_of_node(CppnNodeIndex::new(node), |target_idx, _| {
+                let target = target_idx.index();
+                in_degree[target] -= 1;
+                if in_degree[target] == 0 {
+                    queue.push_back(target);
+                }
+            });
+        }
+        assert!(order.len() == node_count, "CppnGraph contains a cycle");
+
+        let mut link_offsets = Vec::with_capacity(node_count + 1);
+        let mut links = Vec::new();
+        let mut link_indices = Vec::new();
+        link_offsets.push(0);
+        for &node in order.iter() {
+            for &(link, link_index) in incoming_links[node].iter() {
+                links.push(link);
+                link_indices.push(link_index);
+            }
+            link_offsets.push(links.len());
+        }
+
+        CompiledCppn {
+            node_types,
+            aggregations,
+            order,
+            link_offsets,
+            links,
+            link_indices,
+            inputs,
+            outputs,
+            biases,
+            bias_value: F::one(),
+        }
+    }
+
+    /// Returns the number of nodes. Signal arrays passed to `process` must have this length.
+    pub fn node_count(&self) -> usize {
+        self.node_types.len()
+    }
+
+    /// Returns the number of input nodes, i.e. the number of input values `process` expects.
+    /// Bias nodes are not included.
+    pub fn input_count(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn bias_count(&self) -> usize {
+        self.biases.len()
+    }
+
+    pub fn output_count(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// The node indices of all input nodes.
+    pub fn inputs(&self) -> &[usize] {
+        &self.inputs
+    }
+
+    /// The node indices of all output nodes.
+    pub fn outputs(&self) -> &[usize] {
+        &self.outputs
+    }
+
+    /// The node indices of all bias nodes.
+    pub fn biases(&self) -> &[usize] {
+        &self.biases
+    }
+
+    /// The value fed into all bias nodes, `1.0` by default.
+    pub fn bias_value(&self) -> F {
+        self.bias_value
+    }
+
+    /// Sets the value fed into all bias nodes. Like an input value, it is passed through the
+    /// activation function of the bias node.
+    pub fn set_bias_value(&mut self, bias_value: F) {
+        self.bias_value = bias_value;
+    }
+
+    /// The node indices in evaluation (topological) order.
+    pub fn order(&self) -> &[usize] {
+        &self.order
+    }
+
+    /// All active links, grouped by target node in evaluation order.
+    pub fn links(&self) -> &[CompiledLink<F>] {
+        &self.links
+    }
+
+    /// The index in the graph of each of `links()`.
+    pub fn link_indices(&self) -> &[LinkIndex] {
+        &self.link_indices
+    }
+
+    /// Sets the weight of the `k`-th link of `links()`.
+    pub fn set_link_weight(&mut self, k: usize, weight: F) {
+        self.links[k].weight = weight;
+    }
+
+    /// The incoming links of the `nth` node in evaluation order.
+    pub fn incoming_links(&self, nth: usize) -> &[CompiledLink<F>] {
+        &self.links[self.link_offsets[nth]..self.link_offsets[nth + 1]]
+    }
+
+    pub fn node_type(&self, node: usize) -> &N {
+        &self.node_types[node]
+    }
+
+    /// The aggregation of the incoming signals of `node`.
+    pub fn aggregation(&self, node: usize) -> Aggregation {
+        self.aggregations[node]
+    }
+
+    /// Evaluates the network for the given `inputs`.
+    ///
+    /// On return, `incoming[i]` contains the aggregated weighted input signals of node `i` and
+    /// `outgoing[i]` the output of its activation function.
+    pub fn process(&self, inputs: &[&[F]], incoming: &mut [F], outgoing: &mut [F]) {
+        self.assign_inputs(inputs, incoming, outgoing);
+        for nth in 0..self.order.len() {
+            self.evaluate_nth(nth, incoming, outgoing);
+        }
+    }
+
+    /// Like `process`, but only evaluates the nodes of `cone`. On return, only the signals of
+    /// these nodes are valid.
+    pub fn process_cone(
+        &self,
+        cone: &OutputCone,
+        inputs: &[&[F]],
+        incoming: &mut [F],
+        outgoing: &mut [F],
+    ) {
+        self.assign_inputs(inputs, incoming, outgoing);
+        for &nth in cone.steps.iter() {
+            self.evaluate_nth(nth, incoming, outgoing);
+        }
+    }
+
+    /// Determines the nodes that the outputs `nth_outputs` (indices into `outputs()`) depend
+    /// on, i.e. the backward-reachable cone of these outputs.
+    pub fn output_cone(&self, nth_outputs: &[usize]) -> OutputCone {
+        let mut position = vec![0; self.node_count()];
+        for (nth, &node) in self.order.iter().enumerate() {
+            position[node] = nth;
+        }
+
+        let mut needed = vec![false; self.node_count()];
+        let mut stack = Vec::new();
+        for &nth_output in nth_outputs.iter() {
+            assert!(
+                nth_output < self.output_count(),
+                "output {} does not exist",
+                nth_output
+            );
+            stack.push(self.outputs[nth_output]);
+        }
+        while let Some(node) = stack.pop() {
+            if needed[node] {
+                continue;
+            }
+            needed[node] = true;
+            for link in self.incoming_links(position[node]) {
+                stack.push(link.source);
+            }
+        }
+
+        OutputCone {
+            outputs: nth_outputs.to_vec(),
+            steps: (0..self.order.len())
+                .filter(|&nth| needed[self.order[nth]])
+                .collect(),
+        }
+    }
+
+    /// Zeroes `incoming` and assigns `inputs` to the input nodes and the bias value to the bias
+    /// nodes.
+    fn assign_inputs(&self, inputs: &[&[F]], incoming: &mut [F], outgoing: &[F]) {
+        assert!(incoming.len() == self.node_count());
+        assert!(outgoing.len() == self.node_count());
+
+        let input_count = count_inputs(inputs);
+        assert!(
+            input_count == self.input_count(),
+            "{}",
+            CppnError::InputCountMismatch {
+                expected: self.input_count(),
+                actual: input_count,
+            }
+        );
+
+        for value in incoming.iter_mut() {
+            *value = F::zero();
+        }
+
+        // assign all inputs
+        let mut i = 0;
+        for input_list in inputs.iter() {
+            for &input in input_list.iter() {
+                incoming[self.inputs[i]] = input;
+                i += 1;
+            }
+        }
+        for &node in self.biases.iter() {
+            incoming[node] = self.bias_value;
+        }
+    }
+
+    /// Evaluates the `nth` node in evaluation order.
+    #[inline]
+    fn evaluate_nth(&self, nth: usize, incoming: &mut [F], outgoing: &mut [F]) {
+        let node = self.order[nth];
+        let mut sum = incoming[node];
+        let links = self.incoming_links(nth);
+        match self.aggregations[node] {
+            Aggregation::Sum => {
+                for link in links {
+                    sum += link.weight * outgoing[link.source];
+                }
+            }
+            _ if links.is_empty() => {}
+            aggregation => {
+                sum += aggregation.aggregate(links.iter().map(|l| l.weight * outgoing[l.source]));
+            }
+        }
+        incoming[node] = sum;
+        outgoing[node] = self.node_types[node].calculate(sum);
+    }
+
+    /// The partial derivatives of the aggregated input of the `nth` node with respect to each of
+    /// its weighted incoming signals, or `None` if the node sums them (all partials are 1.0).
+    /// `scratch` is resized to hold the weighted signals and the partials.
+    fn aggregation_partials<'s>(
+        &self,
+        nth: usize,
+        outgoing: &[F],
+        scratch: &'s mut Vec<F>,
+    ) -> Option<&'s [F]> {
+        let node = self.order[nth];
+        if self.aggregations[node] == Aggregation::Sum {
+            return None;
+        }
+        let links = self.incoming_links(nth);
+        scratch.clear();
+        scratch.extend(links.iter().map(|l| l.weight * outgoing[l.source]));
+        scratch.resize(2 * links.len(), F::zero());
+        let (values, partials) = scratch.split_at_mut(links.len());
+        self.aggregations[node].partials(values, partials);
+        Some(partials)
+    }
+
+    /// Checks that the network can be evaluated for `inputs`.
+    pub fn check(&self, inputs: &[&[F]]) -> Result<(), CppnError> {
+        if self.node_count() == 0 {
+            return Err(CppnError::EmptyGraph);
+        }
+        if self.outputs.is_empty() {
+            return Err(CppnError::NoOutputs);
+        }
+        let input_count = count_inputs(inputs);
+        if input_count != self.input_count() {
+            return Err(CppnError::InputCountMismatch {
+                expected: self.input_count(),
+                actual: input_count,
+            });
+        }
+        Ok(())
+    }
+
+    /// Like `process`, but returns an error instead of panicking if `check` fails, and if any
+    /// output is not finite.
+    pub fn try_process(
+        &self,
+        inputs: &[&[F]],
+        incoming: &mut [F],
+        outgoing: &mut [F],
+    ) -> Result<(), CppnError> {
+        self.check(inputs)?;
+        self.process(inputs, incoming, outgoing);
+        match self.outputs.iter().position(|&node| !outgoing[node].is_finite()) {
+            Some(nth) => Err(CppnError::NonFiniteOutput { nth }),
+            None => Ok(()),
+        }
+    }
+
+    /// Evaluates the network for the given `inputs` using `buffers`, which are resized as
+    /// needed.
+    pub fn process_with(&self, inputs: &[&[F]], buffers: &mut EvalBuffers<F>) {
+        if buffers.incoming.len() != self.node_count() {
+            buffers.prepare(self.node_count());
+        }
+        self.process(inputs, &mut buffers.incoming, &mut buffers.outgoing);
+    }
+
+    /// Like `process`, but also computes the derivatives of the output of every node with respect
+    /// to every input (forward-mode differentiation).
+    ///
+    /// On return, `tangents[node * input_count() + i]` is the derivative of `outgoing[node]` with
+    /// respect to the `i`-th input value. `tangents` must hold `node_count() * input_count()`
+    /// values. `scratch` holds the partial derivatives of aggregations other than `Sum` and can
+    /// be reused between calls.
+    pub fn process_jacobian(
+        &self,
+        inputs: &[&[F]],
+        incoming: &mut [F],
+        outgoing: &mut [F],
+        tangents: &mut [F],
+        scratch: &mut Vec<F>,
+    ) {
+        let m = self.input_count();
+        assert!(tangents.len() == self.node_count() * m);
+
+        self.assign_inputs(inputs, incoming, outgoing);
+        for value in tangents.iter_mut() {
+            *value = F::zero();
+        }
+        for (i, &node) in self.inputs.iter().enumerate() {
+            tangents[node * m + i] = F::one();
+        }
+
+        for nth in 0..self.order.len() {
+            let node = self.order[nth];
+            // the tangent of the summed input, which is then scaled by the derivative of the
+            // activation function (chain rule).
+            let partials = self.aggregation_partials(nth, outgoing, scratch);
+            for (k, link) in self.incoming_links(nth).iter().enumerate() {
+                let weight = match partials {
+                    Some(partials) => partials[k] * link.weight,
+                    None => link.weight,
+                };
+                let (target, source) = lane_pair(tangents, m, node, link.source);
+                for (t, &s) in target.iter_mut().zip(source.iter()) {
+                    *t += weight * s;
+                }
+            }
+            self.evaluate_nth(nth, incoming, outgoing);
+            let derivative = self.node_types[node].derivative(incoming[node]);
+            for t in tangents[node * m..(node + 1) * m].iter_mut() {
+                *t *= derivative;
+            }
+        }
+    }
+
+    /// Computes the gradient of a loss with respect to the weights of all links (reverse-mode
+    /// differentiation, i.e. backpropagation).
+    ///
+    /// `incoming` and `outgoing` are the signals of a preceding `process` and `output_gradients`
+    /// the derivatives of the loss with respect to each output. The derivative with respect to
+    /// the weight of the `k`-th link of `links()` is added to `weight_gradients[k]`, so that
+    /// gradients can be accumulated over several samples. `node_gradients` is scratch space
+    /// holding the derivative with respect to the output of each node on return. `scratch` is
+    /// used as in `process_jacobian`.
+    pub fn backward(
+        &self,
+        incoming: &[F],
+        outgoing: &[F],
+        output_gradients: &[F],
+        node_gradients: &mut [F],
+        weight_gradients: &mut [F],
+        scratch: &mut Vec<F>,
+    ) {
+        assert!(output_gradients.len() == self.output_count());
+        assert!(node_gradients.len() == self.node_count());
+        assert!(weight_gradients.len() == self.links.len());
+
+        for value in node_gradients.iter_mut() {
+            *value = F::zero();
+        }
+        for (&node, &gradient) in self.outputs.iter().zip(output_gradients.iter()) {
+            node_gradients[node] += gradient;
+        }
+
+        for nth in (0..self.order.len()).rev() {
+            let node = self.order[nth];
+            // the derivative with respect to the summed input of the node.
+            let delta = node_gradients[node] * self.node_types[node].derivative(incoming[node]);
+            let partials = self.aggregation_partials(nth, outgoing, scratch);
+            let range = self.link_offsets[nth]..self.link_offsets[nth + 1];
+            for (k, (link, gradient)) in self.links[range.clone()]
+                .iter()
+                .zip(weight_gradients[range].iter_mut())
+                .enumerate()
+            {
+                // the derivative with respect to the weighted signal of the link.
+                let delta = match partials {
+                    Some(partials) => delta * partials[k],
+                    None => delta,
+                };
+                *gradient += delta * outgoing[link.source];
+                node_gradients[link.source] += delta * link.weight;
+            }
+        }
+    }
+
+    /// Like `process_jacobian`, using `buffers`, which are resized as needed.
+    pub fn process_jacobian_with(&self, inputs: &[&[F]], buffers: &mut EvalBuffers<F>) {
+        if buffers.incoming.len() != self.node_count() {
+            buffers.prepare(self.node_count());
+        }
+        buffers.tangents.clear();
+        buffers
+            .tangents
+            .resize(self.node_count() * self.input_count(), F::zero());
+        self.process_jacobian(
+            inputs,
+            &mut buffers.incoming,
+            &mut buffers.outgoing,
+            &mut buffers.tangents,
+            &mut buffers.partials,
+        );
+    }
+
+    /// Like `backward`, for the signals of the last `process_with` using `buffers`.
+    pub fn backward_with(
+        &self,
+        output_gradients: &[F],
+        node_gradients: &mut [F],
+        weight_gradients: &mut [F],
+        buffers: &mut EvalBuffers<F>,
+    ) {
+        self.backward(
+            &buffers.incoming,
+            &buffers.outgoing,
+            output_gradients,
+            node_gradients,
+            weight_gradients,
+            &mut buffers.partials,
+        );
+    }
+
+    /// Copies the derivatives of the outputs from `tangents` (see `process_jacobian`) into
+    /// `jacobian`, which holds `output_count()` rows of `input_count()` values.
+    pub fn read_jacobian(&self, tangents: &[F], jacobian: &mut [F]) {
+        let m = self.input_count();
+        assert!(jacobian.len() == self.output_count() * m);
+        for (row, &node) in jacobian.chunks_mut(m).zip(self.outputs.iter()) {
+            row.copy_from_slice(&tangents[node * m..(node + 1) * m]);
+        }
+    }
+
+    /// Like `process_with`, but reports errors as `try_process` does.
+    pub fn try_process_with(
+        &self,
+        inputs: &[&[F]],
+        buffers: &mut EvalBuffers<F>,
+    ) -> Result<(), CppnError> {
+        if buffers.incoming.len() != self.node_count() {
+            buffers.prepare(self.node_count());
+        }
+        self.try_process(inputs, &mut buffers.incoming, &mut buffers.outgoing)
+    }
+
+    /// Like `process_with`, but only evaluates the nodes `cone` depends on.
+    pub fn process_cone_with(
+        &self,
+        cone: &OutputCone,
+        inputs: &[&[F]],
+        buffers: &mut EvalBuffers<F>,
+    ) {
+        if buffers.incoming.len() != self.node_count() {
+            buffers.prepare(self.node_count());
+        }
+        self.process_cone(cone, inputs, &mut buffers.incoming, &mut buffers.outgoing);
+    }
+
+    /// Copies the outputs of `cone` from `outgoing` into `outputs`, which must hold one value
+    /// per requested output.
+    pub fn read_cone_outputs(&self, cone: &OutputCone, outgoing: &[F], outputs: &mut [F]) {
+        assert!(outputs.len() == cone.outputs.len());
+        for (output, &nth_output) in outputs.iter_mut().zip(cone.outputs.iter()) {
+            *output = outgoing[self.outputs[nth_output]];
+        }
+    }
+
+    /// Copies the outputs of the last evaluation from `outgoing` into `outputs`.
+    pub fn read_outputs(&self, outgoing: &[F], outputs: &mut [F]) {
+        assert!(outputs.len() == self.output_count());
+        for (output, &node) in outputs.iter_mut().zip(self.outputs.iter()) {
+            *output = outgoing[node];
+        }
+    }
+
+    /// Computes bounds on each output over the box of input vectors given by one interval per
+    /// input.
+    ///
+    /// Signals are propagated as intervals through the links (in evaluation order) and through
+    /// `ActivationFunction::calculate_interval`. Each returned interval contains the output of
+    /// `process` and `process_batch` for every input vector within the box, but may be wider
+    /// than the exact range, as correlations between signals that reach a node along different
+    /// paths are lost.
+    ///
+    /// Links and aggregations use the outward rounding of `Interval`. The bounds of each
+    /// activation function are widened by `INTERVAL_TOLERANCE` relative to the magnitude of
+    /// its argument and of its result, which covers the rounding errors of the scalar
+    /// functions as well as the approximations used by `process_batch` (see `kernels`). As the
+    /// widened bounds are propagated, so is the effect of these errors on later nodes.
+    pub fn process_interval(&self, inputs: &[Interval<F>]) -> Vec<Interval<F>> {
+        assert!(
+            inputs.len() == self.input_count(),
+            "{}",
+            CppnError::InputCountMismatch {
+                expected: self.input_count(),
+                actual: inputs.len(),
+            }
+        );
+
+        let tolerance = F::from_f64(INTERVAL_TOLERANCE * F::EPSILON);
+        let mut incoming = vec![Interval::point(F::zero()); self.node_count()];
+        let mut outgoing = incoming.clone();
+        for (&node, &input) in self.inputs.iter().zip(inputs.iter()) {
+            incoming[node] = input;
+        }
+        for &node in self.biases.iter() {
+            incoming[node] = Interval::point(self.bias_value);
+        }
+
+        for (nth, &node) in self.order.iter().enumerate() {
+            let weighted: Vec<Interval<F>> = self
+                .incoming_links(nth)
+                .iter()
+                .map(|link| outgoing[link.source].scale(link.weight))
+                .collect();
+            let mut sum = incoming[node];
+            if !weighted.is_empty() {
+                sum = sum + self.aggregations[node].aggregate_interval(&weighted);
+            }
+            let sum = sum.widen(tolerance * sum.magnitude());
+            let bounds = self.node_types[node].calculate_interval(sum);
+            outgoing[node] = bounds.widen(tolerance * (F::one() + bounds.magnitude()));
+        }
+
+        self.outputs.iter().map(|&node| outgoing[node]).collect()
+    }
+
+    /// Like `process_batch`, but uses the batch buffer of `buffers` as scratch space.
+    pub fn process_batch_with(
+        &self,
+        sample_count: usize,
+        inputs: &[F],
+        outputs: &mut [F],
+        buffers: &mut EvalBuffers<F>,
+    ) {
+        let mut scratch = mem::take(&mut buffers.batch);
+        self.process_batch(sample_count, inputs, outputs, &mut scratch);
+        buffers.batch = scratch;
+    }
+
+    /// Evaluates the network for a batch of `sample_count` input vectors at once.
+    ///
+    /// `inputs` is a structure-of-arrays block holding `sample_count` values for each input in
+    /// turn, i.e. the value of input `i` for sample `s` is `inputs[i * sample_count + s]`.
+    /// The outputs are written to `outputs` using the same layout.
+    ///
+    /// The network is evaluated node by node across the whole batch, using the vectorized
+    /// `ActivationFunction::calculate_slice`, so results may differ from `process` within the
+    /// error bounds documented in the `kernels` module. `scratch` is resized to hold one lane of
+    /// `sample_count` values per node and can be reused between calls.
+    pub fn process_batch(
+        &self,
+        sample_count: usize,
+        inputs: &[F],
+        outputs: &mut [F],
+        scratch: &mut Vec<F>,
+    ) {
+        let n = sample_count;
+        assert!(inputs.len() == n * self.input_count());
+        assert!(outputs.len() == n * self.output_count());
+
+        scratch.clear();
+        scratch.resize(n * self.node_count(), F::zero());
+
+        for (i, &node) in self.inputs.iter().enumerate() {
+            scratch[node * n..(node + 1) * n].copy_from_slice(&inputs[i * n..(i + 1) * n]);
+        }
+        for &node in self.biases.iter() {
+            for value in scratch[node * n..(node + 1) * n].iter_mut() {
+                *value = self.bias_value;
+            }
+        }
+
+        let mut values = Vec::new();
+        for (nth, &node) in self.order.iter().enumerate() {
+            let links = self.incoming_links(nth);
+            match self.aggregations[node] {
+                Aggregation::Sum => {
+                    for link in links {
+                        let (target, source) = lane_pair(scratch, n, node, link.source);
+                        let weight = link.weight;
+                        for (t, &s) in target.iter_mut().zip(source.iter()) {
+                            *t += weight * s;
+                        }
+                    }
+                }
+                _ if links.is_empty() => {}
+                aggregation => {
+                    for s in 0..n {
+                        values.clear();
+                        values.extend(links.iter().map(|l| l.weight * scratch[l.source * n + s]));
+                        scratch[node * n + s] += aggregation.aggregate(values.iter().cloned());
+                    }
+                }
+            }
+            self.node_types[node].calculate_slice(&mut scratch[node * n..(node + 1) * n]);
+        }
+
+        for (j, &node) in self.outputs.iter().enumerate() {
+            outputs[j * n..(j + 1) * n].copy_from_slice(&scratch[node * n..(node + 1) * n]);
+        }
+    }
+}
+
+fn count_inputs<F>(inputs: &[&[F]]) -> usize {
+    inputs.iter().map(|input_list| input_list.len()).sum()
+}
+
+/// Returns the lane of node `target` mutably and the lane of node `source` immutably.
+fn lane_pair<F>(lanes: &mut [F], n: usize, target: usize, source: usize) -> (&mut [F], &[F]) {
+    assert!(target != source);
+    if source < target {
+        let (left, right) = lanes.split_at_mut(target * n);
+        (&mut right[..n], &left[source * n..(source + 1) * n])
+    } else {
+        let (left, right) = lanes.split_at_mut(source * n);
+        (&mut left[target * n..(target + 1) * n], &right[..n])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BufferPool, CompiledCppn, EvalBuffers, OutputCone};
+    use activation_function::{GeometricActivationFunction as AF, ALL_FUNCTIONS};
+    use acyclic_network::ExternalId;
+    use aggregation::Aggregation;
+    use cppn::{CppnGraph, CppnNode};
+    use float::Float;
+    use interval::Interval;
+
+    #[test]
+    fn test_topological_order() {
+        let mut g = CppnGraph::new();
+        let o1 = g.add_node(CppnNode::output(AF::Linear), ExternalId(1));
+        let h1 = g.add_node(CppnNode::hidden(AF::Linear), ExternalId(2));
+        let h2 = g.add_node(CppnNode::hidden(AF::Linear), ExternalId(3));
+        let i1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(4));
+        g.add_link(i1, h2, 1.0, ExternalId(1));
+        g.add_link(h2, h1, 1.0, ExternalId(2));
+        g.add_link(h1, o1, 1.0, ExternalId(3));
+        g.add_link(i1, o1, 1.0, ExternalId(4));
+
+        let compiled = CompiledCppn::new(&g);
+        assert_eq!(&[3, 2, 1, 0], compiled.order());
+        assert_eq!(4, compiled.links().len());
+        assert_eq!(2, compiled.incoming_links(3).len());
+    }
+
+    #[test]
+    fn test_process_diamond() {
+        let mut g = CppnGraph::new();
+        let i1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let h1 = g.add_node(CppnNode::hidden(AF::Linear), ExternalId(2));
+        let h2 = g.add_node(CppnNode::hidden(AF::Absolute), ExternalId(3));
+        let o1 = g.add_node(CppnNode::output(AF::Linear), ExternalId(4));
+        g.add_link(i1, h1, 2.0, ExternalId(1));
+        g.add_link(i1, h2, -3.0, ExternalId(2));
+        g.add_link(h1, h2, 0.5, ExternalId(3));
+        g.add_link(h2, o1, 3.0, ExternalId(4));
+        g.add_link(h1, o1, 1.0, ExternalId(5));
+
+        let compiled = CompiledCppn::new(&g);
+        let mut incoming = vec![0.0; 4];
+        let mut outgoing = vec![0.0; 4];
+
+        let f = |x: f64| 2.0 * x + 3.0 * (-3.0 * x + 0.5 * (2.0 * x)).abs();
+        for &x in &[0.5, -2.0, 4.0] {
+            compiled.process(&[&[x]], &mut incoming, &mut outgoing);
+            assert_eq!(f(x), outgoing[o1.index()]);
+        }
+    }
+
+    #[test]
+    fn test_output_cone() {
+        let mut g = CppnGraph::new();
+        let i1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let i2 = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let h1 = g.add_node(CppnNode::hidden(AF::Absolute), ExternalId(3));
+        let h2 = g.add_node(CppnNode::hidden(AF::Linear), ExternalId(4));
+        let o1 = g.add_node(CppnNode::output(AF::Linear), ExternalId(5));
+        let o2 = g.add_node(CppnNode::output(AF::Linear), ExternalId(6));
+        g.add_link(i1, h1, 2.0, ExternalId(1));
+        g.add_link(h1, o1, 1.0, ExternalId(2));
+        g.add_link(i2, h2, 3.0, ExternalId(3));
+        g.add_link(h2, o2, 1.0, ExternalId(4));
+        g.add_link(i1, o2, 1.0, ExternalId(5));
+
+        let compiled = CompiledCppn::new(&g);
+        let nodes = |cone: &OutputCone| -> Vec<usize> {
+            let mut nodes: Vec<_> = cone.steps().iter().map(|&nth| compiled.order()[nth]).collect();
+            nodes.sort();
+            nodes
+        };
+        let cone = compiled.output_cone(&[0]);
+        assert_eq!(vec![i1.index(), h1.index(), o1.index()], nodes(&cone));
+        assert_eq!(
+            vec![i1.index(), i2.index(), h2.index(), o2.index()],
+            nodes(&compiled.output_cone(&[1]))
+        );
+        assert_eq!(6, compiled.output_cone(&[1, 0]).steps().len());
+        assert!(compiled.output_cone(&[]).steps().is_empty());
+
+        let mut buffers = EvalBuffers::new();
+        let mut outputs = [0.0];
+        compiled.process_cone_with(&cone, &[&[-1.0, 5.0]], &mut buffers);
+        compiled.read_cone_outputs(&cone, buffers.outgoing(), &mut outputs);
+        assert_eq!([2.0], outputs);
+        // nodes outside of the cone are not evaluated.
+        assert_eq!(0.0, buffers.outgoing()[h2.index()]);
+        assert_eq!(0.0, buffers.outgoing()[o2.index()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "output 2 does not exist")]
+    fn test_output_cone_invalid_output() {
+        let mut g = CppnGraph::new();
+        let i1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let o1 = g.add_node(CppnNode::output(AF::Linear), ExternalId(2));
+        g.add_link(i1, o1, 1.0, ExternalId(1));
+        CompiledCppn::new(&g).output_cone(&[0, 2]);
+    }
+
+    #[test]
+    fn test_disabled_links_are_skipped() {
+        let mut g = CppnGraph::new();
+        let i1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let o1 = g.add_node(CppnNode::output(AF::Linear), ExternalId(2));
+        g.add_link(i1, o1, 2.0, ExternalId(1));
+        g.disable_link(i1, o1);
+
+        let compiled = CompiledCppn::new(&g);
+        assert_eq!(0, compiled.links().len());
+
+        let mut incoming = vec![0.0; 2];
+        let mut outgoing = vec![0.0; 2];
+        compiled.process(&[&[1.0]], &mut incoming, &mut outgoing);
+        assert_eq!(0.0, outgoing[o1.index()]);
+    }
+
+    #[test]
+    fn test_process_batch() {
+        let mut g = CppnGraph::new();
+        let i1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let i2 = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let h1 = g.add_node(CppnNode::hidden(AF::Gaussian), ExternalId(3));
+        let o1 = g.add_node(CppnNode::output(AF::BipolarSigmoid), ExternalId(4));
+        let o2 = g.add_node(CppnNode::output(AF::Absolute), ExternalId(5));
+        g.add_link(i1, h1, 0.7, ExternalId(1));
+        g.add_link(i2, h1, -0.3, ExternalId(2));
+        g.add_link(h1, o1, 1.5, ExternalId(3));
+        g.add_link(i2, o1, 0.5, ExternalId(4));
+        g.add_link(h1, o2, -2.0, ExternalId(5));
+
+        let compiled = CompiledCppn::new(&g);
+        let samples = [(0.0, 0.0), (0.5, -1.0), (-0.25, 0.75)];
+        let n = samples.len();
+
+        let mut inputs = vec![0.0; 2 * n];
+        for (s, &(x, y)) in samples.iter().enumerate() {
+            inputs[s] = x;
+            inputs[n + s] = y;
+        }
+        let mut outputs = vec![0.0; 2 * n];
+        let mut scratch = Vec::new();
+        compiled.process_batch(n, &inputs, &mut outputs, &mut scratch);
+
+        let mut incoming = vec![0.0; 5];
+        let mut outgoing = vec![0.0; 5];
+        for (s, &(x, y)) in samples.iter().enumerate() {
+            compiled.process(&[&[x, y]], &mut incoming, &mut outgoing);
+            assert!((outgoing[o1.index()] - outputs[s]).abs() < 1e-14);
+            assert!((outgoing[o2.index()] - outputs[n + s]).abs() < 1e-14);
+        }
+    }
+
+    fn check_interval_contains_batch<F: Float>() {
+        // one hidden node for each activation function, feeding the outputs, which apply
+        // another function to a sum or product.
+        let mut g = CppnGraph::new();
+        let x = g.add_node(CppnNode::input(AF::Linear), ExternalId(0));
+        let y = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let b = g.add_node(CppnNode::bias(AF::Linear), ExternalId(2));
+        let n = ALL_FUNCTIONS.len();
+        let mut link_id = 0;
+        let mut link = |g: &mut CppnGraph<_, _, _>, source, target, weight| {
+            link_id += 1;
+            g.add_link(source, target, weight, ExternalId(link_id));
+        };
+        let hidden: Vec<_> = ALL_FUNCTIONS
+            .iter()
+            .enumerate()
+            .map(|(k, &f)| {
+                let h = g.add_node(CppnNode::hidden(f), ExternalId(3 + k));
+                link(&mut g, x, h, 1.5);
+                link(&mut g, y, h, -0.8);
+                link(&mut g, b, h, 0.2917);
+                h
+            })
+            .collect();
+        for k in 0..n {
+            let aggregation = if k % 3 == 1 {
+                Aggregation::Product
+            } else {
+                Aggregation::Sum
+            };
+            let f = ALL_FUNCTIONS[(k + 7) % n];
+            let o = g.add_node(
+                CppnNode::output(f).with_aggregation(aggregation),
+                ExternalId(3 + n + k),
+            );
+            link(&mut g, hidden[k], o, 1.2);
+            link(&mut g, hidden[(k + 1) % n], o, -0.6);
+            link(&mut g, x, o, 1.0);
+        }
+
+        let compiled = CompiledCppn::<_, F>::compile(&g);
+        let mut boxes = vec![
+            ((-1.0, 1.0), (-1.0, 1.0)),
+            ((0.0, 0.1), (0.5, 0.6)),
+            ((-0.8, -0.7), (-0.3, 0.2)),
+            ((-1e-3, 1e-3), (0.3, 0.3)),
+        ];
+        // point boxes are most sensitive to rounding and approximation errors.
+        for i in 0..12 {
+            for j in 0..12 {
+                let (px, py) = (-1.1 + 0.187 * (i as f64), -0.9 + 0.163 * (j as f64));
+                boxes.push(((px, px), (py, py)));
+            }
+        }
+        let steps = 16;
+        let samples = (steps + 1) * (steps + 1);
+        let mut scratch = Vec::new();
+        for &((x_lo, x_hi), (y_lo, y_hi)) in boxes.iter() {
+            let bounds = compiled.process_interval(&[
+                Interval::new(F::from_f64(x_lo), F::from_f64(x_hi)),
+                Interval::new(F::from_f64(y_lo), F::from_f64(y_hi)),
+            ]);
+            let mut inputs = vec![F::zero(); 2 * samples];
+            for s in 0..samples {
+                let (i, j) = (s / (steps + 1), s % (steps + 1));
+                inputs[s] = F::from_f64(x_lo + (x_hi - x_lo) * (i as f64) / (steps as f64));
+                inputs[samples + s] =
+                    F::from_f64(y_lo + (y_hi - y_lo) * (j as f64) / (steps as f64));
+            }
+            let mut outputs = vec![F::zero(); n * samples];
+            compiled.process_batch(samples, &inputs, &mut outputs, &mut scratch);
+            for (k, bound) in bounds.iter().enumerate() {
+                for s in 0..samples {
+                    let output = outputs[k * samples + s];
+                    assert!(
+                        bound.contains(output),
+                        "{:?} {:?} {:?} ({:?}, {:?})",
+                        ALL_FUNCTIONS[(k + 7) % n],
+                        bound,
+                        output,
+                        inputs[s],
+                        inputs[samples + s]
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_interval_contains_batch() {
+        check_interval_contains_batch::<f64>();
+        check_interval_contains_batch::<f32>();
+    }
+
+    #[test]
+    fn test_buffers_reused_across_graphs() {
+        let mut g1 = CppnGraph::new();
+        let i1 = g1.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let o1 = g1.add_node(CppnNode::output(AF::Linear), ExternalId(2));
+        g1.add_link(i1, o1, 2.0, ExternalId(1));
+
+        let mut g2 = CppnGraph::new();
+        let i1 = g2.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let h1 = g2.add_node(CppnNode::hidden(AF::Linear), ExternalId(2));
+        let o1 = g2.add_node(CppnNode::output(AF::Linear), ExternalId(3));
+        g2.add_link(i1, h1, 3.0, ExternalId(1));
+        g2.add_link(h1, o1, 0.5, ExternalId(2));
+
+        let pool = BufferPool::new();
+        let mut buffers = pool.acquire();
+        let mut output = [0.0];
+
+        let c1 = CompiledCppn::new(&g1);
+        c1.process_with(&[&[1.5]], &mut buffers);
+        c1.read_outputs(buffers.outgoing(), &mut output);
+        assert_eq!([3.0], output);
+
+        let c2 = CompiledCppn::new(&g2);
+        c2.process_with(&[&[1.5]], &mut buffers);
+        c2.read_outputs(buffers.outgoing(), &mut output);
+        assert_eq!([2.25], output);
+        assert_eq!(3, buffers.incoming().len());
+
+        pool.release(buffers);
+        assert_eq!(1, pool.len());
+        let _: EvalBuffers = pool.acquire();
+        assert!(pool.is_empty());
+    }
+}
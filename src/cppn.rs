@@ -1,12 +1,33 @@
 use activation_function::ActivationFunction;
+use aggregation::Aggregation;
 use acyclic_network::{Network, NodeType};
 pub use acyclic_network::NodeIndex as CppnNodeIndex;
-use fixedbitset::FixedBitSet;
-use std::fmt::Debug;
+use compiled::{CompiledCppn, EvalBuffers};
+use evaluator::CppnEvaluator;
+use expr::{CppnExpr, Expr};
+use float::Float;
+use interval::Interval;
+use std::error::Error;
+use std::fmt::{self, Debug};
+use signature::{BoundSignature, CppnSignature};
+use std::sync::Arc;
+use trace::{CppnTrace, NodeTrace};
 
 pub trait CppnNodeType: NodeType + ActivationFunction {
     fn is_input_node(&self) -> bool;
     fn is_output_node(&self) -> bool;
+
+    /// Bias nodes are not fed from the inputs, but from a constant (see
+    /// `CompiledCppn::set_bias_value`).
+    fn is_bias_node(&self) -> bool {
+        false
+    }
+
+    /// How the weighted incoming signals are combined before the activation function is
+    /// applied.
+    fn aggregation(&self) -> Aggregation {
+        Aggregation::Sum
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -18,12 +39,33 @@ pub enum CppnNodeKind {
 }
 
 /// A concrete implementation of a CppnNodeType.
-#[derive(Clone, Debug, PartialEq, Eq)]
+///
+/// The node computes `activation_function(bias + response * x)`, where `x` is its aggregated
+/// input.
+#[derive(Clone, Debug)]
 pub struct CppnNode<A: ActivationFunction> {
     pub kind: CppnNodeKind,
     pub activation_function: A,
+    pub aggregation: Aggregation,
+    /// Added to the input of the activation function, 0.0 by default.
+    pub bias: f64,
+    /// The gain of the input of the activation function, 1.0 by default.
+    pub response: f64,
+}
+
+// `bias` and `response` are compared bitwise, so that equality is reflexive (`Eq`).
+impl<A: ActivationFunction> PartialEq for CppnNode<A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+            && self.activation_function == other.activation_function
+            && self.aggregation == other.aggregation
+            && self.bias.to_bits() == other.bias.to_bits()
+            && self.response.to_bits() == other.response.to_bits()
+    }
 }
 
+impl<A: ActivationFunction> Eq for CppnNode<A> {}
+
 impl<A> CppnNode<A>
 where
     A: ActivationFunction,
@@ -32,6 +74,9 @@ where
         CppnNode {
             kind: kind,
             activation_function: activation_function,
+            aggregation: Aggregation::Sum,
+            bias: 0.0,
+            response: 1.0,
         }
     }
 
@@ -50,10 +95,70 @@ where
     pub fn bias(activation_function: A) -> Self {
         Self::new(CppnNodeKind::Bias, activation_function)
     }
+
+    /// Sets the aggregation of the incoming signals, which is `Aggregation::Sum` by default.
+    pub fn with_aggregation(mut self, aggregation: Aggregation) -> Self {
+        self.aggregation = aggregation;
+        self
+    }
+
+    pub fn aggregation(&self) -> Aggregation {
+        self.aggregation
+    }
+
+    /// Changes the aggregation of the incoming signals, e.g. when mutating the node (see
+    /// `Aggregation::ALL`).
+    pub fn set_aggregation(&mut self, aggregation: Aggregation) {
+        self.aggregation = aggregation;
+    }
+
+    /// Sets the bias, which is 0.0 by default. The node computes
+    /// `activation_function(bias + response * x)` of its aggregated input `x`.
+    pub fn with_bias(mut self, bias: f64) -> Self {
+        self.bias = bias;
+        self
+    }
+
+    /// Sets the response, which is 1.0 by default. The node computes
+    /// `activation_function(bias + response * x)` of its aggregated input `x`.
+    pub fn with_response(mut self, response: f64) -> Self {
+        self.response = response;
+        self
+    }
+
+    /// The bias, e.g. to perturb it in a mutation operator.
+    pub fn bias_mut(&mut self) -> &mut f64 {
+        &mut self.bias
+    }
+
+    /// The response, e.g. to perturb it in a mutation operator.
+    pub fn response_mut(&mut self) -> &mut f64 {
+        &mut self.response
+    }
+
+    /// Whether `bias` and `response` have their default values, so that the node computes
+    /// `activation_function(x)`.
+    pub fn is_unscaled(&self) -> bool {
+        self.bias == 0.0 && self.response == 1.0
+    }
+
+    /// The input of the activation function, `bias + response * x`.
+    fn activation_input<F: Float>(&self, x: F) -> F {
+        if self.is_unscaled() {
+            x
+        } else {
+            F::from_f64(self.bias) + F::from_f64(self.response) * x
+        }
+    }
 }
 
 impl<A: ActivationFunction> ActivationFunction for CppnNode<A> {
     fn formula_gnuplot(&self, x: String) -> String {
+        let x = if self.is_unscaled() {
+            x
+        } else {
+            format!("({:?} + {:?} * ({}))", self.bias, self.response, x)
+        };
         match self.kind {
             CppnNodeKind::Input
             | CppnNodeKind::Output
@@ -66,8 +171,49 @@ impl<A: ActivationFunction> ActivationFunction for CppnNode<A> {
         self.activation_function.name()
     }
 
-    fn calculate(&self, input: f64) -> f64 {
-        self.activation_function.calculate(input)
+    fn calculate<F: Float>(&self, input: F) -> F {
+        self.activation_function.calculate(self.activation_input(input))
+    }
+
+    fn calculate_slice<F: Float>(&self, xs: &mut [F]) {
+        if !self.is_unscaled() {
+            for x in xs.iter_mut() {
+                *x = self.activation_input(*x);
+            }
+        }
+        self.activation_function.calculate_slice(xs)
+    }
+
+    fn derivative<F: Float>(&self, input: F) -> F {
+        let derivative = self.activation_function.derivative(self.activation_input(input));
+        if self.response == 1.0 {
+            derivative
+        } else {
+            F::from_f64(self.response) * derivative
+        }
+    }
+
+    fn calculate_interval<F: Float>(&self, input: Interval<F>) -> Interval<F> {
+        let input = if self.is_unscaled() {
+            input
+        } else {
+            Interval::point(F::from_f64(self.bias)) + input.scale(F::from_f64(self.response))
+        };
+        self.activation_function.calculate_interval(input)
+    }
+
+    fn expr(&self, x: Expr) -> Expr {
+        let x = if self.response == 1.0 {
+            x
+        } else {
+            Expr::Const(self.response) * x
+        };
+        let x = if self.bias == 0.0 {
+            x
+        } else {
+            Expr::Const(self.bias) + x
+        };
+        self.activation_function.expr(x)
     }
 }
 
@@ -100,6 +246,15 @@ impl<A: ActivationFunction> CppnNodeType for CppnNode<A> {
             _ => false,
         }
     }
+    fn is_bias_node(&self) -> bool {
+        match self.kind {
+            CppnNodeKind::Bias => true,
+            _ => false,
+        }
+    }
+    fn aggregation(&self) -> Aggregation {
+        self.aggregation
+    }
 }
 
 pub type CppnGraph<N, L, EXTID>
@@ -109,26 +264,90 @@ where
     EXTID: Copy + Debug + Send + Sized + Ord,
 = Network<N, L, EXTID>;
 
+/// Errors reported by the fallible evaluation methods (`try_process`, `try_calculate`) and by
+/// evaluation by name (see `CppnSignature`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CppnError {
+    /// The number of input values does not match the number of input nodes.
+    InputCountMismatch { expected: usize, actual: usize },
+    /// The graph does not contain any nodes.
+    EmptyGraph,
+    /// The graph does not contain any output nodes.
+    NoOutputs,
+    /// The `nth` output evaluated to NaN or infinity.
+    NonFiniteOutput { nth: usize },
+    /// No signature was set, so inputs and outputs cannot be addressed by name.
+    NoSignature,
+    /// A label is used more than once.
+    DuplicateLabel { label: String },
+    /// The node labeled `label` does not exist or is not an input (output) node.
+    UnknownNode { label: String },
+    /// The node labeled `label` already has another label.
+    NodeLabeledTwice { label: String },
+    /// The `nth` input node is not labeled by the signature.
+    UnlabeledInput { nth: usize },
+    /// The signature does not contain `label`.
+    UnknownLabel { label: String },
+    /// No value was given for the input labeled `label`.
+    MissingInput { label: String },
+    /// The signature was bound to a graph with a different number of inputs or outputs.
+    SignatureMismatch,
+}
+
+impl fmt::Display for CppnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CppnError::InputCountMismatch { expected, actual } => write!(
+                f,
+                "expected {} input values, but {} were given",
+                expected, actual
+            ),
+            CppnError::EmptyGraph => write!(f, "the CPPN graph is empty"),
+            CppnError::NoOutputs => write!(f, "the CPPN graph has no output nodes"),
+            CppnError::NonFiniteOutput { nth } => write!(f, "output {} is not finite", nth),
+            CppnError::NoSignature => write!(f, "the CPPN has no signature"),
+            CppnError::DuplicateLabel { ref label } => {
+                write!(f, "label {:?} is used more than once", label)
+            }
+            CppnError::UnknownNode { ref label } => write!(
+                f,
+                "the node labeled {:?} is not an input or output node of the graph",
+                label
+            ),
+            CppnError::NodeLabeledTwice { ref label } => {
+                write!(f, "the node labeled {:?} already has another label", label)
+            }
+            CppnError::UnlabeledInput { nth } => write!(f, "input {} is not labeled", nth),
+            CppnError::UnknownLabel { ref label } => write!(f, "unknown label {:?}", label),
+            CppnError::MissingInput { ref label } => {
+                write!(f, "no value given for input {:?}", label)
+            }
+            CppnError::SignatureMismatch => {
+                write!(f, "the signature was bound to a different graph")
+            }
+        }
+    }
+}
+
+impl Error for CppnError {}
+
 /// Represents a Compositional Pattern Producing Network (CPPN)
-pub struct Cppn<'a, N, L, EXTID>
+///
+/// Signals are of type `F`, which defaults to `f64`. Use `with_buffers` to create a `Cppn` for
+/// another `Float` type, e.g. `Cppn::with_buffers(&graph, EvalBuffers::<f32>::new())`.
+pub struct Cppn<'a, N, L, EXTID, F = f64>
 where
     N: CppnNodeType + 'a,
     L: Copy + Debug + Send + Sized + Into<f64> + 'a,
     EXTID: Copy + Debug + Send + Sized + Ord + 'a,
+    F: Float,
 {
     graph: &'a CppnGraph<N, L, EXTID>,
-    inputs: Vec<CppnNodeIndex>,
-    outputs: Vec<CppnNodeIndex>,
-    start_nodes: Vec<CppnNodeIndex>,
-
-    // nodes array and bitarray used in BFS
-    nodes_bfs: Vec<CppnNodeIndex>,
-    seen_bfs: FixedBitSet,
-
-    // For each node in `graph` there exists a corresponding field in `incoming_signals` describing
-    // the sum of all input signals for that node.  We could store it inline in the `CppnNode`, but
-    // this would require to make the whole CppnGraph mutable.
-    incoming_signals: Vec<f64>,
+
+    // Holds the compiled graph and, for each node in `graph`, the sum of all input signals for
+    // that node and the output of its activation function. We could store it inline in the
+    // `CppnNode`, but this would require to make the whole CppnGraph mutable.
+    evaluator: CppnEvaluator<N, F>,
 }
 
 impl<'a, N, L, EXTID> Cppn<'a, N, L, EXTID>
@@ -138,132 +357,239 @@ where
     EXTID: Copy + Debug + Send + Sized + Ord + 'a,
 {
     pub fn new(graph: &'a CppnGraph<N, L, EXTID>) -> Cppn<'a, N, L, EXTID> {
-        let mut inputs = Vec::new();
-        let mut outputs = Vec::new();
-        let mut start_nodes = Vec::new();
-
-        graph.each_node_with_index(|node, index| {
-            if node.node_type().is_input_node() {
-                inputs.push(index);
-            }
-            if node.node_type().is_output_node() {
-                outputs.push(index);
-            }
-            if node.in_degree() == 0 {
-                start_nodes.push(index);
-            }
-        });
-
-        let incoming_signals: Vec<_> = graph.nodes().iter().map(|_| 0.0).collect();
-        let seen_bfs = FixedBitSet::with_capacity(incoming_signals.len());
+        Self::with_buffers(graph, EvalBuffers::new())
+    }
+}
 
+impl<'a, N, L, EXTID, F> Cppn<'a, N, L, EXTID, F>
+where
+    N: CppnNodeType + 'a,
+    L: Copy + Debug + Send + Sized + Into<f64> + 'a,
+    EXTID: Copy + Debug + Send + Sized + Ord + 'a,
+    F: Float,
+{
+    /// Creates a `Cppn` which evaluates `graph` using the given (possibly previously used)
+    /// buffers. Use `into_buffers` to get them back.
+    pub fn with_buffers(
+        graph: &'a CppnGraph<N, L, EXTID>,
+        buffers: EvalBuffers<F>,
+    ) -> Cppn<'a, N, L, EXTID, F> {
+        let compiled = Arc::new(CompiledCppn::compile(graph));
         Cppn {
-            graph: graph,
-            inputs: inputs,
-            outputs: outputs,
-            start_nodes: start_nodes,
-            nodes_bfs: Vec::new(),
-            seen_bfs: seen_bfs,
-            incoming_signals: incoming_signals,
+            graph,
+            evaluator: CppnEvaluator::with_buffers(compiled, buffers),
         }
     }
 
-    fn set_signal(&mut self, node_idx: CppnNodeIndex, value: f64) {
-        self.incoming_signals[node_idx.index()] = value;
+    /// Consumes the `Cppn` and returns its evaluation buffers for reuse.
+    pub fn into_buffers(self) -> EvalBuffers<F> {
+        self.evaluator.into_buffers()
     }
 
-    fn reset_signals(&mut self) {
-        for value in self.incoming_signals.iter_mut() {
-            *value = 0.0;
-        }
+    /// The evaluator of the graph, e.g. to fork it (see `CppnEvaluator::fork`).
+    pub fn evaluator(&self) -> &CppnEvaluator<N, F> {
+        &self.evaluator
+    }
+
+    /// Consumes the `Cppn` and returns an evaluator which no longer borrows the graph.
+    pub fn into_evaluator(self) -> CppnEvaluator<N, F> {
+        self.evaluator
     }
 
-    pub fn incoming_signals(&self) -> &[f64] {
-        &self.incoming_signals
+    /// Returns the compiled (topologically sorted) form of the graph.
+    pub fn compiled(&self) -> &CompiledCppn<N, F> {
+        self.evaluator.compiled()
     }
 
-    /// Forward-propagate the signals starting from `from_nodes`. We use
-    /// breadth-first-search (BFS).
-    fn propagate_signals(&mut self) {
-        while let Some(node_idx) = self.nodes_bfs.pop() {
-            let input = self.incoming_signals[node_idx.index()];
-            let output = self.graph.node(node_idx).node_type().calculate(input);
+    /// The outputs of the network as expressions over its inputs, using the current bias
+    /// value. See `CppnExpr`.
+    pub fn expr(&self) -> CppnExpr {
+        CppnExpr::from_compiled(self.compiled())
+    }
 
-            // propagate output signal to outgoing links.
-            self.graph
-                .each_active_forward_link_of_node(node_idx, |out_node_idx, weight| {
-                    let out_node = out_node_idx.index();
-                    let weight: f64 = weight.into();
-                    self.incoming_signals[out_node] += weight * output;
-                    if !self.seen_bfs.contains(out_node) {
-                        self.seen_bfs.insert(out_node);
-                        self.nodes_bfs.push(out_node_idx);
-                    }
-                });
-        }
+    pub fn incoming_signals(&self) -> &[F] {
+        self.evaluator.incoming_signals()
+    }
+
+    /// Binds `signature` to the graph, so that inputs and outputs can be addressed by their
+    /// labels (see `calculate_named` and `read_output_named`).
+    pub fn set_signature(&mut self, signature: &CppnSignature<EXTID>) -> Result<(), CppnError> {
+        let bound = signature.bind(self.graph)?;
+        self.evaluator.set_signature(bound)
+    }
+
+    pub fn signature(&self) -> Option<&BoundSignature> {
+        self.evaluator.signature()
+    }
+
+    /// Calculate the labeled outputs for labeled input values, e.g.
+    /// `cppn.calculate_named(&[("x1", x1), ("y1", y1), ("x2", x2), ("y2", y2)])`.
+    ///
+    /// Every input of the signature must be given exactly once. The outputs are returned in the
+    /// order of the signature. Errors are reported as by `try_calculate`.
+    pub fn calculate_named(&mut self, inputs: &[(&str, F)]) -> Result<Vec<F>, CppnError> {
+        self.evaluator.calculate_named(inputs)
+    }
+
+    /// Reads the output labeled `label` of the last evaluation.
+    pub fn read_output_named(&self, label: &str) -> Option<F> {
+        self.evaluator.read_output_named(label)
+    }
+
+    /// Enables or disables tracing.
+    ///
+    /// While enabled, every evaluation except `calculate_batch` and `calculate_outputs` records
+    /// the summed input, the output and the contributions of all incoming links of each node,
+    /// which can be inspected with `trace` and `node_trace`. Disabling tracing discards the last
+    /// trace.
+    pub fn set_tracing(&mut self, enabled: bool) {
+        self.evaluator.set_tracing(enabled)
+    }
+
+    /// The trace of the last evaluation, or `None` if tracing is disabled.
+    pub fn trace(&self) -> Option<&CppnTrace<F>> {
+        self.evaluator.trace()
+    }
+
+    /// The trace of the node with the given `external_id` in the last evaluation.
+    pub fn node_trace(&self, external_id: EXTID) -> Option<&NodeTrace<F>> {
+        self.trace()
+            .and_then(|trace| trace.node_by_external_id(self.graph, external_id))
     }
 
     /// Calculate all outputs
+    pub fn calculate(&mut self, inputs: &[&[F]]) -> Vec<F> {
+        self.evaluator.calculate(inputs)
+    }
 
-    pub fn calculate(&mut self, inputs: &[&[f64]]) -> Vec<f64> {
-        self.process(inputs);
-        (0..self.outputs.len())
-            .into_iter()
-            .map(|i| self.read_output(i).unwrap())
-            .collect()
+    /// Calculate all outputs and write them into `outputs`, which must hold exactly
+    /// `output_count()` values.
+    pub fn calculate_into(&mut self, inputs: &[&[F]], outputs: &mut [F]) {
+        self.evaluator.calculate_into(inputs, outputs)
     }
 
-    /// Reads the `nth_output` of the network.
+    /// Like `calculate`, but reports invalid inputs or graphs and non-finite outputs as errors
+    /// instead of panicking.
+    pub fn try_calculate(&mut self, inputs: &[&[F]]) -> Result<Vec<F>, CppnError> {
+        self.evaluator.try_calculate(inputs)
+    }
 
-    pub fn read_output(&self, nth_output: usize) -> Option<f64> {
-        self.outputs.get(nth_output).map(|&node_idx| {
-            let input = self.incoming_signals[node_idx.index()];
-            let output = self.graph.node(node_idx).node_type().calculate(input);
-            output
-        })
+    /// Like `calculate_into`, but reports errors as `try_calculate` does.
+    pub fn try_calculate_into(
+        &mut self,
+        inputs: &[&[F]],
+        outputs: &mut [F],
+    ) -> Result<(), CppnError> {
+        self.evaluator.try_calculate_into(inputs, outputs)
     }
 
-    /// Returns the number of outputs
+    /// Calculate all outputs together with their derivatives with respect to all inputs.
+    ///
+    /// Returns the outputs and the Jacobian, which holds one row per output, i.e. the derivative
+    /// of output `o` with respect to input `i` is `jacobian[o * input_count() + i]`. For a CPPN
+    /// with inputs `(x1, y1, x2, y2)`, row `o` is the gradient of output `o` in substrate space.
+    ///
+    /// The derivatives are exact, as far as the `ActivationFunction::derivative` of every node
+    /// is. Where an activation function is not differentiable (e.g. at the kinks of `Absolute`),
+    /// one of its one-sided derivatives (or zero) is used.
+    pub fn calculate_jacobian(&mut self, inputs: &[&[F]]) -> (Vec<F>, Vec<F>) {
+        self.evaluator.calculate_jacobian(inputs)
+    }
 
-    pub fn output_count(&self) -> usize {
-        self.outputs.len()
+    /// Like `calculate_jacobian`, but writes the outputs into `outputs` (`output_count()`
+    /// values) and the Jacobian into `jacobian` (`output_count() * input_count()` values).
+    pub fn calculate_jacobian_into(
+        &mut self,
+        inputs: &[&[F]],
+        outputs: &mut [F],
+        jacobian: &mut [F],
+    ) {
+        self.evaluator
+            .calculate_jacobian_into(inputs, outputs, jacobian)
     }
 
-    /// Returns the number of inputs
+    /// Calculate only the outputs `nth_outputs` (indices into the list of outputs), in the given
+    /// order.
+    ///
+    /// Only the nodes these outputs depend on are evaluated. The set of nodes is determined on
+    /// the first call for a given `nth_outputs` and cached. Afterwards, `read_output` is only
+    /// valid for the requested outputs.
+    pub fn calculate_outputs(&mut self, inputs: &[&[F]], nth_outputs: &[usize]) -> Vec<F> {
+        self.evaluator.calculate_outputs(inputs, nth_outputs)
+    }
 
-    pub fn input_count(&self) -> usize {
-        self.inputs.len()
+    /// Like `calculate_outputs`, but writes the outputs into `outputs`, which must hold exactly
+    /// `nth_outputs.len()` values.
+    pub fn calculate_outputs_into(
+        &mut self,
+        inputs: &[&[F]],
+        nth_outputs: &[usize],
+        outputs: &mut [F],
+    ) {
+        self.evaluator
+            .calculate_outputs_into(inputs, nth_outputs, outputs)
     }
 
-    /// Process the network for the given `inputs`. Outputs can be read after this call using
-    /// `read_output`.
+    /// Calculate bounds on all outputs over the box of input vectors given by one interval per
+    /// input.
+    ///
+    /// Every output of `calculate` and `calculate_batch` for inputs within the box lies within
+    /// the corresponding returned interval. This can be used to skip regions of a substrate
+    /// where an output (e.g. the weight or LEO output) is known to stay below a threshold,
+    /// without sampling them.
+    pub fn calculate_interval(&self, inputs: &[Interval<F>]) -> Vec<Interval<F>> {
+        self.evaluator.calculate_interval(inputs)
+    }
 
-    pub fn process(&mut self, inputs: &[&[f64]]) {
-        assert!(self.incoming_signals.len() == self.graph.nodes().len());
-        self.reset_signals();
-
-        // assign all inputs
-        let mut i = 0;
-        for input_list in inputs.iter() {
-            for &input in input_list.iter() {
-                let input_idx = self.inputs[i];
-                self.set_signal(input_idx, input);
-                i += 1;
-            }
-        }
-        assert!(i == self.inputs.len());
+    /// Calculate all outputs for a batch of input vectors.
+    ///
+    /// `inputs` holds the values of each input for all samples in turn (structure of arrays), so
+    /// the value of input `i` for sample `s` is `inputs[i * n + s]`, where `n` is the number of
+    /// samples. The outputs are written to `outputs` in the same layout, and `outputs.len()`
+    /// determines the number of samples.
+    pub fn calculate_batch(&mut self, inputs: &[F], outputs: &mut [F]) {
+        self.evaluator.calculate_batch(inputs, outputs)
+    }
 
-        self.nodes_bfs.clear();
-        self.seen_bfs.clear();
+    /// Reads the `nth_output` of the network.
+    pub fn read_output(&self, nth_output: usize) -> Option<F> {
+        self.evaluator.read_output(nth_output)
+    }
 
-        // start from all nodes which have zero in_degree()
-        for &start_node_index in &self.start_nodes {
-            self.nodes_bfs.push(start_node_index);
-            self.seen_bfs.insert(start_node_index.index());
-        }
+    /// Returns the number of outputs
+    pub fn output_count(&self) -> usize {
+        self.evaluator.output_count()
+    }
 
-        // propagate the signals starting from the nodes with zero in degree.
-        self.propagate_signals();
+    /// Returns the number of inputs. Bias nodes are not counted, as they are not fed from the
+    /// inputs (see `bias_count`).
+    pub fn input_count(&self) -> usize {
+        self.evaluator.input_count()
+    }
+
+    /// Returns the number of bias nodes
+    pub fn bias_count(&self) -> usize {
+        self.evaluator.bias_count()
+    }
+
+    /// Sets the value fed into all bias nodes (`1.0` by default).
+    pub fn set_bias_value(&mut self, bias_value: F) {
+        self.evaluator.set_bias_value(bias_value)
+    }
+
+    /// Process the network for the given `inputs`. Outputs can be read after this call using
+    /// `read_output`.
+    ///
+    /// The nodes are evaluated in a single pass in the topological order determined by
+    /// `CompiledCppn`.
+    pub fn process(&mut self, inputs: &[&[F]]) {
+        self.evaluator.process(inputs);
+    }
+
+    /// Like `process`, but returns an error if the number of `inputs` does not match, the graph
+    /// is empty or has no outputs, or if any output is not finite.
+    pub fn try_process(&mut self, inputs: &[&[F]]) -> Result<(), CppnError> {
+        self.evaluator.try_process(inputs)
     }
 
     /// Group the nodes into layers.
@@ -310,7 +636,7 @@ where
             .nodes()
             .iter()
             .map(|node| {
-                if node.node_type().is_input_node() {
+                if node.node_type().is_input_node() || node.node_type().is_bias_node() {
                     0
                 } else if node.node_type().is_output_node() {
                     max_rank
@@ -346,9 +672,12 @@ where
 
 #[cfg(test)]
 mod tests {
-    use activation_function::GeometricActivationFunction as AF;
-    use super::{Cppn, CppnGraph, CppnNode};
+    use activation_function::{ActivationFunction, GeometricActivationFunction as AF};
+    use super::{Cppn, CppnError, CppnGraph, CppnNode};
+    use aggregation::Aggregation;
+    use compiled::EvalBuffers;
     use acyclic_network::ExternalId;
+    use interval::Interval;
     use rand;
 
     #[test]
@@ -398,6 +727,224 @@ mod tests {
         assert_eq!(vec![f(-4.0)], cppn.calculate(&[&[-4.0]]));
     }
 
+    #[test]
+    fn test_calculate_into() {
+        let mut g = CppnGraph::new();
+        let i1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let o1 = g.add_node(CppnNode::output(AF::Linear), ExternalId(2));
+        g.add_node(CppnNode::output(AF::Constant1), ExternalId(3));
+        g.add_link(i1, o1, 0.5, ExternalId(1));
+
+        let mut cppn = Cppn::new(&g);
+        let mut outputs = [0.0; 2];
+        cppn.calculate_into(&[&[3.0]], &mut outputs);
+        assert_eq!([1.5, 1.0], outputs);
+
+        let buffers = cppn.into_buffers();
+        let mut cppn = Cppn::with_buffers(&g, buffers);
+        cppn.calculate_into(&[&[-1.0]], &mut outputs);
+        assert_eq!([-0.5, 1.0], outputs);
+    }
+
+    #[test]
+    fn test_calculate_batch() {
+        let mut g = CppnGraph::new();
+        let i1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let i2 = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let o1 = g.add_node(CppnNode::output(AF::Linear), ExternalId(3));
+        let o2 = g.add_node(CppnNode::output(AF::Absolute), ExternalId(4));
+        g.add_link(i1, o1, 0.5, ExternalId(1));
+        g.add_link(i2, o1, 2.0, ExternalId(2));
+        g.add_link(i2, o2, -1.0, ExternalId(3));
+
+        let mut cppn = Cppn::new(&g);
+
+        // x = [1, 2, 3], y = [-1, 0, 1]
+        let inputs = [1.0, 2.0, 3.0, -1.0, 0.0, 1.0];
+        let mut outputs = [0.0; 6];
+        cppn.calculate_batch(&inputs, &mut outputs);
+        assert_eq!([-1.5, 1.0, 3.5, 1.0, 0.0, 1.0], outputs);
+    }
+
+    #[test]
+    fn test_f32_cppn() {
+        let mut g = CppnGraph::new();
+        let i1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let h1 = g.add_node(CppnNode::hidden(AF::Gaussian), ExternalId(2));
+        let o1 = g.add_node(CppnNode::output(AF::Linear), ExternalId(3));
+        g.add_link(i1, h1, 0.5, ExternalId(1));
+        g.add_link(h1, o1, 2.0, ExternalId(2));
+
+        let mut cppn64 = Cppn::new(&g);
+        let mut cppn32 = Cppn::with_buffers(&g, EvalBuffers::<f32>::new());
+
+        for &x in &[-1.0, 0.0, 0.25, 2.0] {
+            let expected = cppn64.calculate(&[&[x]])[0];
+            let actual: f32 = cppn32.calculate(&[&[x as f32]])[0];
+            assert!((expected - actual as f64).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_calculate_outputs() {
+        let mut g = CppnGraph::new();
+        let i1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let i2 = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let h1 = g.add_node(CppnNode::hidden(AF::Gaussian), ExternalId(3));
+        let o1 = g.add_node(CppnNode::output(AF::Linear), ExternalId(4));
+        let o2 = g.add_node(CppnNode::output(AF::BipolarSigmoid), ExternalId(5));
+        let o3 = g.add_node(CppnNode::output(AF::Absolute), ExternalId(6));
+        g.add_link(i1, h1, 0.5, ExternalId(1));
+        g.add_link(i2, h1, -1.5, ExternalId(2));
+        g.add_link(h1, o1, 2.0, ExternalId(3));
+        g.add_link(i1, o2, 1.0, ExternalId(4));
+        g.add_link(h1, o3, -1.0, ExternalId(5));
+        g.add_link(i2, o3, 0.25, ExternalId(6));
+
+        let mut cppn = Cppn::new(&g);
+        for &(x, y) in &[(0.0, 0.0), (0.5, -1.0), (2.0, 3.0)] {
+            let all = cppn.calculate(&[&[x, y]]);
+            assert_eq!(vec![all[1]], cppn.calculate_outputs(&[&[x, y]], &[1]));
+            assert_eq!(
+                vec![all[2], all[0]],
+                cppn.calculate_outputs(&[&[x, y]], &[2, 0])
+            );
+            let mut outputs = [0.0; 3];
+            cppn.calculate_outputs_into(&[&[x, y]], &[0, 1, 2], &mut outputs);
+            assert_eq!(&all[..], &outputs[..]);
+        }
+    }
+
+    #[test]
+    fn test_bias_node() {
+        let mut g = CppnGraph::new();
+        let b1 = g.add_node(CppnNode::bias(AF::Linear), ExternalId(1));
+        let i1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let o1 = g.add_node(CppnNode::output(AF::Linear), ExternalId(3));
+        g.add_link(b1, o1, 0.5, ExternalId(1));
+        g.add_link(i1, o1, 2.0, ExternalId(2));
+
+        let mut cppn = Cppn::new(&g);
+        assert_eq!(1, cppn.input_count());
+        assert_eq!(1, cppn.bias_count());
+        assert_eq!(vec![2.5], cppn.calculate(&[&[1.0]]));
+
+        let mut outputs = [0.0; 2];
+        cppn.calculate_batch(&[1.0, -1.0], &mut outputs);
+        assert_eq!([2.5, -1.5], outputs);
+
+        cppn.set_bias_value(-2.0);
+        assert_eq!(vec![1.0], cppn.calculate(&[&[1.0]]));
+        cppn.calculate_batch(&[1.0, -1.0], &mut outputs);
+        assert_eq!([1.0, -3.0], outputs);
+
+        // bias nodes are placed in the first layer, together with the inputs.
+        assert_eq!(vec![0, 0, 4], cppn.layout());
+    }
+
+    #[test]
+    fn test_calculate_jacobian() {
+        let mut g = CppnGraph::new();
+        let x = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let y = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let b = g.add_node(CppnNode::bias(AF::Linear), ExternalId(3));
+        let h1 = g.add_node(CppnNode::hidden(AF::Gaussian), ExternalId(4));
+        let h2 = g.add_node(CppnNode::hidden(AF::Sine), ExternalId(5));
+        let o1 = g.add_node(CppnNode::output(AF::BipolarSigmoid), ExternalId(6));
+        let o2 = g.add_node(CppnNode::output(AF::Linear), ExternalId(7));
+        g.add_link(x, h1, 0.7, ExternalId(1));
+        g.add_link(y, h1, -0.4, ExternalId(2));
+        g.add_link(b, h1, 0.1, ExternalId(3));
+        g.add_link(x, h2, 0.3, ExternalId(4));
+        g.add_link(h1, h2, 1.2, ExternalId(5));
+        g.add_link(h1, o1, 2.0, ExternalId(6));
+        g.add_link(h2, o1, -1.5, ExternalId(7));
+        g.add_link(y, o2, 3.0, ExternalId(8));
+
+        let mut cppn = Cppn::new(&g);
+        let h = 1e-6;
+        for &(px, py) in &[(0.0, 0.0), (0.3, -0.2), (-0.8, 0.5)] {
+            let (outputs, jacobian) = cppn.calculate_jacobian(&[&[px, py]]);
+            assert_eq!(cppn.calculate(&[&[px, py]]), outputs);
+            assert_eq!(4, jacobian.len());
+
+            let dx: Vec<_> = cppn
+                .calculate(&[&[px + h, py]])
+                .iter()
+                .zip(cppn.calculate(&[&[px - h, py]]))
+                .map(|(a, b)| (a - b) / (2.0 * h))
+                .collect();
+            let dy: Vec<_> = cppn
+                .calculate(&[&[px, py + h]])
+                .iter()
+                .zip(cppn.calculate(&[&[px, py - h]]))
+                .map(|(a, b)| (a - b) / (2.0 * h))
+                .collect();
+            for o in 0..2 {
+                assert!((jacobian[o * 2] - dx[o]).abs() < 1e-6);
+                assert!((jacobian[o * 2 + 1] - dy[o]).abs() < 1e-6);
+            }
+            assert_eq!(0.0, jacobian[2]);
+            assert_eq!(3.0, jacobian[3]);
+        }
+    }
+
+    #[test]
+    fn test_try_calculate() {
+        let mut g = CppnGraph::new();
+        let i1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let i2 = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let o1 = g.add_node(CppnNode::output(AF::Linear), ExternalId(3));
+        g.add_link(i1, o1, 1.0, ExternalId(1));
+        g.add_link(i2, o1, 1.0, ExternalId(2));
+
+        let mut cppn = Cppn::new(&g);
+        assert_eq!(Ok(vec![3.0]), cppn.try_calculate(&[&[1.0], &[2.0]]));
+        assert_eq!(
+            Err(CppnError::InputCountMismatch {
+                expected: 2,
+                actual: 3,
+            }),
+            cppn.try_calculate(&[&[1.0, 2.0, 3.0]])
+        );
+        assert_eq!(
+            Err(CppnError::InputCountMismatch {
+                expected: 2,
+                actual: 1,
+            }),
+            cppn.try_calculate(&[&[1.0]])
+        );
+        assert_eq!(
+            Err(CppnError::NonFiniteOutput { nth: 0 }),
+            cppn.try_calculate(&[&[1.0, f64::INFINITY]])
+        );
+
+        let mut outputs = [0.0];
+        assert_eq!(Ok(()), cppn.try_calculate_into(&[&[1.0, 0.5]], &mut outputs));
+        assert_eq!([1.5], outputs);
+    }
+
+    #[test]
+    fn test_try_calculate_invalid_graph() {
+        let g: CppnGraph<CppnNode<AF>, f64, ExternalId> = CppnGraph::new();
+        assert_eq!(Err(CppnError::EmptyGraph), Cppn::new(&g).try_process(&[]));
+
+        let mut g: CppnGraph<CppnNode<AF>, f64, ExternalId> = CppnGraph::new();
+        g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        g.add_node(CppnNode::hidden(AF::Linear), ExternalId(2));
+        assert_eq!(Err(CppnError::NoOutputs), Cppn::new(&g).try_process(&[&[1.0]]));
+    }
+
+    #[test]
+    #[should_panic(expected = "expected 1 input values, but 2 were given")]
+    fn test_process_wrong_input_count() {
+        let mut g = CppnGraph::new();
+        let i1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let o1 = g.add_node(CppnNode::output(AF::Linear), ExternalId(2));
+        g.add_link(i1, o1, 1.0, ExternalId(1));
+        Cppn::new(&g).process(&[&[1.0, 2.0]]);
+    }
+
     #[test]
     fn test_cppn_with_output_activation_function() {
         let mut g = CppnGraph::new();
@@ -414,6 +961,227 @@ mod tests {
         assert_eq!(vec![1.0], cppn.calculate(&[&[-4.0]]));
     }
 
+    #[test]
+    fn test_calculate_interval() {
+        let mut g = CppnGraph::new();
+        let x = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let y = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let b = g.add_node(CppnNode::bias(AF::Linear), ExternalId(3));
+        let h1 = g.add_node(CppnNode::hidden(AF::Gaussian), ExternalId(4));
+        let h2 = g.add_node(CppnNode::hidden(AF::Sine), ExternalId(5));
+        let h3 = g.add_node(CppnNode::hidden(AF::Absolute), ExternalId(6));
+        let o1 = g.add_node(CppnNode::output(AF::BipolarSigmoid), ExternalId(7));
+        let o2 = g.add_node(CppnNode::output(AF::Cosine), ExternalId(8));
+        g.add_link(x, h1, 0.7, ExternalId(1));
+        g.add_link(y, h1, -0.4, ExternalId(2));
+        g.add_link(b, h1, 0.1, ExternalId(3));
+        g.add_link(x, h2, 0.3, ExternalId(4));
+        g.add_link(h1, h2, 1.2, ExternalId(5));
+        g.add_link(y, h3, -2.0, ExternalId(6));
+        g.add_link(h1, o1, 2.0, ExternalId(7));
+        g.add_link(h2, o1, -1.5, ExternalId(8));
+        g.add_link(h3, o2, 0.25, ExternalId(9));
+        g.add_link(h2, o2, 0.5, ExternalId(10));
+
+        let mut cppn = Cppn::new(&g);
+        let boxes = [
+            ((-1.0, 1.0), (-1.0, 1.0)),
+            ((0.0, 0.1), (0.5, 0.6)),
+            ((-0.8, -0.7), (-0.3, 0.2)),
+            ((0.25, 0.25), (0.75, 0.75)),
+        ];
+        for &((x_lo, x_hi), (y_lo, y_hi)) in boxes.iter() {
+            let bounds = cppn.calculate_interval(&[
+                Interval::new(x_lo, x_hi),
+                Interval::new(y_lo, y_hi),
+            ]);
+            assert_eq!(2, bounds.len());
+            for i in 0..=20 {
+                for j in 0..=20 {
+                    let px = x_lo + (x_hi - x_lo) * (i as f64) / 20.0;
+                    let py = y_lo + (y_hi - y_lo) * (j as f64) / 20.0;
+                    let outputs = cppn.calculate(&[&[px, py]]);
+                    for (bound, &output) in bounds.iter().zip(outputs.iter()) {
+                        assert!(bound.contains(output), "{:?} {} ({}, {})", bound, output, px, py);
+                    }
+                }
+            }
+        }
+
+        // a point box yields the outputs up to the tolerance of `process_interval`.
+        let outputs = cppn.calculate(&[&[0.25, 0.75]]);
+        let bounds = cppn.calculate_interval(&[Interval::point(0.25), Interval::point(0.75)]);
+        for (bound, &output) in bounds.iter().zip(outputs.iter()) {
+            assert!(bound.contains(output) && bound.width() < 1e-11);
+        }
+    }
+
+    #[test]
+    fn test_aggregation() {
+        let mut g = CppnGraph::new();
+        let x = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let y = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let h1 = g.add_node(CppnNode::hidden(AF::Sine), ExternalId(3));
+        let h2 = g.add_node(CppnNode::hidden(AF::Gaussian), ExternalId(4));
+        let o1 = g.add_node(
+            CppnNode::output(AF::Linear).with_aggregation(Aggregation::Product),
+            ExternalId(5),
+        );
+        let o2 = g.add_node(
+            CppnNode::output(AF::BipolarSigmoid).with_aggregation(Aggregation::MaxAbs),
+            ExternalId(6),
+        );
+        g.add_link(x, h1, 1.0, ExternalId(1));
+        g.add_link(y, h2, 1.0, ExternalId(2));
+        g.add_link(h1, o1, 2.0, ExternalId(3));
+        g.add_link(h2, o1, 0.5, ExternalId(4));
+        g.add_link(x, o2, 1.0, ExternalId(5));
+        g.add_link(y, o2, -2.0, ExternalId(6));
+        g.add_link(h1, o2, 1.0, ExternalId(7));
+
+        let mut cppn = Cppn::new(&g);
+        let expr = cppn.expr();
+        let h = 1e-6;
+        for &(px, py) in &[(0.1, 0.4), (0.3, -0.2), (-0.8, 0.1)] {
+            let outputs = cppn.calculate(&[&[px, py]]);
+            // the sine gated by the gaussian.
+            let gated = (2.0 * AF::Sine.calculate(px)) * (0.5 * AF::Gaussian.calculate(py));
+            assert_eq!(gated, outputs[0]);
+            let max_abs =
+                Aggregation::MaxAbs.aggregate(vec![px, -2.0 * py, AF::Sine.calculate(px)]);
+            assert_eq!(AF::BipolarSigmoid.calculate(max_abs), outputs[1]);
+            assert_eq!(outputs, expr.eval(&[px, py]));
+
+            let (_, jacobian) = cppn.calculate_jacobian(&[&[px, py]]);
+            for i in 0..2 {
+                let mut plus = [px, py];
+                let mut minus = [px, py];
+                plus[i] += h;
+                minus[i] -= h;
+                let plus = cppn.calculate(&[&plus]);
+                let minus = cppn.calculate(&[&minus]);
+                for o in 0..2 {
+                    let expected = (plus[o] - minus[o]) / (2.0 * h);
+                    assert!((jacobian[o * 2 + i] - expected).abs() < 1e-6);
+                }
+            }
+
+            let bounds = cppn.calculate_interval(&[
+                Interval::new(px - 0.1, px + 0.1),
+                Interval::new(py - 0.1, py + 0.1),
+            ]);
+            for (bound, &output) in bounds.iter().zip(outputs.iter()) {
+                assert!(bound.contains(output));
+            }
+        }
+    }
+
+    #[test]
+    fn test_mutate_aggregation() {
+        let mut g = CppnGraph::new();
+        let x = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let y = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let o = g.add_node(CppnNode::output(AF::Linear), ExternalId(3));
+        g.add_link(x, o, 1.0, ExternalId(1));
+        g.add_link(y, o, 2.0, ExternalId(2));
+        assert_eq!(vec![7.0], Cppn::new(&g).calculate(&[&[3.0, 2.0]]));
+
+        let mut node = g.node(o).node_type().clone();
+        assert_eq!(Aggregation::Sum, node.aggregation());
+        node.set_aggregation(Aggregation::Product);
+        assert_eq!(Aggregation::Product, node.aggregation());
+        g.node_mut(o).set_node_type(node);
+        assert_eq!(vec![12.0], Cppn::new(&g).calculate(&[&[3.0, 2.0]]));
+
+        let mut node = g.node(o).node_type().clone();
+        node.set_aggregation(Aggregation::Min);
+        g.node_mut(o).set_node_type(node);
+        assert_eq!(vec![3.0], Cppn::new(&g).calculate(&[&[3.0, 2.0]]));
+    }
+
+    #[test]
+    fn test_bias_and_response() {
+        let mut g = CppnGraph::new();
+        let x = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let y = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let h1 = g.add_node(
+            CppnNode::hidden(AF::Gaussian).with_bias(0.25).with_response(2.0),
+            ExternalId(3),
+        );
+        let o1 = g.add_node(CppnNode::output(AF::BipolarSigmoid).with_bias(-0.5), ExternalId(4));
+        g.add_link(x, h1, 0.7, ExternalId(1));
+        g.add_link(y, h1, -0.4, ExternalId(2));
+        g.add_link(h1, o1, 1.5, ExternalId(3));
+        g.add_link(y, o1, 0.5, ExternalId(4));
+
+        let node = CppnNode::hidden(AF::Sine).with_bias(0.25).with_response(2.0);
+        assert_eq!(
+            "sin(2.0 * pi * ((0.25 + 2.0 * (x))))",
+            node.formula_gnuplot("x".to_string())
+        );
+        assert_eq!(
+            "sin(2.0 * pi * (x))",
+            CppnNode::hidden(AF::Sine).formula_gnuplot("x".to_string())
+        );
+
+        let mut cppn = Cppn::new(&g);
+        let expr = cppn.expr();
+        let h = 1e-6;
+        let samples = [(0.0, 0.0), (0.3, -0.2), (-0.8, 0.5)];
+        for &(px, py) in samples.iter() {
+            let hidden = AF::Gaussian.calculate(0.25 + 2.0 * (0.7 * px - 0.4 * py));
+            let expected = AF::BipolarSigmoid.calculate(-0.5 + 1.5 * hidden + 0.5 * py);
+            let outputs = cppn.calculate(&[&[px, py]]);
+            assert_eq!(vec![expected], outputs);
+            assert_eq!(outputs, expr.eval(&[px, py]));
+
+            let (_, jacobian) = cppn.calculate_jacobian(&[&[px, py]]);
+            let dx = (cppn.calculate(&[&[px + h, py]])[0] - cppn.calculate(&[&[px - h, py]])[0])
+                / (2.0 * h);
+            let dy = (cppn.calculate(&[&[px, py + h]])[0] - cppn.calculate(&[&[px, py - h]])[0])
+                / (2.0 * h);
+            assert!((jacobian[0] - dx).abs() < 1e-6);
+            assert!((jacobian[1] - dy).abs() < 1e-6);
+
+            let bounds = cppn.calculate_interval(&[
+                Interval::new(px - 0.1, px + 0.1),
+                Interval::new(py - 0.1, py + 0.1),
+            ]);
+            assert!(bounds[0].contains(expected));
+        }
+
+        let mut inputs = [0.0; 6];
+        for (s, &(px, py)) in samples.iter().enumerate() {
+            inputs[s] = px;
+            inputs[3 + s] = py;
+        }
+        let mut outputs = [0.0; 3];
+        cppn.calculate_batch(&inputs, &mut outputs);
+        for (s, &(px, py)) in samples.iter().enumerate() {
+            assert!((cppn.calculate(&[&[px, py]])[0] - outputs[s]).abs() < 1e-14);
+        }
+    }
+
+    #[test]
+    fn test_mutate_bias_and_response() {
+        let mut g = CppnGraph::new();
+        let x = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let o = g.add_node(CppnNode::output(AF::Linear), ExternalId(2));
+        g.add_link(x, o, 1.0, ExternalId(1));
+        assert_eq!(vec![3.0], Cppn::new(&g).calculate(&[&[3.0]]));
+
+        let mut node = g.node(o).node_type().clone();
+        *node.bias_mut() += 0.5;
+        *node.response_mut() *= 2.0;
+        assert!(!node.is_unscaled());
+        assert_eq!((0.5, 2.0), (node.bias, node.response));
+        g.node_mut(o).set_node_type(node);
+
+        let mut cppn = Cppn::new(&g);
+        assert_eq!(vec![6.5], cppn.calculate(&[&[3.0]]));
+        assert_eq!(vec![6.5], cppn.expr().eval(&[3.0]));
+    }
+
     #[test]
     fn test_find_random_unconnected_link_no_cycle() {
         let mut g: CppnGraph<CppnNode<AF>, _, _> = CppnGraph::new();
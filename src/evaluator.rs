@@ -0,0 +1,382 @@
+use compiled::{CompiledCppn, EvalBuffers, OutputCone};
+use cppn::{CppnError, CppnGraph, CppnNodeType};
+use float::Float;
+use interval::Interval;
+use signature::BoundSignature;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+use trace::CppnTrace;
+
+/// Evaluates a `CompiledCppn` which is shared via an `Arc`.
+///
+/// In contrast to `Cppn`, an evaluator does not borrow the `CppnGraph` it was created from, so
+/// it can be stored alongside its genome, moved into another thread or returned from a function.
+/// The compiled network is immutable and shared (`Send + Sync` as long as the node type is),
+/// while each evaluator owns its own `EvalBuffers`. Use `fork` to cheaply create another
+/// evaluator of the same network, e.g. one per thread.
+#[derive(Clone, Debug)]
+pub struct CppnEvaluator<N, F = f64>
+where
+    N: CppnNodeType,
+    F: Float,
+{
+    compiled: Arc<CompiledCppn<N, F>>,
+    buffers: EvalBuffers<F>,
+    trace: Option<CppnTrace<F>>,
+    signature: Option<BoundSignature>,
+    // The cones of all output subsets evaluated so far.
+    cones: HashMap<Vec<usize>, OutputCone>,
+}
+
+impl<N> CppnEvaluator<N>
+where
+    N: CppnNodeType,
+{
+    /// Compiles `graph` and creates an evaluator for it, using `f64` signals.
+    pub fn new<L, EXTID>(graph: &CppnGraph<N, L, EXTID>) -> Self
+    where
+        L: Copy + Debug + Send + Sized + Into<f64>,
+        EXTID: Copy + Debug + Send + Sized + Ord,
+    {
+        Self::from_compiled(Arc::new(CompiledCppn::new(graph)))
+    }
+}
+
+impl<N, F> CppnEvaluator<N, F>
+where
+    N: CppnNodeType,
+    F: Float,
+{
+    pub fn from_compiled(compiled: Arc<CompiledCppn<N, F>>) -> Self {
+        Self::with_buffers(compiled, EvalBuffers::new())
+    }
+
+    /// Creates an evaluator using the given (possibly previously used) buffers.
+    pub fn with_buffers(compiled: Arc<CompiledCppn<N, F>>, mut buffers: EvalBuffers<F>) -> Self {
+        buffers.prepare(compiled.node_count());
+        CppnEvaluator {
+            compiled,
+            buffers,
+            trace: None,
+            signature: None,
+            cones: HashMap::new(),
+        }
+    }
+
+    /// Returns a new evaluator sharing the compiled network, with fresh buffers.
+    pub fn fork(&self) -> Self {
+        Self::from_compiled(self.compiled.clone())
+    }
+
+    /// Consumes the evaluator and returns its buffers for reuse.
+    pub fn into_buffers(self) -> EvalBuffers<F> {
+        self.buffers
+    }
+
+    pub fn compiled(&self) -> &Arc<CompiledCppn<N, F>> {
+        &self.compiled
+    }
+
+    pub fn incoming_signals(&self) -> &[F] {
+        self.buffers.incoming()
+    }
+
+    /// Sets the signature used to address inputs and outputs by name. It must have been bound
+    /// to the graph this evaluator was compiled from. See `Cppn::set_signature`.
+    ///
+    /// Fails with `CppnError::SignatureMismatch` if the signature was bound to a graph with a
+    /// different number of inputs or outputs.
+    pub fn set_signature(&mut self, signature: BoundSignature) -> Result<(), CppnError> {
+        if signature.input_count() != self.compiled.input_count()
+            || signature.output_count() != self.compiled.output_count()
+        {
+            return Err(CppnError::SignatureMismatch);
+        }
+        self.signature = Some(signature);
+        Ok(())
+    }
+
+    pub fn signature(&self) -> Option<&BoundSignature> {
+        self.signature.as_ref()
+    }
+
+    /// Calculate the labeled outputs for labeled input values. See `Cppn::calculate_named`.
+    pub fn calculate_named(&mut self, inputs: &[(&str, F)]) -> Result<Vec<F>, CppnError> {
+        let values = match self.signature {
+            Some(ref signature) => signature.input_values(inputs)?,
+            None => return Err(CppnError::NoSignature),
+        };
+        self.try_process(&[&values])?;
+        let signature = self.signature.as_ref().unwrap();
+        Ok(signature
+            .outputs()
+            .iter()
+            .map(|&(_, slot)| self.buffers.outgoing()[self.compiled.outputs()[slot]])
+            .collect())
+    }
+
+    /// Reads the output labeled `label` of the last evaluation.
+    pub fn read_output_named(&self, label: &str) -> Option<F> {
+        self.signature
+            .as_ref()
+            .and_then(|signature| signature.output_slot(label))
+            .and_then(|slot| self.read_output(slot))
+    }
+
+    /// Enables or disables tracing. See `Cppn::set_tracing`.
+    pub fn set_tracing(&mut self, enabled: bool) {
+        if !enabled {
+            self.trace = None;
+        } else if self.trace.is_none() {
+            self.trace = Some(CppnTrace::new());
+        }
+    }
+
+    /// The trace of the last evaluation, if tracing is enabled. See `Cppn::trace`.
+    pub fn trace(&self) -> Option<&CppnTrace<F>> {
+        self.trace.as_ref()
+    }
+
+    fn record_trace(&mut self) {
+        if let Some(ref mut trace) = self.trace {
+            trace.record(
+                &self.compiled,
+                self.buffers.incoming(),
+                self.buffers.outgoing(),
+            );
+        }
+    }
+
+    /// Calculate all outputs
+    pub fn calculate(&mut self, inputs: &[&[F]]) -> Vec<F> {
+        self.process(inputs);
+        let mut outputs = vec![F::zero(); self.output_count()];
+        self.compiled.read_outputs(self.buffers.outgoing(), &mut outputs);
+        outputs
+    }
+
+    /// Calculate all outputs and write them into `outputs`, which must hold exactly
+    /// `output_count()` values.
+    pub fn calculate_into(&mut self, inputs: &[&[F]], outputs: &mut [F]) {
+        self.process(inputs);
+        self.compiled.read_outputs(self.buffers.outgoing(), outputs);
+    }
+
+    /// Like `calculate`, but reports errors instead of panicking. See `Cppn::try_calculate`.
+    pub fn try_calculate(&mut self, inputs: &[&[F]]) -> Result<Vec<F>, CppnError> {
+        let mut outputs = vec![F::zero(); self.output_count()];
+        self.try_calculate_into(inputs, &mut outputs)?;
+        Ok(outputs)
+    }
+
+    /// Like `calculate_into`, but reports errors instead of panicking.
+    pub fn try_calculate_into(
+        &mut self,
+        inputs: &[&[F]],
+        outputs: &mut [F],
+    ) -> Result<(), CppnError> {
+        self.try_process(inputs)?;
+        self.compiled.read_outputs(self.buffers.outgoing(), outputs);
+        Ok(())
+    }
+
+    /// Calculate all outputs and their derivatives with respect to all inputs. See
+    /// `Cppn::calculate_jacobian`.
+    pub fn calculate_jacobian(&mut self, inputs: &[&[F]]) -> (Vec<F>, Vec<F>) {
+        let mut outputs = vec![F::zero(); self.output_count()];
+        let mut jacobian = vec![F::zero(); self.output_count() * self.input_count()];
+        self.calculate_jacobian_into(inputs, &mut outputs, &mut jacobian);
+        (outputs, jacobian)
+    }
+
+    /// Like `calculate_jacobian`, but writes into `outputs` and `jacobian`.
+    pub fn calculate_jacobian_into(
+        &mut self,
+        inputs: &[&[F]],
+        outputs: &mut [F],
+        jacobian: &mut [F],
+    ) {
+        self.compiled.process_jacobian_with(inputs, &mut self.buffers);
+        self.record_trace();
+        self.compiled.read_outputs(self.buffers.outgoing(), outputs);
+        self.compiled.read_jacobian(self.buffers.tangents(), jacobian);
+    }
+
+    /// Calculate only the outputs `nth_outputs`. See `Cppn::calculate_outputs`.
+    pub fn calculate_outputs(&mut self, inputs: &[&[F]], nth_outputs: &[usize]) -> Vec<F> {
+        let mut outputs = vec![F::zero(); nth_outputs.len()];
+        self.calculate_outputs_into(inputs, nth_outputs, &mut outputs);
+        outputs
+    }
+
+    /// Calculate only the outputs `nth_outputs` and write them into `outputs`.
+    pub fn calculate_outputs_into(
+        &mut self,
+        inputs: &[&[F]],
+        nth_outputs: &[usize],
+        outputs: &mut [F],
+    ) {
+        if !self.cones.contains_key(nth_outputs) {
+            let cone = self.compiled.output_cone(nth_outputs);
+            self.cones.insert(nth_outputs.to_vec(), cone);
+        }
+        let cone = &self.cones[nth_outputs];
+        self.compiled
+            .process_cone_with(cone, inputs, &mut self.buffers);
+        self.compiled
+            .read_cone_outputs(cone, self.buffers.outgoing(), outputs);
+    }
+
+    /// Calculate bounds on all outputs over a box of inputs. See `Cppn::calculate_interval`.
+    pub fn calculate_interval(&self, inputs: &[Interval<F>]) -> Vec<Interval<F>> {
+        self.compiled.process_interval(inputs)
+    }
+
+    /// Calculate all outputs for a batch of input vectors. See `Cppn::calculate_batch`.
+    // `usize::is_multiple_of` would require Rust 1.87.
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn calculate_batch(&mut self, inputs: &[F], outputs: &mut [F]) {
+        let output_count = self.output_count();
+        if output_count == 0 {
+            return;
+        }
+        assert!(outputs.len() % output_count == 0);
+        let sample_count = outputs.len() / output_count;
+        self.compiled
+            .process_batch_with(sample_count, inputs, outputs, &mut self.buffers);
+    }
+
+    /// Reads the `nth_output` of the network.
+    pub fn read_output(&self, nth_output: usize) -> Option<F> {
+        self.compiled
+            .outputs()
+            .get(nth_output)
+            .map(|&node| self.buffers.outgoing()[node])
+    }
+
+    /// Returns the number of outputs
+    pub fn output_count(&self) -> usize {
+        self.compiled.output_count()
+    }
+
+    /// Returns the number of inputs
+    pub fn input_count(&self) -> usize {
+        self.compiled.input_count()
+    }
+
+    /// Returns the number of bias nodes
+    pub fn bias_count(&self) -> usize {
+        self.compiled.bias_count()
+    }
+
+    /// Sets the value fed into all bias nodes. See `CompiledCppn::set_bias_value`.
+    ///
+    /// If the compiled network is shared with other evaluators, this evaluator gets its own
+    /// copy first, so the others are not affected.
+    pub fn set_bias_value(&mut self, bias_value: F) {
+        Arc::make_mut(&mut self.compiled).set_bias_value(bias_value);
+    }
+
+    /// Process the network for the given `inputs`. Outputs can be read after this call using
+    /// `read_output`.
+    pub fn process(&mut self, inputs: &[&[F]]) {
+        self.compiled.process_with(inputs, &mut self.buffers);
+        self.record_trace();
+    }
+
+    /// Like `process`, but reports errors instead of panicking. See `Cppn::try_process`.
+    pub fn try_process(&mut self, inputs: &[&[F]]) -> Result<(), CppnError> {
+        self.compiled.check(inputs)?;
+        let result = self.compiled.try_process_with(inputs, &mut self.buffers);
+        // Also record evaluations with non-finite outputs, which are the ones worth debugging.
+        self.record_trace();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CppnEvaluator;
+    use activation_function::GeometricActivationFunction as AF;
+    use acyclic_network::ExternalId;
+    use cppn::{CppnError, CppnGraph, CppnNode};
+    use signature::CppnSignature;
+    use std::thread;
+
+    type Genome = CppnGraph<CppnNode<AF>, f64, ExternalId>;
+
+    struct Individual {
+        genome: Genome,
+        evaluator: CppnEvaluator<CppnNode<AF>>,
+    }
+
+    fn individual(weight: f64) -> Individual {
+        let mut g = CppnGraph::new();
+        let i1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let o1 = g.add_node(CppnNode::output(AF::Linear), ExternalId(2));
+        g.add_link(i1, o1, weight, ExternalId(1));
+        let evaluator = CppnEvaluator::new(&g);
+        Individual {
+            genome: g,
+            evaluator,
+        }
+    }
+
+    #[test]
+    fn test_evaluator_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<CppnEvaluator<CppnNode<AF>>>();
+    }
+
+    #[test]
+    fn test_evaluator_outlives_borrow() {
+        let mut population: Vec<Individual> = vec![individual(2.0), individual(-1.0)];
+        assert_eq!(vec![1.0], population[0].evaluator.calculate(&[&[0.5]]));
+        assert_eq!(vec![-0.5], population[1].evaluator.calculate(&[&[0.5]]));
+        assert_eq!(2, population[0].genome.node_count());
+    }
+
+    #[test]
+    fn test_fork_into_threads() {
+        let evaluator = individual(3.0).evaluator;
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let mut evaluator = evaluator.fork();
+                thread::spawn(move || evaluator.calculate(&[&[i as f64]])[0])
+            })
+            .collect();
+        let results: Vec<f64> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(vec![0.0, 3.0, 6.0, 9.0], results);
+    }
+
+    #[test]
+    fn test_set_signature_of_other_graph() {
+        let mut signature = CppnSignature::new();
+        signature.add_input("x", ExternalId(1));
+        signature.add_output("out", ExternalId(2));
+        let mut individual = individual(2.0);
+        let bound = signature.bind(&individual.genome).unwrap();
+        assert_eq!(Ok(()), individual.evaluator.set_signature(bound));
+        assert_eq!(
+            Ok(vec![1.0]),
+            individual.evaluator.calculate_named(&[("x", 0.5)])
+        );
+
+        // a graph with a second output.
+        let mut g = CppnGraph::new();
+        let i1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let o1 = g.add_node(CppnNode::output(AF::Linear), ExternalId(2));
+        let o2 = g.add_node(CppnNode::output(AF::Linear), ExternalId(3));
+        g.add_link(i1, o1, 1.0, ExternalId(1));
+        g.add_link(i1, o2, 1.0, ExternalId(2));
+        signature.add_output("other", ExternalId(3));
+        let bound = signature.bind(&g).unwrap();
+        let mut evaluator: CppnEvaluator<CppnNode<AF>> = individual.evaluator.fork();
+        assert_eq!(
+            Err(CppnError::SignatureMismatch),
+            evaluator.set_signature(bound)
+        );
+        assert!(evaluator.signature().is_none());
+    }
+}
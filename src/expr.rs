@@ -0,0 +1,695 @@
+use aggregation::Aggregation;
+use compiled::CompiledCppn;
+use cppn::{CppnGraph, CppnNodeType};
+use float::Float;
+use std::fmt::{self, Debug};
+use std::ops;
+
+/// A unary elementary function used in an `Expr`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Function {
+    Abs,
+    Exp,
+    /// The natural logarithm.
+    Log,
+    Sin,
+    Cos,
+    Tanh,
+    Floor,
+    /// The Heaviside step function, which is 1.0 for `x >= 0.0` and 0.0 otherwise.
+    Step,
+}
+
+impl Function {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Function::Abs => "abs",
+            Function::Exp => "exp",
+            Function::Log => "log",
+            Function::Sin => "sin",
+            Function::Cos => "cos",
+            Function::Tanh => "tanh",
+            Function::Floor => "floor",
+            Function::Step => "step",
+        }
+    }
+
+    pub fn apply<F: Float>(&self, x: F) -> F {
+        match *self {
+            Function::Abs => x.abs(),
+            Function::Exp => x.exp(),
+            Function::Log => x.ln(),
+            Function::Sin => x.sin(),
+            Function::Cos => x.cos(),
+            Function::Tanh => x.tanh(),
+            Function::Floor => x.floor(),
+            Function::Step => {
+                if x >= F::zero() {
+                    F::one()
+                } else {
+                    F::zero()
+                }
+            }
+        }
+    }
+}
+
+/// A symbolic expression over the inputs of a CPPN.
+///
+/// Expressions are built by `ActivationFunction::expr` for single activation functions and by
+/// `CppnExpr` for whole networks. The usual arithmetic operators are implemented for `Expr`,
+/// so `Expr::Const(2.0) * x.sin()` builds `2.0 * sin(x)`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Const(f64),
+    /// The value of the `n`th input (in the order of `Cppn::calculate`).
+    Input(usize),
+    /// A reference to the `n`th shared subexpression of a `CppnExpr`.
+    Var(usize),
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Min(Box<Expr>, Box<Expr>),
+    Max(Box<Expr>, Box<Expr>),
+    Powi(Box<Expr>, i32),
+    Apply(Function, Box<Expr>),
+    /// An aggregation of several values which has no closed form in terms of the other
+    /// variants (see `Aggregation::expr`).
+    Aggregate(Aggregation, Vec<Expr>),
+    /// A call of a function which is only known by name. Such an expression cannot be
+    /// evaluated.
+    Call(String, Box<Expr>),
+}
+
+impl Expr {
+    pub fn abs(self) -> Expr {
+        Expr::Apply(Function::Abs, Box::new(self))
+    }
+
+    pub fn exp(self) -> Expr {
+        Expr::Apply(Function::Exp, Box::new(self))
+    }
+
+    pub fn sin(self) -> Expr {
+        Expr::Apply(Function::Sin, Box::new(self))
+    }
+
+    pub fn cos(self) -> Expr {
+        Expr::Apply(Function::Cos, Box::new(self))
+    }
+
+    pub fn log(self) -> Expr {
+        Expr::Apply(Function::Log, Box::new(self))
+    }
+
+    pub fn tanh(self) -> Expr {
+        Expr::Apply(Function::Tanh, Box::new(self))
+    }
+
+    pub fn floor(self) -> Expr {
+        Expr::Apply(Function::Floor, Box::new(self))
+    }
+
+    pub fn step(self) -> Expr {
+        Expr::Apply(Function::Step, Box::new(self))
+    }
+
+    pub fn min(self, other: Expr) -> Expr {
+        Expr::Min(Box::new(self), Box::new(other))
+    }
+
+    pub fn max(self, other: Expr) -> Expr {
+        Expr::Max(Box::new(self), Box::new(other))
+    }
+
+    pub fn powi(self, n: i32) -> Expr {
+        Expr::Powi(Box::new(self), n)
+    }
+
+    /// Constants, inputs and variables.
+    pub fn is_leaf(&self) -> bool {
+        matches!(*self, Expr::Const(_) | Expr::Input(_) | Expr::Var(_))
+    }
+
+    /// The direct subexpressions.
+    pub fn children(&self) -> Vec<&Expr> {
+        match *self {
+            Expr::Const(_) | Expr::Input(_) | Expr::Var(_) => Vec::new(),
+            Expr::Neg(ref a)
+            | Expr::Powi(ref a, _)
+            | Expr::Apply(_, ref a)
+            | Expr::Call(_, ref a) => vec![a],
+            Expr::Add(ref a, ref b)
+            | Expr::Sub(ref a, ref b)
+            | Expr::Mul(ref a, ref b)
+            | Expr::Div(ref a, ref b)
+            | Expr::Min(ref a, ref b)
+            | Expr::Max(ref a, ref b) => vec![a, b],
+            Expr::Aggregate(_, ref values) => values.iter().collect(),
+        }
+    }
+
+    /// Returns a copy of the expression with each direct subexpression replaced by `f(child)`.
+    pub fn map_children<M: FnMut(&Expr) -> Expr>(&self, mut f: M) -> Expr {
+        let mut g = |a: &Expr| Box::new(f(a));
+        match *self {
+            Expr::Const(_) | Expr::Input(_) | Expr::Var(_) => self.clone(),
+            Expr::Neg(ref a) => Expr::Neg(g(a)),
+            Expr::Add(ref a, ref b) => Expr::Add(g(a), g(b)),
+            Expr::Sub(ref a, ref b) => Expr::Sub(g(a), g(b)),
+            Expr::Mul(ref a, ref b) => Expr::Mul(g(a), g(b)),
+            Expr::Div(ref a, ref b) => Expr::Div(g(a), g(b)),
+            Expr::Min(ref a, ref b) => Expr::Min(g(a), g(b)),
+            Expr::Max(ref a, ref b) => Expr::Max(g(a), g(b)),
+            Expr::Powi(ref a, n) => Expr::Powi(g(a), n),
+            Expr::Apply(function, ref a) => Expr::Apply(function, g(a)),
+            Expr::Aggregate(aggregation, ref values) => {
+                Expr::Aggregate(aggregation, values.iter().map(|a| *g(a)).collect())
+            }
+            Expr::Call(ref name, ref a) => Expr::Call(name.clone(), g(a)),
+        }
+    }
+
+    /// Calls `f` for each occurrence of a variable.
+    pub fn each_var<V: FnMut(usize)>(&self, f: &mut V) {
+        match *self {
+            Expr::Var(n) => f(n),
+            _ => {
+                for child in self.children() {
+                    child.each_var(f);
+                }
+            }
+        }
+    }
+
+    /// Replaces each variable `Var(n)` by `vars[n]`.
+    pub fn substitute_vars(&self, vars: &[Expr]) -> Expr {
+        match *self {
+            Expr::Var(n) => vars[n].clone(),
+            _ => self.map_children(|child| child.substitute_vars(vars)),
+        }
+    }
+
+    /// Evaluates the expression for the given input values and values of the variables.
+    ///
+    /// Panics if the expression contains a `Call`.
+    pub fn eval<F: Float>(&self, inputs: &[F], vars: &[F]) -> F {
+        let e = |a: &Expr| a.eval(inputs, vars);
+        match *self {
+            Expr::Const(c) => F::from_f64(c),
+            Expr::Input(n) => inputs[n],
+            Expr::Var(n) => vars[n],
+            Expr::Neg(ref a) => -e(a),
+            Expr::Add(ref a, ref b) => e(a) + e(b),
+            Expr::Sub(ref a, ref b) => e(a) - e(b),
+            Expr::Mul(ref a, ref b) => e(a) * e(b),
+            Expr::Div(ref a, ref b) => e(a) / e(b),
+            Expr::Min(ref a, ref b) => e(a).min(e(b)),
+            Expr::Max(ref a, ref b) => e(a).max(e(b)),
+            Expr::Powi(ref a, n) => e(a).powi(n),
+            Expr::Apply(function, ref a) => function.apply(e(a)),
+            Expr::Aggregate(aggregation, ref values) => aggregation.aggregate(values.iter().map(e)),
+            Expr::Call(ref name, _) => panic!("cannot evaluate unknown function {}", name),
+        }
+    }
+
+    /// Folds constant subexpressions and removes neutral operations like `x * 1.0`, `x + 0.0`
+    /// and `--x`.
+    ///
+    /// The result evaluates to the same values, except that the sign of zero results may
+    /// differ.
+    pub fn simplify(&self) -> Expr {
+        let expr = self.map_children(|child| child.simplify());
+
+        let foldable = match expr {
+            Expr::Call(..) => false,
+            _ => !expr.is_leaf() && expr.children().iter().all(|child| child.is_const()),
+        };
+        if foldable {
+            return Expr::Const(expr.eval(&[], &[]));
+        }
+
+        let is = |e: &Expr, c: f64| *e == Expr::Const(c);
+        match expr {
+            Expr::Add(a, b) => {
+                if is(&a, 0.0) {
+                    *b
+                } else if is(&b, 0.0) {
+                    *a
+                } else if let Expr::Neg(b) = *b {
+                    Expr::Sub(a, b)
+                } else if let Some(b) = negated_product(&b) {
+                    Expr::Sub(a, Box::new(b))
+                } else {
+                    Expr::Add(a, b)
+                }
+            }
+            Expr::Sub(a, b) => {
+                if is(&b, 0.0) {
+                    *a
+                } else if is(&a, 0.0) {
+                    Expr::Neg(b).simplify()
+                } else if let Expr::Neg(b) = *b {
+                    Expr::Add(a, b)
+                } else if let Some(b) = negated_product(&b) {
+                    Expr::Add(a, Box::new(b))
+                } else {
+                    Expr::Sub(a, b)
+                }
+            }
+            Expr::Mul(a, b) => {
+                if is(&a, 1.0) {
+                    *b
+                } else if is(&b, 1.0) {
+                    *a
+                } else if is(&a, -1.0) {
+                    Expr::Neg(b).simplify()
+                } else if is(&b, -1.0) {
+                    Expr::Neg(a).simplify()
+                } else {
+                    Expr::Mul(a, b)
+                }
+            }
+            Expr::Div(a, b) => {
+                if is(&b, 1.0) {
+                    *a
+                } else {
+                    Expr::Div(a, b)
+                }
+            }
+            Expr::Neg(a) => match *a {
+                Expr::Neg(a) => *a,
+                a => Expr::Neg(Box::new(a)),
+            },
+            Expr::Powi(a, 1) => *a,
+            expr => expr,
+        }
+    }
+
+    fn is_const(&self) -> bool {
+        matches!(*self, Expr::Const(_))
+    }
+
+    /// Operands with a lower precedence have to be put in parentheses.
+    fn precedence(&self) -> u8 {
+        match *self {
+            Expr::Add(..) | Expr::Sub(..) => 1,
+            Expr::Mul(..) | Expr::Div(..) => 2,
+            Expr::Neg(_) => 3,
+            Expr::Const(c) if c.is_sign_negative() => 3,
+            Expr::Powi(..) => 4,
+            _ => 5,
+        }
+    }
+}
+
+/// Returns `(-c) * b` for a product `c * b` with a negative constant `c`, which turns e.g.
+/// `a + -2.0 * b` into the more readable (and equal) `a - 2.0 * b`.
+fn negated_product(expr: &Expr) -> Option<Expr> {
+    match *expr {
+        Expr::Mul(ref a, ref b) => match **a {
+            Expr::Const(c) if c < 0.0 => Some(Expr::Const(-c) * (**b).clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn fmt_operand(expr: &Expr, precedence: u8, f: &mut fmt::Formatter) -> fmt::Result {
+    if expr.precedence() < precedence {
+        write!(f, "({})", expr)
+    } else {
+        write!(f, "{}", expr)
+    }
+}
+
+fn fmt_binary(a: &Expr, op: &str, b: &Expr, precedence: u8, f: &mut fmt::Formatter) -> fmt::Result {
+    fmt_operand(a, precedence, f)?;
+    write!(f, " {} ", op)?;
+    // Parenthesize right operands of equal precedence, as the operators are evaluated from
+    // left to right.
+    fmt_operand(b, precedence + 1, f)
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Expr::Const(c) => write!(f, "{:?}", c),
+            Expr::Input(n) => write!(f, "x{}", n),
+            Expr::Var(n) => write!(f, "t{}", n),
+            Expr::Neg(ref a) => {
+                write!(f, "-")?;
+                fmt_operand(a, 4, f)
+            }
+            Expr::Add(ref a, ref b) => fmt_binary(a, "+", b, 1, f),
+            Expr::Sub(ref a, ref b) => fmt_binary(a, "-", b, 1, f),
+            Expr::Mul(ref a, ref b) => fmt_binary(a, "*", b, 2, f),
+            Expr::Div(ref a, ref b) => fmt_binary(a, "/", b, 2, f),
+            Expr::Min(ref a, ref b) => write!(f, "min({}, {})", a, b),
+            Expr::Max(ref a, ref b) => write!(f, "max({}, {})", a, b),
+            Expr::Powi(ref a, n) => {
+                fmt_operand(a, 5, f)?;
+                write!(f, "^{}", n)
+            }
+            Expr::Apply(function, ref a) => write!(f, "{}({})", function.name(), a),
+            Expr::Aggregate(aggregation, ref values) => {
+                write!(f, "{}(", aggregation.name())?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Call(ref name, ref a) => write!(f, "{}({})", name, a),
+        }
+    }
+}
+
+impl ops::Neg for Expr {
+    type Output = Expr;
+
+    fn neg(self) -> Expr {
+        Expr::Neg(Box::new(self))
+    }
+}
+
+macro_rules! impl_binary_op {
+    ($op:ident, $method:ident, $variant:ident) => {
+        impl ops::$op for Expr {
+            type Output = Expr;
+
+            fn $method(self, other: Expr) -> Expr {
+                Expr::$variant(Box::new(self), Box::new(other))
+            }
+        }
+    };
+}
+
+impl_binary_op!(Add, add, Add);
+impl_binary_op!(Sub, sub, Sub);
+impl_binary_op!(Mul, mul, Mul);
+impl_binary_op!(Div, div, Div);
+
+/// The outputs of a whole network as closed-form expressions over its inputs.
+///
+/// The output of a node which is used more than once is computed only once, as a shared
+/// subexpression (variable) `Var(n)` which can refer to the variables before it. Nodes which
+/// no output depends on are left out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CppnExpr {
+    input_count: usize,
+    vars: Vec<Expr>,
+    outputs: Vec<Expr>,
+}
+
+impl CppnExpr {
+    pub fn new<N, L, EXTID>(graph: &CppnGraph<N, L, EXTID>) -> Self
+    where
+        N: CppnNodeType,
+        L: Copy + Debug + Send + Sized + Into<f64>,
+        EXTID: Copy + Debug + Send + Sized + Ord,
+    {
+        Self::from_compiled(&CompiledCppn::new(graph))
+    }
+
+    /// Builds the expressions of a compiled network. Bias nodes are replaced by the bias value
+    /// of `compiled`.
+    pub fn from_compiled<N, F>(compiled: &CompiledCppn<N, F>) -> Self
+    where
+        N: CppnNodeType,
+        F: Float,
+    {
+        let all_outputs: Vec<usize> = (0..compiled.output_count()).collect();
+        let cone = compiled.output_cone(&all_outputs);
+
+        let mut node_exprs = vec![None; compiled.node_count()];
+        let mut vars = Vec::new();
+        for &nth in cone.steps() {
+            let node = compiled.order()[nth];
+            let input = if let Some(slot) = compiled.inputs().iter().position(|&i| i == node) {
+                Some(Expr::Input(slot))
+            } else if compiled.biases().contains(&node) {
+                Some(Expr::Const(compiled.bias_value().to_f64()))
+            } else {
+                None
+            };
+            let mut terms: Vec<Expr> = compiled
+                .incoming_links(nth)
+                .iter()
+                .map(|link| {
+                    let source = node_exprs[link.source].clone().unwrap();
+                    Expr::Const(link.weight.to_f64()) * source
+                })
+                .collect();
+            let sum = match (input, compiled.aggregation(node)) {
+                (Some(input), Aggregation::Sum) => {
+                    terms.insert(0, input);
+                    Aggregation::Sum.expr(terms)
+                }
+                (input, _) if terms.is_empty() => input.unwrap_or(Expr::Const(0.0)),
+                (Some(input), aggregation) => input + aggregation.expr(terms),
+                (None, aggregation) => aggregation.expr(terms),
+            };
+            let expr = compiled.node_type(node).expr(sum);
+            node_exprs[node] = Some(if expr.is_leaf() {
+                expr
+            } else {
+                share(&mut vars, expr)
+            });
+        }
+
+        let outputs = compiled
+            .outputs()
+            .iter()
+            .map(|&node| node_exprs[node].clone().unwrap())
+            .collect();
+
+        CppnExpr {
+            input_count: compiled.input_count(),
+            vars,
+            outputs,
+        }
+        .rebuild(|expr| expr)
+    }
+
+    pub fn input_count(&self) -> usize {
+        self.input_count
+    }
+
+    /// The shared subexpressions. Each one only refers to the variables before it.
+    pub fn vars(&self) -> &[Expr] {
+        &self.vars
+    }
+
+    pub fn outputs(&self) -> &[Expr] {
+        &self.outputs
+    }
+
+    /// The `nth` output with all variables substituted, i.e. without any sharing.
+    pub fn expand(&self, nth: usize) -> Expr {
+        let mut vars: Vec<Expr> = Vec::with_capacity(self.vars.len());
+        for var in self.vars.iter() {
+            let expr = var.substitute_vars(&vars);
+            vars.push(expr);
+        }
+        self.outputs[nth].substitute_vars(&vars)
+    }
+
+    /// Evaluates all outputs for the given input values.
+    pub fn eval<F: Float>(&self, inputs: &[F]) -> Vec<F> {
+        assert!(inputs.len() == self.input_count);
+        let mut vars = Vec::with_capacity(self.vars.len());
+        for var in self.vars.iter() {
+            let value = var.eval(inputs, &vars);
+            vars.push(value);
+        }
+        self.outputs
+            .iter()
+            .map(|output| output.eval(inputs, &vars))
+            .collect()
+    }
+
+    /// Simplifies all expressions (see `Expr::simplify`).
+    pub fn simplify(&self) -> Self {
+        self.rebuild(|expr| expr.simplify())
+    }
+
+    /// Applies `transform` to all expressions, inlining variables which are used only once or
+    /// are transformed into leafs, and dropping unused ones.
+    fn rebuild<T: Fn(Expr) -> Expr>(&self, transform: T) -> Self {
+        let mut uses = vec![0usize; self.vars.len()];
+        for output in self.outputs.iter() {
+            output.each_var(&mut |n| uses[n] += 1);
+        }
+        for (n, var) in self.vars.iter().enumerate().rev() {
+            if uses[n] > 0 {
+                var.each_var(&mut |m| uses[m] += 1);
+            }
+        }
+
+        // The replacement of each old variable.
+        let mut replacements = Vec::with_capacity(self.vars.len());
+        let mut vars = Vec::new();
+        for (var, &uses) in self.vars.iter().zip(uses.iter()) {
+            if uses == 0 {
+                // never referenced
+                replacements.push(Expr::Const(0.0));
+                continue;
+            }
+            let expr = transform(var.substitute_vars(&replacements));
+            replacements.push(if uses == 1 || expr.is_leaf() {
+                expr
+            } else {
+                share(&mut vars, expr)
+            });
+        }
+
+        CppnExpr {
+            input_count: self.input_count,
+            vars,
+            outputs: self
+                .outputs
+                .iter()
+                .map(|output| transform(output.substitute_vars(&replacements)))
+                .collect(),
+        }
+    }
+}
+
+/// Returns a variable referring to `expr`, reusing an existing variable with the same
+/// expression.
+fn share(vars: &mut Vec<Expr>, expr: Expr) -> Expr {
+    match vars.iter().position(|var| *var == expr) {
+        Some(n) => Expr::Var(n),
+        None => {
+            vars.push(expr);
+            Expr::Var(vars.len() - 1)
+        }
+    }
+}
+
+impl fmt::Display for CppnExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (n, var) in self.vars.iter().enumerate() {
+            writeln!(f, "t{} = {}", n, var)?;
+        }
+        for (n, output) in self.outputs.iter().enumerate() {
+            writeln!(f, "y{} = {}", n, output)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CppnExpr, Expr};
+    use activation_function::GeometricActivationFunction as AF;
+    use acyclic_network::ExternalId;
+    use cppn::{Cppn, CppnGraph, CppnNode};
+
+    #[test]
+    fn test_display() {
+        let x = Expr::Input(0);
+        let y = Expr::Input(1);
+        let c = Expr::Const;
+        assert_eq!(
+            "x0 - (x1 - 1.0)",
+            (x.clone() - (y.clone() - c(1.0))).to_string()
+        );
+        assert_eq!(
+            "x0 - x1 - 1.0",
+            (x.clone() - y.clone() - c(1.0)).to_string()
+        );
+        assert_eq!(
+            "(x0 + x1) * -2.5",
+            ((x.clone() + y.clone()) * c(-2.5)).to_string()
+        );
+        assert_eq!(
+            "exp(-(x0 * 2.5)^2)",
+            (-(x.clone() * c(2.5)).powi(2)).exp().to_string()
+        );
+        assert_eq!("-(-x1)", (-(-y.clone())).to_string());
+        assert_eq!(
+            "max(-1.0, min(1.0, x0))",
+            c(-1.0).max(c(1.0).min(x)).to_string()
+        );
+    }
+
+    #[test]
+    fn test_simplify() {
+        let x = Expr::Input(0);
+        let c = Expr::Const;
+        assert_eq!(x.clone(), (c(1.0) * x.clone() + c(0.0)).simplify());
+        assert_eq!(c(4.0), (c(2.0) * c(3.0) - c(2.0)).simplify());
+        assert_eq!(x.clone(), (-(-x.clone())).simplify());
+        assert_eq!(
+            x.clone() - Expr::Input(1),
+            (x.clone() + c(-1.0) * Expr::Input(1)).simplify()
+        );
+        assert_eq!(
+            (x.clone() * c(2.0)).sin(),
+            (x.clone() * (c(1.0) + c(1.0))).sin().simplify()
+        );
+    }
+
+    fn graph() -> CppnGraph<CppnNode<AF>, f64, ExternalId> {
+        let mut g = CppnGraph::new();
+        let x = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let y = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let b = g.add_node(CppnNode::bias(AF::Linear), ExternalId(3));
+        let h1 = g.add_node(CppnNode::hidden(AF::Gaussian), ExternalId(4));
+        let h2 = g.add_node(CppnNode::hidden(AF::Sine), ExternalId(5));
+        let o1 = g.add_node(CppnNode::output(AF::BipolarSigmoid), ExternalId(6));
+        let o2 = g.add_node(CppnNode::output(AF::Linear), ExternalId(7));
+        // not connected to any output
+        let h3 = g.add_node(CppnNode::hidden(AF::Cosine), ExternalId(8));
+        g.add_link(x, h1, 0.7, ExternalId(1));
+        g.add_link(y, h1, -0.4, ExternalId(2));
+        g.add_link(b, h1, 0.1, ExternalId(3));
+        g.add_link(x, h2, 0.3, ExternalId(4));
+        g.add_link(h1, h2, 1.2, ExternalId(5));
+        g.add_link(h1, o1, 2.0, ExternalId(6));
+        g.add_link(h2, o1, -1.5, ExternalId(7));
+        g.add_link(y, o2, 1.0, ExternalId(8));
+        g.add_link(x, h3, 1.0, ExternalId(9));
+        g
+    }
+
+    #[test]
+    fn test_cppn_expr_matches_calculate() {
+        let g = graph();
+        let mut cppn = Cppn::new(&g);
+        let expr = CppnExpr::new(&g);
+        let simplified = expr.simplify();
+        assert_eq!(2, expr.input_count());
+        for &(px, py) in &[(0.0, 0.0), (0.3, -0.2), (-0.8, 0.5)] {
+            let outputs = cppn.calculate(&[&[px, py]]);
+            assert_eq!(outputs, expr.eval(&[px, py]));
+            for (a, b) in outputs.iter().zip(simplified.eval(&[px, py])) {
+                assert!((a - b).abs() < 1e-12);
+            }
+            let expanded: Vec<f64> = (0..2)
+                .map(|o| expr.expand(o).eval(&[px, py], &[]))
+                .collect();
+            assert_eq!(outputs, expanded);
+        }
+    }
+
+    #[test]
+    fn test_cppn_expr_shares_subexpressions() {
+        let expr = CppnExpr::new(&graph()).simplify();
+        // only the gaussian node is used twice.
+        assert_eq!(1, expr.vars().len());
+        assert_eq!(
+            "t0 = exp(-((0.7 * x0 - 0.4 * x1 + 0.1) * 2.5)^2)\n\
+             y0 = 2.0 / (1.0 + exp(-4.9 * (2.0 * t0 - 1.5 * sin(6.283185307179586 * \
+             (0.3 * x0 + 1.2 * t0))))) - 1.0\n\
+             y1 = x1\n",
+            expr.to_string()
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use std::fmt::Debug;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+/// The numeric type of the signals flowing through a CPPN.
+///
+/// Implemented for `f32` and `f64`. `f64` is the default everywhere, `f32` halves the memory
+/// footprint of signal buffers for large substrates or image rendering.
+pub trait Float:
+    Copy
+    + Debug
+    + Default
+    + PartialOrd
+    + Send
+    + Sync
+    + 'static
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + SubAssign
+    + MulAssign
+    + DivAssign
+{
+    /// The smallest argument for which `exp` yields a normal number.
+    const EXP_MIN: f64;
+    /// The largest argument for which `exp` yields a finite number.
+    const EXP_MAX: f64;
+    /// The machine epsilon.
+    const EPSILON: f64;
+
+    fn from_f64(x: f64) -> Self;
+    fn to_f64(self) -> f64;
+
+    #[inline(always)]
+    fn zero() -> Self {
+        Self::from_f64(0.0)
+    }
+
+    #[inline(always)]
+    fn one() -> Self {
+        Self::from_f64(1.0)
+    }
+
+    fn abs(self) -> Self;
+    fn exp(self) -> Self;
+    /// The natural logarithm.
+    fn ln(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tanh(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn round(self) -> Self;
+    fn floor(self) -> Self;
+    fn ceil(self) -> Self;
+    fn powi(self, n: i32) -> Self;
+    fn min(self, other: Self) -> Self;
+    fn max(self, other: Self) -> Self;
+    fn clamp(self, min: Self, max: Self) -> Self;
+    fn is_finite(self) -> bool;
+    /// Computes `self * a + b` with a single rounding.
+    fn mul_add(self, a: Self, b: Self) -> Self;
+
+    /// The least number greater than `self`. Positive infinity and NaN are returned unchanged.
+    /// Like `f64::next_up`, which would require Rust 1.86.
+    fn next_up(self) -> Self;
+
+    /// The greatest number less than `self`. Negative infinity and NaN are returned unchanged.
+    #[inline(always)]
+    fn next_down(self) -> Self {
+        -(-self).next_up()
+    }
+
+    /// Returns `self * 2^k` for an integral `k` within the range of normal exponents. This is
+    /// done by constructing `2^k` from its bit pattern and is much cheaper than `powi`.
+    fn mul_pow2(self, k: Self) -> Self;
+}
+
+macro_rules! impl_float {
+    ($t:ident, $bits:ident, $bias:expr, $mantissa:expr, $exp_min:expr, $exp_max:expr) => {
+        impl Float for $t {
+            const EXP_MIN: f64 = $exp_min;
+            const EXP_MAX: f64 = $exp_max;
+            const EPSILON: f64 = <$t>::EPSILON as f64;
+
+            #[inline(always)]
+            fn from_f64(x: f64) -> Self {
+                x as $t
+            }
+
+            #[inline(always)]
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            #[inline(always)]
+            fn abs(self) -> Self {
+                <$t>::abs(self)
+            }
+
+            #[inline(always)]
+            fn exp(self) -> Self {
+                <$t>::exp(self)
+            }
+
+            #[inline(always)]
+            fn ln(self) -> Self {
+                <$t>::ln(self)
+            }
+
+            #[inline(always)]
+            fn sin(self) -> Self {
+                <$t>::sin(self)
+            }
+
+            #[inline(always)]
+            fn cos(self) -> Self {
+                <$t>::cos(self)
+            }
+
+            #[inline(always)]
+            fn tanh(self) -> Self {
+                <$t>::tanh(self)
+            }
+
+            #[inline(always)]
+            fn sqrt(self) -> Self {
+                <$t>::sqrt(self)
+            }
+
+            #[inline(always)]
+            fn round(self) -> Self {
+                <$t>::round(self)
+            }
+
+            #[inline(always)]
+            fn floor(self) -> Self {
+                <$t>::floor(self)
+            }
+
+            #[inline(always)]
+            fn ceil(self) -> Self {
+                <$t>::ceil(self)
+            }
+
+            #[inline(always)]
+            fn powi(self, n: i32) -> Self {
+                <$t>::powi(self, n)
+            }
+
+            #[inline(always)]
+            fn min(self, other: Self) -> Self {
+                <$t>::min(self, other)
+            }
+
+            #[inline(always)]
+            fn max(self, other: Self) -> Self {
+                <$t>::max(self, other)
+            }
+
+            #[inline(always)]
+            fn clamp(self, min: Self, max: Self) -> Self {
+                <$t>::clamp(self, min, max)
+            }
+
+            #[inline(always)]
+            fn is_finite(self) -> bool {
+                <$t>::is_finite(self)
+            }
+
+            #[inline(always)]
+            fn mul_add(self, a: Self, b: Self) -> Self {
+                <$t>::mul_add(self, a, b)
+            }
+
+            #[inline(always)]
+            fn next_up(self) -> Self {
+                if self.is_nan() || self == <$t>::INFINITY {
+                    return self;
+                }
+                let bits = self.to_bits();
+                let abs = <$t>::abs(self).to_bits();
+                if abs == 0 {
+                    // the smallest positive subnormal number.
+                    <$t>::from_bits(1)
+                } else if bits == abs {
+                    <$t>::from_bits(bits + 1)
+                } else {
+                    <$t>::from_bits(bits - 1)
+                }
+            }
+
+            #[inline(always)]
+            fn mul_pow2(self, k: Self) -> Self {
+                let exponent = (k as i32 + $bias) as $bits;
+                self * <$t>::from_bits(exponent << $mantissa)
+            }
+        }
+    };
+}
+
+impl_float!(f32, u32, 127, 23, -87.0, 88.0);
+impl_float!(f64, u64, 1023, 52, -708.0, 709.0);
@@ -0,0 +1,260 @@
+use float::Float;
+use std::cmp::Ordering;
+use std::ops::{Add, Mul};
+
+/// A closed interval `[lo, hi]` of signal values.
+///
+/// Used to compute bounds on the outputs of a CPPN over a whole box of inputs (see
+/// `Cppn::calculate_interval`). The arithmetic operations round the lower bound down and the
+/// upper bound up, so their results contain every value the operation can take in floating
+/// point arithmetic. `map_increasing` and `map_periodic` do no such rounding, see
+/// `CompiledCppn::process_interval` for how the bounds of activation functions are widened.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interval<F = f64>
+where
+    F: Float,
+{
+    pub lo: F,
+    pub hi: F,
+}
+
+impl<F> Interval<F>
+where
+    F: Float,
+{
+    pub fn new(lo: F, hi: F) -> Self {
+        debug_assert!(lo.partial_cmp(&hi) != Some(Ordering::Greater));
+        Interval { lo, hi }
+    }
+
+    /// The interval containing only `x`.
+    pub fn point(x: F) -> Self {
+        Self::new(x, x)
+    }
+
+    /// The interval of all values.
+    pub fn entire() -> Self {
+        Self::new(
+            F::from_f64(f64::NEG_INFINITY),
+            F::from_f64(f64::INFINITY),
+        )
+    }
+
+    /// The largest absolute value of a finite bound, or zero if both bounds are infinite.
+    pub fn magnitude(&self) -> F {
+        let finite = |x: F| if x.is_finite() { x.abs() } else { F::zero() };
+        finite(self.lo).max(finite(self.hi))
+    }
+
+    /// Moves both bounds outwards by `margin`.
+    pub fn widen(&self, margin: F) -> Self {
+        Self::new(self.lo - margin, self.hi + margin)
+    }
+
+    pub fn width(&self) -> F {
+        self.hi - self.lo
+    }
+
+    pub fn contains(&self, x: F) -> bool {
+        x >= self.lo && x <= self.hi
+    }
+
+    /// The smallest interval containing both `self` and `other`.
+    pub fn hull(&self, other: &Self) -> Self {
+        Self::new(self.lo.min(other.lo), self.hi.max(other.hi))
+    }
+
+    /// Multiplies the interval by the scalar `weight`.
+    pub fn scale(&self, weight: F) -> Self {
+        if weight > F::zero() {
+            Self::new(mul_down(weight, self.lo), mul_up(weight, self.hi))
+        } else if weight < F::zero() {
+            Self::new(mul_down(weight, self.hi), mul_up(weight, self.lo))
+        } else {
+            // avoid `0 * inf = NaN` for unbounded intervals.
+            Self::point(F::zero())
+        }
+    }
+
+    /// Applies a monotonically increasing function to both bounds. The results are ordered, as
+    /// floating point implementations of such functions can decrease by an ulp here and there.
+    pub fn map_increasing<M: Fn(F) -> F>(&self, f: M) -> Self {
+        let (lo, hi) = (f(self.lo), f(self.hi));
+        Self::new(lo.min(hi), lo.max(hi))
+    }
+
+    /// The interval of `|x|` for all `x` in `self`.
+    pub fn abs(&self) -> Self {
+        if self.lo >= F::zero() {
+            *self
+        } else if self.hi <= F::zero() {
+            Self::new(-self.hi, -self.lo)
+        } else {
+            Self::new(F::zero(), (-self.lo).max(self.hi))
+        }
+    }
+
+    /// The interval of `f(x)` for all `x` in `self`, where `f` has period 1, takes its maximum
+    /// `max` at `x = peak` and its minimum `min` at `x = peak + 0.5`, and is monotonic in
+    /// between (like `sin(2 pi x)` and `cos(2 pi x)`).
+    pub fn map_periodic<M: Fn(F) -> F>(&self, f: M, peak: F, min: F, max: F) -> Self {
+        if !self.width().is_finite() || self.width() >= F::one() {
+            return Self::new(min, max);
+        }
+        let half = F::from_f64(0.5);
+        let lo = f(self.lo).min(f(self.hi));
+        let hi = f(self.lo).max(f(self.hi));
+        let contains_shifted = |shift: F| {
+            // whether `self` contains `shift + k` for an integer `k`.
+            (self.lo - shift).ceil() <= self.hi - shift
+        };
+        Self::new(
+            if contains_shifted(peak + half) { min } else { lo },
+            if contains_shifted(peak) { max } else { hi },
+        )
+    }
+}
+
+impl<F> Add for Interval<F>
+where
+    F: Float,
+{
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(add_down(self.lo, other.lo), add_up(self.hi, other.hi))
+    }
+}
+
+impl<F> Mul for Interval<F>
+where
+    F: Float,
+{
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        // `0 * inf` is taken as 0, as both bounds stand for finite values.
+        let product = |a: F, b: F| {
+            if a == F::zero() || b == F::zero() {
+                Self::point(F::zero())
+            } else {
+                Self::new(mul_down(a, b), mul_up(a, b))
+            }
+        };
+        let products = [
+            product(self.lo, other.lo),
+            product(self.lo, other.hi),
+            product(self.hi, other.lo),
+            product(self.hi, other.hi),
+        ];
+        products[1..].iter().fold(products[0], |a, b| a.hull(b))
+    }
+}
+
+/// Rounds `value` down unless `value + error` shows that it is exact. `error` is not finite if
+/// an operand is infinite or the result overflowed.
+fn round_down<F: Float>(value: F, error: F) -> F {
+    if error < F::zero() || !error.is_finite() {
+        value.next_down()
+    } else {
+        value
+    }
+}
+
+/// Rounds `value` up unless `value + error` shows that it is exact.
+fn round_up<F: Float>(value: F, error: F) -> F {
+    if error > F::zero() || !error.is_finite() {
+        value.next_up()
+    } else {
+        value
+    }
+}
+
+/// The rounding error of `a + b`, such that `(a + b) + error` is exact (Knuth's TwoSum).
+fn add_error<F: Float>(a: F, b: F, sum: F) -> F {
+    let b_virtual = sum - a;
+    (a - (sum - b_virtual)) + (b - b_virtual)
+}
+
+fn add_down<F: Float>(a: F, b: F) -> F {
+    round_down(a + b, add_error(a, b, a + b))
+}
+
+fn add_up<F: Float>(a: F, b: F) -> F {
+    round_up(a + b, add_error(a, b, a + b))
+}
+
+fn mul_down<F: Float>(a: F, b: F) -> F {
+    round_down(a * b, a.mul_add(b, -(a * b)))
+}
+
+fn mul_up<F: Float>(a: F, b: F) -> F {
+    round_up(a * b, a.mul_add(b, -(a * b)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Interval;
+
+    #[test]
+    fn test_scale() {
+        let x = Interval::new(-1.0, 2.0);
+        assert_eq!(Interval::new(-2.0, 4.0), x.scale(2.0));
+        assert_eq!(Interval::new(-6.0, 3.0), x.scale(-3.0));
+        assert_eq!(Interval::point(0.0), Interval::entire().scale(0.0));
+    }
+
+    #[test]
+    fn test_mul() {
+        let x = Interval::new(-1.0, 2.0);
+        assert_eq!(Interval::new(-6.0, 3.0), x * Interval::new(-3.0, 1.0));
+        assert_eq!(Interval::new(2.0, 8.0), Interval::new(1.0, 2.0) * Interval::new(2.0, 4.0));
+        assert_eq!(Interval::point(0.0), Interval::point(0.0) * Interval::entire());
+    }
+
+    #[test]
+    fn test_outward_rounding() {
+        use float::Float;
+
+        // exact results are not widened.
+        assert_eq!(Interval::new(1.5, 3.5), Interval::new(1.0, 2.0) + Interval::new(0.5, 1.5));
+        let x = Interval::point(0.1) + Interval::point(0.2);
+        assert!(x.lo < 0.1 + 0.2 && 0.1 + 0.2 <= x.hi);
+        assert_eq!((0.1 + 0.2).next_down(), x.lo);
+        let x = Interval::point(0.1f32).scale(3.0);
+        assert!(x.lo < x.hi && x.contains(0.1 * 3.0));
+        let x = Interval::point(0.1) * Interval::point(-0.7);
+        assert!(x.lo < x.hi && x.contains(0.1 * -0.7));
+
+        // results that overflow are bounded by infinity.
+        let x = Interval::point(f64::MAX) + Interval::point(f64::MAX);
+        assert_eq!(Interval::new(f64::MAX, f64::INFINITY), x);
+        assert_eq!(Interval::entire(), Interval::entire() + Interval::point(1.0));
+
+        assert_eq!(f64::MIN_POSITIVE / 4503599627370496.0, 0.0.next_up());
+        assert_eq!(-1.0 + f64::EPSILON / 2.0, (-1.0f64).next_up());
+        assert_eq!(1.0 - f64::EPSILON / 2.0, 1.0.next_down());
+        assert_eq!(f32::INFINITY, f32::MAX.next_up());
+        assert_eq!(f32::NEG_INFINITY, f32::NEG_INFINITY.next_down());
+        assert_eq!(-f32::MAX, f32::NEG_INFINITY.next_up());
+    }
+
+    #[test]
+    fn test_abs() {
+        assert_eq!(Interval::new(1.0, 2.0), Interval::new(1.0, 2.0).abs());
+        assert_eq!(Interval::new(1.0, 2.0), Interval::new(-2.0, -1.0).abs());
+        assert_eq!(Interval::new(0.0, 3.0), Interval::new(-3.0, 1.0).abs());
+    }
+
+    #[test]
+    fn test_map_periodic() {
+        let triangle = |x: f64| 1.0 - 4.0 * (x - x.floor() - 0.5).abs();
+        let f = |x: Interval| x.map_periodic(triangle, 0.5, -1.0, 1.0);
+        assert_eq!(Interval::new(-1.0, 1.0), f(Interval::new(0.0, 1.0)));
+        assert_eq!(Interval::new(-1.0, 1.0), f(Interval::new(-10.0, 10.0)));
+        assert_eq!(Interval::new(0.0, 1.0), f(Interval::new(0.25, 0.75)));
+        assert_eq!(Interval::new(-1.0, 0.0), f(Interval::new(-0.25, 0.25)));
+        assert_eq!(Interval::new(0.0, 1.0), f(Interval::new(1.25, 1.5)));
+        assert_eq!(Interval::new(-1.0, -0.5), f(Interval::new(0.875, 1.125)));
+    }
+}
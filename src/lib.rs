@@ -1,3 +1,15 @@
+// The original code predates these lints and is kept as written.
+#![allow(
+    type_alias_bounds,
+    clippy::bool_assert_comparison,
+    clippy::len_zero,
+    clippy::match_like_matches_macro,
+    clippy::needless_return,
+    clippy::new_without_default,
+    clippy::redundant_field_names,
+    clippy::useless_format
+)]
+
 extern crate acyclic_network;
 extern crate fixedbitset;
 #[cfg(test)]
@@ -8,6 +20,21 @@ extern crate rand;
 extern crate serde;
 
 pub mod activation_function;
+pub mod aggregation;
+pub mod codegen;
+pub mod compiled;
 pub mod cppn;
+pub mod evaluator;
+pub mod expr;
+pub mod float;
+pub mod interval;
+pub mod kernels;
+pub mod parametric;
 pub mod position;
+pub mod recurrent;
+pub mod registry;
+pub mod signature;
+pub mod simplify;
 pub mod substrate;
+pub mod trace;
+pub mod train;
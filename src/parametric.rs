@@ -0,0 +1,491 @@
+use activation_function::{ActivationFunction, GeometricActivationFunction};
+use expr::Expr;
+use float::Float;
+use interval::Interval;
+use std::f64::consts::PI;
+use std::mem;
+
+/// Activation functions whose shape is controlled by parameters, which are stored with each
+/// node and can be evolved along with the link weights (see `parameters_mut`).
+///
+/// The default parameters (see `gaussian`, `bipolar_sigmoid`, `sine` etc.) yield the same
+/// functions as the corresponding `GeometricActivationFunction`s. `Geometric` embeds any of the
+/// functions without parameters, so that a network can mix both kinds.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ParametricActivationFunction {
+    Geometric(GeometricActivationFunction),
+    /// `exp(-((x - offset) * width)^2)`.
+    Gaussian {
+        width: f64,
+        offset: f64,
+    },
+    /// `2 * exp(-((x - offset) * width)^2) - 1`.
+    BipolarGaussian {
+        width: f64,
+        offset: f64,
+    },
+    /// `2 / (1 + exp(-slope * (x - offset))) - 1`.
+    BipolarSigmoid {
+        slope: f64,
+        offset: f64,
+    },
+    /// `sin(frequency * x + phase)`.
+    Sine {
+        frequency: f64,
+        phase: f64,
+    },
+    /// `cos(frequency * x + phase)`.
+    Cosine {
+        frequency: f64,
+        phase: f64,
+    },
+}
+
+impl ParametricActivationFunction {
+    pub fn gaussian() -> Self {
+        ParametricActivationFunction::Gaussian {
+            width: 2.5,
+            offset: 0.0,
+        }
+    }
+
+    pub fn bipolar_gaussian() -> Self {
+        ParametricActivationFunction::BipolarGaussian {
+            width: 2.5,
+            offset: 0.0,
+        }
+    }
+
+    pub fn bipolar_sigmoid() -> Self {
+        ParametricActivationFunction::BipolarSigmoid {
+            slope: 4.9,
+            offset: 0.0,
+        }
+    }
+
+    pub fn sine() -> Self {
+        ParametricActivationFunction::Sine {
+            frequency: 2.0 * PI,
+            phase: 0.0,
+        }
+    }
+
+    pub fn cosine() -> Self {
+        ParametricActivationFunction::Cosine {
+            frequency: 2.0 * PI,
+            phase: 0.0,
+        }
+    }
+
+    /// The names of the parameters, in the order of `parameters`.
+    pub fn parameter_names(&self) -> &'static [&'static str] {
+        match *self {
+            ParametricActivationFunction::Geometric(_) => &[],
+            ParametricActivationFunction::Gaussian { .. }
+            | ParametricActivationFunction::BipolarGaussian { .. } => &["width", "offset"],
+            ParametricActivationFunction::BipolarSigmoid { .. } => &["slope", "offset"],
+            ParametricActivationFunction::Sine { .. }
+            | ParametricActivationFunction::Cosine { .. } => &["frequency", "phase"],
+        }
+    }
+
+    pub fn parameters(&self) -> Vec<f64> {
+        let mut copy = *self;
+        copy.parameters_mut().into_iter().map(|p| *p).collect()
+    }
+
+    /// References to all parameters, e.g. to perturb them in a mutation operator.
+    pub fn parameters_mut(&mut self) -> Vec<&mut f64> {
+        match *self {
+            ParametricActivationFunction::Geometric(_) => vec![],
+            ParametricActivationFunction::Gaussian {
+                ref mut width,
+                ref mut offset,
+            }
+            | ParametricActivationFunction::BipolarGaussian {
+                ref mut width,
+                ref mut offset,
+            } => vec![width, offset],
+            ParametricActivationFunction::BipolarSigmoid {
+                ref mut slope,
+                ref mut offset,
+            } => vec![slope, offset],
+            ParametricActivationFunction::Sine {
+                ref mut frequency,
+                ref mut phase,
+            }
+            | ParametricActivationFunction::Cosine {
+                ref mut frequency,
+                ref mut phase,
+            } => vec![frequency, phase],
+        }
+    }
+}
+
+impl From<GeometricActivationFunction> for ParametricActivationFunction {
+    fn from(f: GeometricActivationFunction) -> Self {
+        ParametricActivationFunction::Geometric(f)
+    }
+}
+
+// Parameters are compared bitwise, so that equality is reflexive (`Eq`).
+impl PartialEq for ParametricActivationFunction {
+    fn eq(&self, other: &Self) -> bool {
+        match (*self, *other) {
+            (
+                ParametricActivationFunction::Geometric(a),
+                ParametricActivationFunction::Geometric(b),
+            ) => a == b,
+            (a, b) => {
+                mem::discriminant(&a) == mem::discriminant(&b)
+                    && a.parameters()
+                        .iter()
+                        .zip(b.parameters().iter())
+                        .all(|(p, q)| p.to_bits() == q.to_bits())
+            }
+        }
+    }
+}
+
+impl Eq for ParametricActivationFunction {}
+
+/// Bounds of `f(y)` for all `y` within `[a, b]`, where `f` has period `2 pi`, takes its maximum
+/// 1 at `y = peak` and its minimum -1 at `y = peak + pi` (like `sin` and `cos`).
+fn periodic_interval<F, M>(a: F, b: F, f: M, peak: f64) -> Interval<F>
+where
+    F: Float,
+    M: Fn(F) -> F,
+{
+    let period = F::from_f64(2.0 * PI);
+    if !(b - a).is_finite() || b - a >= period {
+        return Interval::new(-F::one(), F::one());
+    }
+    let contains_shifted = |shift: F| {
+        // whether `[a, b]` contains `shift + k * period` for an integer `k`.
+        ((a - shift) / period).ceil() <= (b - shift) / period
+    };
+    Interval::new(
+        if contains_shifted(F::from_f64(peak + PI)) {
+            -F::one()
+        } else {
+            f(a).min(f(b))
+        },
+        if contains_shifted(F::from_f64(peak)) {
+            F::one()
+        } else {
+            f(a).max(f(b))
+        },
+    )
+}
+
+impl ActivationFunction for ParametricActivationFunction {
+    fn calculate<F: Float>(&self, x: F) -> F {
+        let c = F::from_f64;
+        match *self {
+            ParametricActivationFunction::Geometric(f) => f.calculate(x),
+            ParametricActivationFunction::Gaussian { width, offset } => {
+                (-((x - c(offset)) * c(width)).powi(2)).exp()
+            }
+            ParametricActivationFunction::BipolarGaussian { width, offset } => {
+                c(2.0) * (-((x - c(offset)) * c(width)).powi(2)).exp() - F::one()
+            }
+            ParametricActivationFunction::BipolarSigmoid { slope, offset } => {
+                c(2.0) / (F::one() + (c(-slope) * (x - c(offset))).exp()) - F::one()
+            }
+            ParametricActivationFunction::Sine { frequency, phase } => {
+                (c(frequency) * x + c(phase)).sin()
+            }
+            ParametricActivationFunction::Cosine { frequency, phase } => {
+                (c(frequency) * x + c(phase)).cos()
+            }
+        }
+    }
+
+    fn calculate_slice<F: Float>(&self, xs: &mut [F]) {
+        match *self {
+            ParametricActivationFunction::Geometric(f) => f.calculate_slice(xs),
+            _ => {
+                for x in xs.iter_mut() {
+                    *x = self.calculate(*x);
+                }
+            }
+        }
+    }
+
+    fn derivative<F: Float>(&self, x: F) -> F {
+        let c = F::from_f64;
+        match *self {
+            ParametricActivationFunction::Geometric(f) => f.derivative(x),
+            ParametricActivationFunction::Gaussian { width, offset } => {
+                c(-2.0 * width * width) * (x - c(offset)) * self.calculate(x)
+            }
+            ParametricActivationFunction::BipolarGaussian { width, offset } => {
+                let gaussian = ParametricActivationFunction::Gaussian { width, offset };
+                c(-4.0 * width * width) * (x - c(offset)) * gaussian.calculate(x)
+            }
+            ParametricActivationFunction::BipolarSigmoid { slope, .. } => {
+                // the bipolar sigmoid is tanh(slope / 2 * (x - offset)).
+                let y = self.calculate(x);
+                c(slope / 2.0) * (F::one() - y * y)
+            }
+            ParametricActivationFunction::Sine { frequency, phase } => {
+                c(frequency) * (c(frequency) * x + c(phase)).cos()
+            }
+            ParametricActivationFunction::Cosine { frequency, phase } => {
+                -c(frequency) * (c(frequency) * x + c(phase)).sin()
+            }
+        }
+    }
+
+    fn calculate_interval<F: Float>(&self, x: Interval<F>) -> Interval<F> {
+        let c = F::from_f64;
+        let f = |x| self.calculate(x);
+        match *self {
+            ParametricActivationFunction::Geometric(f) => f.calculate_interval(x),
+            ParametricActivationFunction::Gaussian { offset, .. }
+            | ParametricActivationFunction::BipolarGaussian { offset, .. } => {
+                // decreasing in |x - offset|, with the maximum at `offset`.
+                let hi = if x.contains(c(offset)) {
+                    f(c(offset))
+                } else {
+                    f(x.lo).max(f(x.hi))
+                };
+                Interval::new(f(x.lo).min(f(x.hi)), hi)
+            }
+            ParametricActivationFunction::BipolarSigmoid { .. } => {
+                Interval::new(f(x.lo).min(f(x.hi)), f(x.lo).max(f(x.hi)))
+            }
+            ParametricActivationFunction::Sine { frequency, phase }
+            | ParametricActivationFunction::Cosine { frequency, phase } => {
+                let a = c(frequency) * x.lo + c(phase);
+                let b = c(frequency) * x.hi + c(phase);
+                let (a, b) = if a <= b { (a, b) } else { (b, a) };
+                match *self {
+                    ParametricActivationFunction::Sine { .. } => {
+                        periodic_interval(a, b, |y: F| y.sin(), PI / 2.0)
+                    }
+                    _ => periodic_interval(a, b, |y: F| y.cos(), 0.0),
+                }
+            }
+        }
+    }
+
+    fn expr(&self, x: Expr) -> Expr {
+        let c = Expr::Const;
+        match *self {
+            ParametricActivationFunction::Geometric(f) => f.expr(x),
+            ParametricActivationFunction::Gaussian { width, offset } => {
+                (-((x - c(offset)) * c(width)).powi(2)).exp()
+            }
+            ParametricActivationFunction::BipolarGaussian { width, offset } => {
+                c(2.0) * (-((x - c(offset)) * c(width)).powi(2)).exp() - c(1.0)
+            }
+            ParametricActivationFunction::BipolarSigmoid { slope, offset } => {
+                c(2.0) / (c(1.0) + (c(-slope) * (x - c(offset))).exp()) - c(1.0)
+            }
+            ParametricActivationFunction::Sine { frequency, phase } => {
+                (c(frequency) * x + c(phase)).sin()
+            }
+            ParametricActivationFunction::Cosine { frequency, phase } => {
+                (c(frequency) * x + c(phase)).cos()
+            }
+        }
+    }
+
+    fn formula_gnuplot(&self, x: String) -> String {
+        match *self {
+            ParametricActivationFunction::Geometric(f) => f.formula_gnuplot(x),
+            ParametricActivationFunction::Gaussian { width, offset } => {
+                format!("(exp(-(((({}) - {:?}) * {:?})**2.0))", x, offset, width)
+            }
+            ParametricActivationFunction::BipolarGaussian { width, offset } => format!(
+                "2.0 * exp(-(((({}) - {:?}) * {:?})**2.0)) - 1.0",
+                x, offset, width
+            ),
+            ParametricActivationFunction::BipolarSigmoid { slope, offset } => format!(
+                "2.0 / (1.0 + exp({:?} * (({}) - {:?}))) - 1.0",
+                -slope, x, offset
+            ),
+            ParametricActivationFunction::Sine { frequency, phase } => {
+                format!("sin({:?} * ({}) + {:?})", frequency, x, phase)
+            }
+            ParametricActivationFunction::Cosine { frequency, phase } => {
+                format!("cos({:?} * ({}) + {:?})", frequency, x, phase)
+            }
+        }
+    }
+
+    fn name(&self) -> String {
+        let name = match *self {
+            ParametricActivationFunction::Geometric(f) => return f.name(),
+            ParametricActivationFunction::Gaussian { .. } => "Gaussian",
+            ParametricActivationFunction::BipolarGaussian { .. } => "BipolarGaussian",
+            ParametricActivationFunction::BipolarSigmoid { .. } => "BipolarSigmoid",
+            ParametricActivationFunction::Sine { .. } => "Sine",
+            ParametricActivationFunction::Cosine { .. } => "Cosine",
+        };
+        let parameters: Vec<String> = self
+            .parameter_names()
+            .iter()
+            .zip(self.parameters())
+            .map(|(name, value)| format!("{}={:?}", name, value))
+            .collect();
+        format!("{}({})", name, parameters.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParametricActivationFunction as P;
+    use activation_function::{ActivationFunction, GeometricActivationFunction as AF};
+    use acyclic_network::ExternalId;
+    use cppn::{Cppn, CppnGraph, CppnNode};
+    use interval::Interval;
+
+    fn examples() -> Vec<P> {
+        vec![
+            P::Geometric(AF::Tanh),
+            P::Gaussian {
+                width: 1.3,
+                offset: 0.2,
+            },
+            P::BipolarGaussian {
+                width: -0.7,
+                offset: -0.4,
+            },
+            P::BipolarSigmoid {
+                slope: 2.2,
+                offset: 0.3,
+            },
+            P::BipolarSigmoid {
+                slope: -1.5,
+                offset: 0.0,
+            },
+            P::Sine {
+                frequency: 3.1,
+                phase: 0.5,
+            },
+            P::Cosine {
+                frequency: -1.7,
+                phase: -2.0,
+            },
+        ]
+    }
+
+    fn samples() -> Vec<f64> {
+        (0..=80).map(|i| -2.0 + i as f64 * 0.05 + 0.001).collect()
+    }
+
+    #[test]
+    fn test_defaults_match_geometric() {
+        let pairs = [
+            (P::gaussian(), AF::Gaussian),
+            (P::bipolar_gaussian(), AF::BipolarGaussian),
+            (P::bipolar_sigmoid(), AF::BipolarSigmoid),
+            (P::sine(), AF::Sine),
+            (P::cosine(), AF::Cosine),
+        ];
+        for &(p, g) in pairs.iter() {
+            for x in samples() {
+                assert_eq!(g.calculate(x), p.calculate(x));
+                assert!((g.derivative(x) - p.derivative(x)).abs() < 1e-9);
+            }
+        }
+        assert_eq!(P::from(AF::Tanh), P::Geometric(AF::Tanh));
+        assert_eq!("Tanh", P::from(AF::Tanh).name());
+    }
+
+    #[test]
+    fn test_derivative() {
+        let h = 1e-6;
+        for f in examples() {
+            for x in samples() {
+                let numeric = (f.calculate(x + h) - f.calculate(x - h)) / (2.0 * h);
+                assert!((numeric - f.derivative(x)).abs() < 1e-5, "{}", f.name());
+            }
+        }
+    }
+
+    #[test]
+    fn test_interval() {
+        for f in examples() {
+            let xs = samples();
+            for window in xs.windows(9) {
+                let bounds = f.calculate_interval(Interval::new(window[0], window[8]));
+                for &x in window.iter() {
+                    assert!(bounds.contains(f.calculate(x)), "{} at {}", f.name(), x);
+                }
+            }
+            let wide = f.calculate_interval(Interval::new(-10.0, 10.0));
+            assert!(wide.lo >= -1.0 && wide.hi <= 1.0, "{}", f.name());
+        }
+        let bounds = P::sine().calculate_interval(Interval::new(0.2, 0.3));
+        assert_eq!(1.0, bounds.hi);
+        let bounds = P::gaussian().calculate_interval(Interval::new(0.5, 1.0));
+        assert!(bounds.hi < 1.0);
+    }
+
+    #[test]
+    fn test_expr_and_formula() {
+        for f in examples() {
+            let expr = f.expr(super::Expr::Var(0));
+            for x in samples() {
+                assert!((expr.eval(&[], &[x]) - f.calculate(x)).abs() < 1e-12);
+            }
+        }
+        let f = P::Sine {
+            frequency: 3.0,
+            phase: 0.5,
+        };
+        assert_eq!("sin(3.0 * (x) + 0.5)", f.formula_gnuplot("x".to_string()));
+        assert_eq!("Sine(frequency=3.0, phase=0.5)", f.name());
+    }
+
+    #[test]
+    fn test_mutate_parameters() {
+        let mut f = P::gaussian();
+        assert_eq!(vec![2.5, 0.0], f.parameters());
+        assert_eq!(&["width", "offset"], f.parameter_names());
+        let before = f.calculate(0.3);
+        for p in f.parameters_mut() {
+            *p += 0.1;
+        }
+        assert_eq!(vec![2.6, 0.1], f.parameters());
+        assert!(f.calculate(0.3) != before);
+        assert!(f != P::gaussian());
+        assert!(P::Geometric(AF::Linear).parameters().is_empty());
+    }
+
+    #[test]
+    fn test_in_cppn() {
+        let mut g = CppnGraph::new();
+        let x = g.add_node(CppnNode::input(P::from(AF::Linear)), ExternalId(1));
+        let h = g.add_node(
+            CppnNode::hidden(P::Sine {
+                frequency: 1.5,
+                phase: 0.25,
+            }),
+            ExternalId(2),
+        );
+        let o = g.add_node(
+            CppnNode::output(P::Gaussian {
+                width: 0.8,
+                offset: 0.1,
+            }),
+            ExternalId(3),
+        );
+        g.add_link(x, h, 0.5, ExternalId(1));
+        g.add_link(h, o, 2.0, ExternalId(2));
+
+        let mut cppn = Cppn::new(&g);
+        let expr = cppn.expr();
+        for px in samples() {
+            let hidden = (1.5 * 0.5 * px + 0.25).sin();
+            let expected = (-((2.0 * hidden - 0.1) * 0.8f64).powi(2)).exp();
+            let outputs = cppn.calculate(&[&[px]]);
+            assert!((outputs[0] - expected).abs() < 1e-12);
+            assert!((expr.eval(&[px])[0] - outputs[0]).abs() < 1e-12);
+        }
+    }
+}
@@ -1,135 +1,163 @@
+use float::Float;
+
 /// Represents a position within a substrate.
 /// Each position coordinate is mapped to an input of a CPPN.
 pub trait Position {
+    /// The numeric type of the coordinates, which is also the signal type of the CPPN.
+    type Scalar: Float;
+
     // Number of dimensions
     fn dims() -> usize;
 
-    fn coords(&self) -> &[f64];
-    fn distance_square(&self, other: &Self) -> f64;
-    fn distance(&self, other: &Self) -> f64 {
+    fn coords(&self) -> &[Self::Scalar];
+    fn distance_square(&self, other: &Self) -> Self::Scalar;
+    fn distance(&self, other: &Self) -> Self::Scalar {
         self.distance_square(other).sqrt()
     }
     fn origin() -> Self;
 }
 
-pub trait Interpolate {
+pub trait Interpolate: Position {
     /// Linearly interpolates between `self` and `other`.
     /// For t = 0.0 this should return `self`. For t = 1.0 this
     /// should return `other`.
-    fn interpolate(&self, other: &Self, t: f64) -> Self;
+    fn interpolate(&self, other: &Self, t: Self::Scalar) -> Self;
 
     fn interpolate_multi(&self, other: &Self, t: &Self) -> Self;
 }
 
-pub struct Position2d([f64; 2]);
+pub struct Position2d<F = f64>([F; 2])
+where
+    F: Float;
 
-impl Position2d {
+impl<F> Position2d<F>
+where
+    F: Float,
+{
     #[inline(always)]
-    pub fn new(x: f64, y: f64) -> Self {
+    pub fn new(x: F, y: F) -> Self {
         Position2d([x, y])
     }
 
     #[inline(always)]
-    pub fn x(&self) -> f64 {
+    pub fn x(&self) -> F {
         self.0[0]
     }
 
     #[inline(always)]
-    pub fn y(&self) -> f64 {
+    pub fn y(&self) -> F {
         self.0[1]
     }
 
     #[inline(always)]
-    pub fn xy(&self) -> (f64, f64) {
+    pub fn xy(&self) -> (F, F) {
         (self.0[0], self.0[1])
     }
 }
 
-impl Position for Position2d {
+impl<F> Position for Position2d<F>
+where
+    F: Float,
+{
+    type Scalar = F;
+
     #[inline(always)]
     fn dims() -> usize {
         2
     }
 
     #[inline(always)]
-    fn coords(&self) -> &[f64] {
+    fn coords(&self) -> &[F] {
         &self.0
     }
 
     #[inline(always)]
     fn origin() -> Self {
-        Position2d::new(0.0, 0.0)
+        Position2d::new(F::zero(), F::zero())
     }
 
     #[inline]
-    fn distance_square(&self, other: &Self) -> f64 {
+    fn distance_square(&self, other: &Self) -> F {
         (self.x() - other.x()).powi(2) + (self.y() - other.y()).powi(2)
     }
 }
 
-impl Interpolate for Position2d {
-    fn interpolate(&self, other: &Self, t: f64) -> Self {
-        let x = self.x() * (1.0 - t) + other.x() * t;
-        let y = self.y() * (1.0 - t) + other.y() * t;
+impl<F> Interpolate for Position2d<F>
+where
+    F: Float,
+{
+    fn interpolate(&self, other: &Self, t: F) -> Self {
+        let x = self.x() * (F::one() - t) + other.x() * t;
+        let y = self.y() * (F::one() - t) + other.y() * t;
         Position2d([x, y])
     }
 
     fn interpolate_multi(&self, other: &Self, t: &Self) -> Self {
         let tx = t.x();
         let ty = t.y();
-        let x = self.x() * (1.0 - tx) + other.x() * tx;
-        let y = self.y() * (1.0 - ty) + other.y() * ty;
+        let x = self.x() * (F::one() - tx) + other.x() * tx;
+        let y = self.y() * (F::one() - ty) + other.y() * ty;
         Position2d([x, y])
     }
 }
 
-pub struct Position3d([f64; 3]);
+pub struct Position3d<F = f64>([F; 3])
+where
+    F: Float;
 
-impl Position3d {
+impl<F> Position3d<F>
+where
+    F: Float,
+{
     #[inline(always)]
-    pub fn new(x: f64, y: f64, z: f64) -> Self {
+    pub fn new(x: F, y: F, z: F) -> Self {
         Position3d([x, y, z])
     }
 
     #[inline(always)]
-    pub fn x(&self) -> f64 {
+    pub fn x(&self) -> F {
         self.0[0]
     }
 
     #[inline(always)]
-    pub fn y(&self) -> f64 {
+    pub fn y(&self) -> F {
         self.0[1]
     }
 
     #[inline(always)]
-    pub fn z(&self) -> f64 {
+    pub fn z(&self) -> F {
         self.0[2]
     }
 
     #[inline(always)]
-    pub fn xyz(&self) -> (f64, f64, f64) {
+    pub fn xyz(&self) -> (F, F, F) {
         (self.0[0], self.0[1], self.0[2])
     }
 }
 
-impl Position for Position3d {
+impl<F> Position for Position3d<F>
+where
+    F: Float,
+{
+    type Scalar = F;
+
     #[inline(always)]
     fn dims() -> usize {
         3
     }
 
     #[inline(always)]
-    fn coords(&self) -> &[f64] {
+    fn coords(&self) -> &[F] {
         &self.0
     }
 
     #[inline(always)]
     fn origin() -> Self {
-        Position3d::new(0.0, 0.0, 0.0)
+        Position3d::new(F::zero(), F::zero(), F::zero())
     }
 
     #[inline]
-    fn distance_square(&self, other: &Self) -> f64 {
+    fn distance_square(&self, other: &Self) -> F {
         (self.x() - other.x()).powi(2) + (self.y() - other.y()).powi(2)
             + (self.z() - other.z()).powi(2)
     }
@@ -0,0 +1,455 @@
+use aggregation::Aggregation;
+use compiled::CompiledLink;
+use cppn::{CppnError, CppnGraph, CppnNodeIndex, CppnNodeType};
+use float::Float;
+use std::fmt::Debug;
+
+/// A link of a `RecurrentGraph`. Unlike in a `CppnGraph`, links may form cycles and a node may
+/// be linked to itself.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RecurrentLink {
+    pub source: CppnNodeIndex,
+    pub target: CppnNodeIndex,
+    pub weight: f64,
+}
+
+/// A CPPN graph which allows cycles and self-loops.
+#[derive(Clone, Debug)]
+pub struct RecurrentGraph<N>
+where
+    N: CppnNodeType,
+{
+    nodes: Vec<N>,
+    links: Vec<RecurrentLink>,
+}
+
+impl<N> Default for RecurrentGraph<N>
+where
+    N: CppnNodeType,
+{
+    fn default() -> Self {
+        RecurrentGraph {
+            nodes: Vec::new(),
+            links: Vec::new(),
+        }
+    }
+}
+
+impl<N> RecurrentGraph<N>
+where
+    N: CppnNodeType,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a recurrent graph with the same nodes (and node indices) and active links as
+    /// `graph`, e.g. to add recurrent links to an evolved feed-forward CPPN.
+    pub fn from_graph<L, EXTID>(graph: &CppnGraph<N, L, EXTID>) -> Self
+    where
+        L: Copy + Debug + Send + Sized + Into<f64>,
+        EXTID: Copy + Debug + Send + Sized + Ord,
+    {
+        let mut recurrent = Self::new();
+        for node in graph.nodes().iter() {
+            recurrent.add_node(node.node_type().clone());
+        }
+        graph.each_node_with_index(|_, source| {
+            graph.each_active_forward_link_of_node(source, |target, weight| {
+                recurrent.add_link(source, target, weight.into());
+            });
+        });
+        recurrent
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn nodes(&self) -> &[N] {
+        &self.nodes
+    }
+
+    pub fn links(&self) -> &[RecurrentLink] {
+        &self.links
+    }
+
+    pub fn links_mut(&mut self) -> &mut [RecurrentLink] {
+        &mut self.links
+    }
+
+    pub fn add_node(&mut self, node_type: N) -> CppnNodeIndex {
+        self.nodes.push(node_type);
+        CppnNodeIndex::new(self.nodes.len() - 1)
+    }
+
+    /// Adds a link from `source` to `target`, which may be the same node.
+    ///
+    /// Panics if `source` does not accept outgoing links or `target` does not accept incoming
+    /// links (see `NodeType`).
+    pub fn add_link(&mut self, source: CppnNodeIndex, target: CppnNodeIndex, weight: f64) {
+        assert!(self.nodes[source.index()].accept_outgoing_links());
+        assert!(self.nodes[target.index()].accept_incoming_links());
+        self.links.push(RecurrentLink {
+            source,
+            target,
+            weight,
+        });
+    }
+}
+
+/// A stateful evaluator of a `RecurrentGraph`.
+///
+/// Each call to `step` advances the network by one time step: all nodes are updated
+/// synchronously from the outputs of the previous time step, so a signal needs one time step per
+/// link to travel through the network. Only the values of input and bias nodes take effect
+/// immediately. The outputs of all nodes are kept between calls and
+/// only cleared by `reset`.
+///
+/// With `set_relaxation_iterations(n)`, each step performs `n` updates with the same inputs,
+/// which lets the network settle before the outputs are read.
+#[derive(Clone, Debug)]
+pub struct RecurrentCppn<N, F = f64>
+where
+    N: CppnNodeType,
+    F: Float,
+{
+    node_types: Vec<N>,
+    // All links, grouped by target node. The incoming links of node `i` are
+    // `links[link_offsets[i]..link_offsets[i + 1]]`.
+    link_offsets: Vec<usize>,
+    links: Vec<CompiledLink<F>>,
+    inputs: Vec<usize>,
+    outputs: Vec<usize>,
+    biases: Vec<usize>,
+    bias_value: F,
+    relaxation_iterations: usize,
+    incoming: Vec<F>,
+    outgoing: Vec<F>,
+    previous: Vec<F>,
+}
+
+impl<N> RecurrentCppn<N>
+where
+    N: CppnNodeType,
+{
+    /// Creates an evaluator for `graph` using `f64` signals.
+    pub fn new(graph: &RecurrentGraph<N>) -> Self {
+        Self::compile(graph)
+    }
+}
+
+impl<N, F> RecurrentCppn<N, F>
+where
+    N: CppnNodeType,
+    F: Float,
+{
+    /// Creates an evaluator for `graph` using signals of type `F`. All node outputs start at
+    /// zero.
+    pub fn compile(graph: &RecurrentGraph<N>) -> Self {
+        let node_count = graph.node_count();
+        let mut incoming_links: Vec<Vec<CompiledLink<F>>> = vec![Vec::new(); node_count];
+        for link in graph.links().iter() {
+            incoming_links[link.target.index()].push(CompiledLink {
+                source: link.source.index(),
+                target: link.target.index(),
+                weight: F::from_f64(link.weight),
+            });
+        }
+
+        let mut link_offsets = Vec::with_capacity(node_count + 1);
+        let mut links = Vec::with_capacity(graph.links().len());
+        link_offsets.push(0);
+        for node_links in incoming_links.iter() {
+            links.extend_from_slice(node_links);
+            link_offsets.push(links.len());
+        }
+
+        let nodes_where = |f: &dyn Fn(&N) -> bool| -> Vec<usize> {
+            (0..node_count).filter(|&i| f(&graph.nodes()[i])).collect()
+        };
+
+        RecurrentCppn {
+            node_types: graph.nodes().to_vec(),
+            link_offsets,
+            links,
+            inputs: nodes_where(&|node| node.is_input_node()),
+            outputs: nodes_where(&|node| node.is_output_node()),
+            biases: nodes_where(&|node| node.is_bias_node()),
+            bias_value: F::one(),
+            relaxation_iterations: 1,
+            incoming: vec![F::zero(); node_count],
+            outgoing: vec![F::zero(); node_count],
+            previous: vec![F::zero(); node_count],
+        }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.node_types.len()
+    }
+
+    pub fn input_count(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn output_count(&self) -> usize {
+        self.outputs.len()
+    }
+
+    pub fn bias_count(&self) -> usize {
+        self.biases.len()
+    }
+
+    /// Sets the value fed into all bias nodes (`1.0` by default).
+    pub fn set_bias_value(&mut self, bias_value: F) {
+        self.bias_value = bias_value;
+    }
+
+    /// Sets the number of updates performed by each `step` (at least 1, which is the default).
+    pub fn set_relaxation_iterations(&mut self, iterations: usize) {
+        assert!(iterations >= 1);
+        self.relaxation_iterations = iterations;
+    }
+
+    pub fn relaxation_iterations(&self) -> usize {
+        self.relaxation_iterations
+    }
+
+    /// Clears the state of the network, i.e. sets the outputs of all nodes to zero.
+    pub fn reset(&mut self) {
+        for value in self.incoming.iter_mut().chain(self.outgoing.iter_mut()) {
+            *value = F::zero();
+        }
+    }
+
+    /// The output of the activation function of each node after the last step.
+    pub fn state(&self) -> &[F] {
+        &self.outgoing
+    }
+
+    /// The sum of all weighted input signals of each node in the last step.
+    pub fn incoming_signals(&self) -> &[F] {
+        &self.incoming
+    }
+
+    /// Advances the network by one time step (or `relaxation_iterations` updates) with the
+    /// given `inputs`.
+    pub fn step(&mut self, inputs: &[&[F]]) {
+        if let Err(err) = self.check_inputs(inputs) {
+            panic!("{}", err);
+        }
+        for _ in 0..self.relaxation_iterations {
+            self.update(inputs);
+        }
+    }
+
+    /// Like `step`, but returns an error instead of panicking if the number of inputs does not
+    /// match, and if any output is not finite. The state is advanced in either case.
+    pub fn try_step(&mut self, inputs: &[&[F]]) -> Result<(), CppnError> {
+        self.check_inputs(inputs)?;
+        if self.outputs.is_empty() {
+            return Err(CppnError::NoOutputs);
+        }
+        for _ in 0..self.relaxation_iterations {
+            self.update(inputs);
+        }
+        match self
+            .outputs
+            .iter()
+            .position(|&node| !self.outgoing[node].is_finite())
+        {
+            Some(nth) => Err(CppnError::NonFiniteOutput { nth }),
+            None => Ok(()),
+        }
+    }
+
+    /// Advances the network by one step and returns all outputs.
+    pub fn calculate(&mut self, inputs: &[&[F]]) -> Vec<F> {
+        self.step(inputs);
+        self.outputs
+            .iter()
+            .map(|&node| self.outgoing[node])
+            .collect()
+    }
+
+    /// Reads the `nth_output` after the last step.
+    pub fn read_output(&self, nth_output: usize) -> Option<F> {
+        self.outputs
+            .get(nth_output)
+            .map(|&node| self.outgoing[node])
+    }
+
+    fn check_inputs(&self, inputs: &[&[F]]) -> Result<(), CppnError> {
+        let actual = inputs.iter().map(|input_list| input_list.len()).sum();
+        if actual != self.input_count() {
+            return Err(CppnError::InputCountMismatch {
+                expected: self.input_count(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// Updates all nodes synchronously from the outputs of the previous update. Input and bias
+    /// nodes are updated first, so that new inputs take effect immediately.
+    fn update(&mut self, inputs: &[&[F]]) {
+        for value in self.incoming.iter_mut() {
+            *value = F::zero();
+        }
+        for (&node, &input) in self
+            .inputs
+            .iter()
+            .zip(inputs.iter().flat_map(|input_list| input_list.iter()))
+        {
+            self.incoming[node] = input;
+        }
+        for &node in self.biases.iter() {
+            self.incoming[node] = self.bias_value;
+        }
+
+        self.previous.copy_from_slice(&self.outgoing);
+        for &node in self.inputs.iter().chain(self.biases.iter()) {
+            self.previous[node] = self.node_types[node].calculate(self.incoming[node]);
+        }
+
+        for node in 0..self.node_types.len() {
+            let mut sum = self.incoming[node];
+            let links = &self.links[self.link_offsets[node]..self.link_offsets[node + 1]];
+            match self.node_types[node].aggregation() {
+                Aggregation::Sum => {
+                    for link in links {
+                        sum += link.weight * self.previous[link.source];
+                    }
+                }
+                _ if links.is_empty() => {}
+                aggregation => {
+                    let previous = &self.previous;
+                    sum += aggregation
+                        .aggregate(links.iter().map(|l| l.weight * previous[l.source]));
+                }
+            }
+            self.incoming[node] = sum;
+            self.outgoing[node] = self.node_types[node].calculate(sum);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RecurrentCppn, RecurrentGraph};
+    use activation_function::GeometricActivationFunction as AF;
+    use acyclic_network::ExternalId;
+    use cppn::{CppnError, CppnGraph, CppnNode};
+
+    #[test]
+    fn test_self_loop_integrates() {
+        let mut g = RecurrentGraph::new();
+        let i1 = g.add_node(CppnNode::input(AF::Linear));
+        let h1 = g.add_node(CppnNode::hidden(AF::Linear));
+        let o1 = g.add_node(CppnNode::output(AF::Linear));
+        g.add_link(i1, h1, 1.0);
+        g.add_link(h1, h1, 1.0);
+        g.add_link(h1, o1, 1.0);
+
+        let mut cppn = RecurrentCppn::new(&g);
+        // the output lags one step behind the hidden node.
+        assert_eq!(vec![0.0], cppn.calculate(&[&[1.0]]));
+        assert_eq!(vec![1.0], cppn.calculate(&[&[1.0]]));
+        assert_eq!(vec![2.0], cppn.calculate(&[&[1.0]]));
+        assert_eq!(vec![3.0], cppn.calculate(&[&[1.0]]));
+        assert_eq!(vec![4.0], cppn.calculate(&[&[0.0]]));
+        assert_eq!(vec![4.0], cppn.calculate(&[&[0.0]]));
+
+        cppn.reset();
+        assert_eq!(&[0.0, 0.0, 0.0], cppn.state());
+        assert_eq!(vec![0.0], cppn.calculate(&[&[0.0]]));
+    }
+
+    #[test]
+    fn test_oscillator() {
+        let mut g = RecurrentGraph::new();
+        let i1 = g.add_node(CppnNode::input(AF::Linear));
+        let h1 = g.add_node(CppnNode::hidden(AF::Linear));
+        let h2 = g.add_node(CppnNode::hidden(AF::Linear));
+        let o1 = g.add_node(CppnNode::output(AF::Linear));
+        g.add_link(i1, h1, 1.0);
+        g.add_link(h1, h2, 1.0);
+        g.add_link(h2, h1, -1.0);
+        g.add_link(h1, o1, 1.0);
+
+        let mut cppn = RecurrentCppn::new(&g);
+        // a single impulse starts an oscillation with a period of 4 steps.
+        let mut outputs = cppn.calculate(&[&[1.0]]);
+        for _ in 0..8 {
+            outputs.extend(cppn.calculate(&[&[0.0]]));
+        }
+        assert_eq!(vec![0.0, 1.0, 0.0, -1.0, 0.0, 1.0, 0.0, -1.0, 0.0], outputs);
+    }
+
+    #[test]
+    fn test_bias_node() {
+        let mut g = RecurrentGraph::new();
+        let b1 = g.add_node(CppnNode::bias(AF::Linear));
+        let h1 = g.add_node(CppnNode::hidden(AF::Linear));
+        let o1 = g.add_node(CppnNode::output(AF::Linear));
+        g.add_link(b1, h1, 2.0);
+        g.add_link(h1, h1, 0.5);
+        g.add_link(h1, o1, 1.0);
+
+        let mut cppn = RecurrentCppn::new(&g);
+        assert_eq!(0, cppn.input_count());
+        assert_eq!(1, cppn.bias_count());
+        cppn.set_relaxation_iterations(50);
+        cppn.step(&[]);
+        // converges to the fixed point h = 2 + 0.5 * h.
+        assert!((cppn.read_output(0).unwrap() - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_relaxation() {
+        let mut acyclic = CppnGraph::new();
+        let i1 = acyclic.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let h1 = acyclic.add_node(CppnNode::hidden(AF::Absolute), ExternalId(2));
+        let o1 = acyclic.add_node(CppnNode::output(AF::Linear), ExternalId(3));
+        acyclic.add_link(i1, h1, 2.0, ExternalId(1));
+        acyclic.add_link(h1, o1, 0.5, ExternalId(2));
+
+        let g = RecurrentGraph::from_graph(&acyclic);
+        assert_eq!(2, g.links().len());
+
+        // a feed-forward network settles after as many updates as it is deep.
+        let mut cppn = RecurrentCppn::new(&g);
+        cppn.set_relaxation_iterations(3);
+        assert_eq!(vec![3.0], cppn.calculate(&[&[-3.0]]));
+        assert_eq!(vec![1.0], cppn.calculate(&[&[1.0]]));
+    }
+
+    #[test]
+    fn test_try_step() {
+        let mut g = RecurrentGraph::new();
+        let i1 = g.add_node(CppnNode::input(AF::Linear));
+        let h1 = g.add_node(CppnNode::hidden(AF::Linear));
+        let o1 = g.add_node(CppnNode::output(AF::Linear));
+        g.add_link(i1, h1, 1.0);
+        g.add_link(h1, h1, 2.0);
+        g.add_link(h1, o1, 1.0);
+
+        let mut cppn: RecurrentCppn<_, f32> = RecurrentCppn::compile(&g);
+        assert_eq!(
+            Err(CppnError::InputCountMismatch {
+                expected: 1,
+                actual: 2,
+            }),
+            cppn.try_step(&[&[1.0, 2.0]])
+        );
+        assert_eq!(Ok(()), cppn.try_step(&[&[1.0]]));
+        let mut result = Ok(());
+        for _ in 0..200 {
+            result = cppn.try_step(&[&[1.0]]);
+            if result.is_err() {
+                break;
+            }
+        }
+        assert_eq!(Err(CppnError::NonFiniteOutput { nth: 0 }), result);
+    }
+}
@@ -0,0 +1,439 @@
+//! A runtime registry of user-defined activation functions.
+//!
+//! Functions are registered by name together with a closure, a formula template and optional
+//! metadata (see `FunctionEntry`). `DynamicActivationFunction` refers to a registered function
+//! and can be used as the activation function of a `CppnNode` like any other. It is serialized
+//! by name, so genomes stay loadable as long as their functions are registered again.
+
+use activation_function::ActivationFunction;
+use expr::Expr;
+use float::Float;
+use interval::Interval;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{self, Debug};
+use std::sync::{Arc, RwLock};
+
+type Function = Box<dyn Fn(f64) -> f64 + Send + Sync>;
+type ExprFunction = Box<dyn Fn(Expr) -> Expr + Send + Sync>;
+
+/// A user-defined activation function, which is registered by name in an
+/// `ActivationRegistry`.
+///
+/// Only `name`, `formula` and the function itself are required. The optional metadata is used
+/// to implement the remaining methods of `ActivationFunction`; without it, the derivative is
+/// approximated numerically, interval bounds are unbounded and the function is exported as a
+/// call of `name` (see `Expr::Call`).
+pub struct FunctionEntry {
+    name: String,
+    formula: String,
+    function: Function,
+    derivative: Option<Function>,
+    expr: Option<ExprFunction>,
+    range: Option<(f64, f64)>,
+    increasing: bool,
+    description: String,
+}
+
+impl FunctionEntry {
+    /// `formula` is a template for `formula_gnuplot`, in which each occurrence of `{x}` is
+    /// replaced by the (parenthesized) argument, e.g. `"tanh({x}) * 0.5"`.
+    pub fn new<S, T, M>(name: S, formula: T, function: M) -> Self
+    where
+        S: Into<String>,
+        T: Into<String>,
+        M: Fn(f64) -> f64 + Send + Sync + 'static,
+    {
+        FunctionEntry {
+            name: name.into(),
+            formula: formula.into(),
+            function: Box::new(function),
+            derivative: None,
+            expr: None,
+            range: None,
+            increasing: false,
+            description: String::new(),
+        }
+    }
+
+    /// Sets the exact derivative of the function.
+    pub fn with_derivative<M>(mut self, derivative: M) -> Self
+    where
+        M: Fn(f64) -> f64 + Send + Sync + 'static,
+    {
+        self.derivative = Some(Box::new(derivative));
+        self
+    }
+
+    /// Sets the symbolic expression of the function, which makes it exportable by the code
+    /// generators.
+    pub fn with_expr<M>(mut self, expr: M) -> Self
+    where
+        M: Fn(Expr) -> Expr + Send + Sync + 'static,
+    {
+        self.expr = Some(Box::new(expr));
+        self
+    }
+
+    /// Declares that all values of the function lie within `[lo, hi]`.
+    pub fn with_range(mut self, lo: f64, hi: f64) -> Self {
+        self.range = Some((lo, hi));
+        self
+    }
+
+    /// Declares that the function is monotonically increasing, which gives tight interval
+    /// bounds.
+    pub fn increasing(mut self) -> Self {
+        self.increasing = true;
+        self
+    }
+
+    pub fn with_description<S: Into<String>>(mut self, description: S) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn formula(&self) -> &str {
+        &self.formula
+    }
+
+    pub fn range(&self) -> Option<(f64, f64)> {
+        self.range
+    }
+
+    pub fn is_increasing(&self) -> bool {
+        self.increasing
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+/// Errors reported by `ActivationRegistry`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RegistryError {
+    /// A function named `name` is already registered.
+    DuplicateName { name: String },
+    /// No function named `name` is registered.
+    UnknownName { name: String },
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RegistryError::DuplicateName { ref name } => {
+                write!(f, "activation function {:?} is already registered", name)
+            }
+            RegistryError::UnknownName { ref name } => {
+                write!(f, "unknown activation function {:?}", name)
+            }
+        }
+    }
+}
+
+impl Error for RegistryError {}
+
+/// A set of user-defined activation functions, addressed by name.
+///
+/// Functions can be registered at runtime, so that new activation functions do not require a
+/// new `ActivationFunction` type. Handles to the registered functions
+/// (`DynamicActivationFunction`) are serialized by name and looked up in the `global` registry
+/// when deserialized, so functions have to be registered there before loading a genome.
+pub struct ActivationRegistry {
+    entries: RwLock<BTreeMap<String, Arc<FunctionEntry>>>,
+}
+
+static GLOBAL: ActivationRegistry = ActivationRegistry::new();
+
+impl ActivationRegistry {
+    pub const fn new() -> Self {
+        ActivationRegistry {
+            entries: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// The registry used to deserialize `DynamicActivationFunction`s.
+    pub fn global() -> &'static ActivationRegistry {
+        &GLOBAL
+    }
+
+    /// Registers `entry` and returns a handle to it. Fails if the name is already taken.
+    pub fn register(
+        &self,
+        entry: FunctionEntry,
+    ) -> Result<DynamicActivationFunction, RegistryError> {
+        let mut entries = self.entries.write().unwrap();
+        if entries.contains_key(&entry.name) {
+            return Err(RegistryError::DuplicateName { name: entry.name });
+        }
+        let entry = Arc::new(entry);
+        entries.insert(entry.name.clone(), entry.clone());
+        Ok(DynamicActivationFunction { entry })
+    }
+
+    /// The handle to the function named `name`.
+    pub fn get(&self, name: &str) -> Result<DynamicActivationFunction, RegistryError> {
+        match self.entries.read().unwrap().get(name) {
+            Some(entry) => Ok(DynamicActivationFunction {
+                entry: entry.clone(),
+            }),
+            None => Err(RegistryError::UnknownName {
+                name: name.to_string(),
+            }),
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.read().unwrap().contains_key(name)
+    }
+
+    /// The names of all registered functions, in sorted order.
+    pub fn names(&self) -> Vec<String> {
+        self.entries.read().unwrap().keys().cloned().collect()
+    }
+}
+
+impl Default for ActivationRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A handle to a function registered in an `ActivationRegistry`.
+///
+/// Handles are equal if they refer to the same registered entry. Entries of the same name in
+/// different registries are distinct functions, so their handles are not equal.
+#[derive(Clone)]
+pub struct DynamicActivationFunction {
+    entry: Arc<FunctionEntry>,
+}
+
+impl DynamicActivationFunction {
+    /// The handle to the function named `name` in the `global` registry.
+    pub fn named(name: &str) -> Result<Self, RegistryError> {
+        ActivationRegistry::global().get(name)
+    }
+
+    pub fn entry(&self) -> &FunctionEntry {
+        &self.entry
+    }
+}
+
+impl PartialEq for DynamicActivationFunction {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.entry, &other.entry)
+    }
+}
+
+impl Eq for DynamicActivationFunction {}
+
+impl Debug for DynamicActivationFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("DynamicActivationFunction")
+            .field(&self.entry.name)
+            .finish()
+    }
+}
+
+impl ActivationFunction for DynamicActivationFunction {
+    fn formula_gnuplot(&self, x: String) -> String {
+        self.entry.formula.replace("{x}", &format!("({})", x))
+    }
+
+    fn name(&self) -> String {
+        self.entry.name.clone()
+    }
+
+    fn calculate<F: Float>(&self, x: F) -> F {
+        F::from_f64((self.entry.function)(x.to_f64()))
+    }
+
+    fn derivative<F: Float>(&self, x: F) -> F {
+        match self.entry.derivative {
+            Some(ref derivative) => F::from_f64(derivative(x.to_f64())),
+            None => {
+                let x = x.to_f64();
+                let h = f64::EPSILON.cbrt() * (1.0 + x.abs());
+                let f = &self.entry.function;
+                F::from_f64((f(x + h) - f(x - h)) / (2.0 * h))
+            }
+        }
+    }
+
+    fn calculate_interval<F: Float>(&self, x: Interval<F>) -> Interval<F> {
+        if self.entry.increasing {
+            x.map_increasing(|x| self.calculate(x))
+        } else if let Some((lo, hi)) = self.entry.range {
+            Interval::new(F::from_f64(lo), F::from_f64(hi))
+        } else {
+            Interval::entire()
+        }
+    }
+
+    fn expr(&self, x: Expr) -> Expr {
+        match self.entry.expr {
+            Some(ref expr) => expr(x),
+            None => Expr::Call(self.entry.name.clone(), Box::new(x)),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for DynamicActivationFunction {
+    fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.entry.name)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for DynamicActivationFunction {
+    fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        DynamicActivationFunction::named(&name).map_err(::serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ActivationRegistry, DynamicActivationFunction, FunctionEntry, RegistryError};
+    use activation_function::ActivationFunction;
+    use acyclic_network::ExternalId;
+    use cppn::{Cppn, CppnGraph, CppnNode};
+    use expr::Expr;
+    use interval::Interval;
+
+    fn softsign() -> FunctionEntry {
+        FunctionEntry::new("softsign", "{x} / (1.0 + abs({x}))", |x: f64| {
+            x / (1.0 + x.abs())
+        })
+        .with_derivative(|x: f64| 1.0 / (1.0 + x.abs()).powi(2))
+        .with_expr(|x: Expr| x.clone() / (Expr::Const(1.0) + x.abs()))
+        .with_range(-1.0, 1.0)
+        .increasing()
+        .with_description("x / (1 + |x|)")
+    }
+
+    #[test]
+    fn test_register() {
+        let registry = ActivationRegistry::new();
+        let f = registry.register(softsign()).unwrap();
+        assert_eq!(
+            Err(RegistryError::DuplicateName {
+                name: "softsign".to_string()
+            }),
+            registry.register(softsign())
+        );
+        assert_eq!(Ok(f.clone()), registry.get("softsign"));
+        assert!(registry.get("softplus").is_err());
+        registry
+            .register(FunctionEntry::new("bump", "exp(-{x}**2)", |x: f64| {
+                (-x * x).exp()
+            }))
+            .unwrap();
+        assert_eq!(vec!["bump", "softsign"], registry.names());
+        assert!(registry.contains("bump"));
+        assert_eq!("x / (1 + |x|)", f.entry().description());
+        assert_eq!(Some((-1.0, 1.0)), f.entry().range());
+    }
+
+    #[test]
+    fn test_activation_function() {
+        let registry = ActivationRegistry::new();
+        let f = registry.register(softsign()).unwrap();
+        let bump = registry
+            .register(FunctionEntry::new("bump", "exp(-{x}**2)", |x: f64| {
+                (-x * x).exp()
+            }))
+            .unwrap();
+
+        assert_eq!("softsign", f.name());
+        assert_eq!(0.5, f.calculate(1.0));
+        assert_eq!(0.5f32, f.calculate(1.0f32));
+        assert_eq!(0.25, f.derivative(1.0));
+        assert!((bump.derivative(0.5) - (-(-0.25f64).exp())).abs() < 1e-8);
+        assert_eq!(
+            "exp(-(a + b)**2)",
+            bump.formula_gnuplot("a + b".to_string())
+        );
+
+        let x = Interval::new(-1.0, 3.0);
+        assert_eq!(Interval::new(-0.5, 0.75), f.calculate_interval(x));
+        assert_eq!(Interval::entire(), bump.calculate_interval(x));
+
+        let expr = f.expr(Expr::Var(0));
+        assert_eq!(f.calculate(-2.0), expr.eval(&[], &[-2.0]));
+        assert_eq!(
+            Expr::Call("bump".to_string(), Box::new(Expr::Var(0))),
+            bump.expr(Expr::Var(0))
+        );
+    }
+
+    #[test]
+    fn test_equality_across_registries() {
+        let registry = ActivationRegistry::new();
+        let other = ActivationRegistry::new();
+        let f = registry.register(softsign()).unwrap();
+        let g = other
+            .register(FunctionEntry::new("softsign", "{x}", |x: f64| x))
+            .unwrap();
+        assert_eq!(f, f.clone());
+        assert_eq!(f, registry.get("softsign").unwrap());
+        assert!(f != g);
+        assert!(CppnNode::hidden(f) != CppnNode::hidden(g));
+    }
+
+    #[test]
+    fn test_global_registry() {
+        let f = ActivationRegistry::global()
+            .register(FunctionEntry::new("test-cube", "{x}**3", |x: f64| {
+                x * x * x
+            }))
+            .unwrap();
+        assert_eq!(Ok(f), DynamicActivationFunction::named("test-cube"));
+        assert!(DynamicActivationFunction::named("test-missing").is_err());
+    }
+
+    #[test]
+    fn test_in_cppn() {
+        let registry = ActivationRegistry::new();
+        let linear = registry
+            .register(FunctionEntry::new("linear", "{x}", |x: f64| x).increasing())
+            .unwrap();
+        let f = registry.register(softsign()).unwrap();
+
+        let mut g = CppnGraph::new();
+        let i = g.add_node(CppnNode::input(linear), ExternalId(1));
+        let o = g.add_node(CppnNode::output(f.clone()), ExternalId(2));
+        g.add_link(i, o, 2.0, ExternalId(1));
+
+        let mut cppn = Cppn::new(&g);
+        assert_eq!(vec![f.calculate(1.0)], cppn.calculate(&[&[0.5]]));
+        let bounds = cppn.calculate_interval(&[Interval::new(0.0, 0.5)]);
+        assert!(bounds[0].contains(0.0) && bounds[0].contains(0.5));
+        assert!(bounds[0].width() < 0.5 + 1e-12);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize_by_name() {
+        use serde::de::value::{Error, StrDeserializer};
+        use serde::de::IntoDeserializer;
+        use serde::Deserialize;
+
+        let f = ActivationRegistry::global()
+            .register(FunctionEntry::new("test-square", "{x}**2", |x: f64| x * x))
+            .unwrap();
+        let deserializer: StrDeserializer<Error> = "test-square".into_deserializer();
+        assert_eq!(
+            f,
+            DynamicActivationFunction::deserialize(deserializer).unwrap()
+        );
+        let deserializer: StrDeserializer<Error> = "test-unknown".into_deserializer();
+        assert!(DynamicActivationFunction::deserialize(deserializer).is_err());
+    }
+}
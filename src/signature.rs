@@ -0,0 +1,354 @@
+use cppn::{CppnError, CppnGraph, CppnNodeType};
+use std::fmt::Debug;
+
+/// Describes the inputs and outputs of a CPPN genome by attaching labels (e.g. "x1", "y1",
+/// "bias", "weight", "leo") to the external ids of its input and output nodes.
+///
+/// A signature is independent of the order in which the nodes of a graph are stored. Binding
+/// it to a concrete graph (`bind`) resolves the labels to input and output slots, which is
+/// what `Cppn::set_signature` does to enable evaluation by name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CppnSignature<EXTID> {
+    inputs: Vec<(String, EXTID)>,
+    outputs: Vec<(String, EXTID)>,
+}
+
+impl<EXTID> Default for CppnSignature<EXTID> {
+    fn default() -> Self {
+        CppnSignature {
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+}
+
+impl<EXTID> CppnSignature<EXTID>
+where
+    EXTID: Copy + Debug + Send + Sized + Ord,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Labels the input node with the external id `node`.
+    pub fn add_input(&mut self, label: &str, node: EXTID) {
+        self.inputs.push((label.to_string(), node));
+    }
+
+    /// Labels the output node with the external id `node`.
+    pub fn add_output(&mut self, label: &str, node: EXTID) {
+        self.outputs.push((label.to_string(), node));
+    }
+
+    pub fn inputs(&self) -> &[(String, EXTID)] {
+        &self.inputs
+    }
+
+    pub fn outputs(&self) -> &[(String, EXTID)] {
+        &self.outputs
+    }
+
+    /// Resolves the labels of the signature against `graph`.
+    ///
+    /// Fails if a label or node is labeled twice, if a labeled node does not exist or is not an
+    /// input (output) node, or if an input node of `graph` is not labeled. Not every output has
+    /// to be labeled.
+    ///
+    /// Bias nodes cannot be labeled, as they are all driven by the same bias value (see
+    /// `Cppn::set_bias_value`) rather than by an input slot. Labeling one fails with
+    /// `CppnError::UnknownNode`.
+    pub fn bind<N, L>(&self, graph: &CppnGraph<N, L, EXTID>) -> Result<BoundSignature, CppnError>
+    where
+        N: CppnNodeType,
+        L: Copy + Debug + Send + Sized + Into<f64>,
+    {
+        // The slots of the nodes are determined by the order of the nodes, as in
+        // `CompiledCppn::inputs` and `CompiledCppn::outputs`.
+        let mut input_nodes = Vec::new();
+        let mut output_nodes = Vec::new();
+        for node in graph.nodes().iter() {
+            if node.node_type().is_input_node() {
+                input_nodes.push(node.external_node_id());
+            }
+            if node.node_type().is_output_node() {
+                output_nodes.push(node.external_node_id());
+            }
+        }
+
+        let input_slots = resolve(&self.inputs, &input_nodes)?;
+        let output_slots = resolve(&self.outputs, &output_nodes)?;
+
+        if let Some(nth) = (0..input_nodes.len()).find(|slot| !input_slots.contains(slot)) {
+            return Err(CppnError::UnlabeledInput { nth });
+        }
+
+        Ok(BoundSignature {
+            inputs: labeled_slots(&self.inputs, input_slots),
+            outputs: labeled_slots(&self.outputs, output_slots),
+            input_count: input_nodes.len(),
+            output_count: output_nodes.len(),
+        })
+    }
+}
+
+/// Returns the slot (position within `nodes`) of each labeled node.
+fn resolve<EXTID>(labels: &[(String, EXTID)], nodes: &[EXTID]) -> Result<Vec<usize>, CppnError>
+where
+    EXTID: Copy + Ord,
+{
+    let mut slots = Vec::with_capacity(labels.len());
+    for (i, (label, node)) in labels.iter().enumerate() {
+        if labels[..i].iter().any(|(other, _)| other == label) {
+            return Err(CppnError::DuplicateLabel {
+                label: label.clone(),
+            });
+        }
+        match nodes.iter().position(|id| id == node) {
+            Some(slot) if slots.contains(&slot) => {
+                return Err(CppnError::NodeLabeledTwice {
+                    label: label.clone(),
+                })
+            }
+            Some(slot) => slots.push(slot),
+            None => {
+                return Err(CppnError::UnknownNode {
+                    label: label.clone(),
+                })
+            }
+        }
+    }
+    Ok(slots)
+}
+
+fn labeled_slots<EXTID>(labels: &[(String, EXTID)], slots: Vec<usize>) -> Vec<(String, usize)> {
+    labels
+        .iter()
+        .map(|(label, _)| label.clone())
+        .zip(slots)
+        .collect()
+}
+
+/// A `CppnSignature` resolved against a particular graph, mapping labels to the positions of
+/// the input values and outputs of `Cppn::calculate`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoundSignature {
+    inputs: Vec<(String, usize)>,
+    outputs: Vec<(String, usize)>,
+    input_count: usize,
+    output_count: usize,
+}
+
+impl BoundSignature {
+    /// The number of inputs of the graph the signature was bound to.
+    pub fn input_count(&self) -> usize {
+        self.input_count
+    }
+
+    /// The number of outputs of the graph the signature was bound to.
+    pub fn output_count(&self) -> usize {
+        self.output_count
+    }
+
+    /// The input slot of the input labeled `label`.
+    pub fn input_slot(&self, label: &str) -> Option<usize> {
+        find_slot(&self.inputs, label)
+    }
+
+    /// The index (as in `Cppn::read_output`) of the output labeled `label`.
+    pub fn output_slot(&self, label: &str) -> Option<usize> {
+        find_slot(&self.outputs, label)
+    }
+
+    /// The labels and slots of all inputs, in the order of the signature.
+    pub fn inputs(&self) -> &[(String, usize)] {
+        &self.inputs
+    }
+
+    /// The labels and slots of all labeled outputs, in the order of the signature.
+    pub fn outputs(&self) -> &[(String, usize)] {
+        &self.outputs
+    }
+
+    /// Arranges labeled input values in slot order, as expected by `Cppn::calculate`.
+    pub fn input_values<F: Copy + Default>(
+        &self,
+        inputs: &[(&str, F)],
+    ) -> Result<Vec<F>, CppnError> {
+        let mut values = vec![F::default(); self.inputs.len()];
+        let mut assigned = vec![false; self.inputs.len()];
+        for &(label, value) in inputs.iter() {
+            let slot = self
+                .input_slot(label)
+                .ok_or_else(|| CppnError::UnknownLabel {
+                    label: label.to_string(),
+                })?;
+            if assigned[slot] {
+                return Err(CppnError::DuplicateLabel {
+                    label: label.to_string(),
+                });
+            }
+            assigned[slot] = true;
+            values[slot] = value;
+        }
+        match assigned.iter().position(|&assigned| !assigned) {
+            Some(slot) => {
+                let label = &self.inputs.iter().find(|&&(_, s)| s == slot).unwrap().0;
+                Err(CppnError::MissingInput {
+                    label: label.clone(),
+                })
+            }
+            None => Ok(values),
+        }
+    }
+}
+
+fn find_slot(labels: &[(String, usize)], label: &str) -> Option<usize> {
+    labels
+        .iter()
+        .find(|(l, _)| l == label)
+        .map(|&(_, slot)| slot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CppnSignature;
+    use activation_function::GeometricActivationFunction as AF;
+    use acyclic_network::ExternalId;
+    use cppn::{Cppn, CppnError, CppnGraph, CppnNode};
+
+    type Graph = CppnGraph<CppnNode<AF>, f64, ExternalId>;
+
+    // Builds the same network with the nodes stored in a different order.
+    fn graph(reversed: bool) -> Graph {
+        let mut g = CppnGraph::new();
+        let mut ids = [1, 2, 3, 4];
+        if reversed {
+            ids.reverse();
+        }
+        let mut nodes = Vec::new();
+        for &id in ids.iter() {
+            let node = match id {
+                1 | 2 => CppnNode::input(AF::Linear),
+                _ => CppnNode::output(AF::Linear),
+            };
+            nodes.push((id, g.add_node(node, ExternalId(id))));
+        }
+        let index = |id| nodes.iter().find(|&&(i, _)| i == id).unwrap().1;
+        let (x, y, sum, diff) = (index(1), index(2), index(3), index(4));
+        g.add_link(x, sum, 1.0, ExternalId(1));
+        g.add_link(y, sum, 1.0, ExternalId(2));
+        g.add_link(x, diff, 1.0, ExternalId(3));
+        g.add_link(y, diff, -1.0, ExternalId(4));
+        g
+    }
+
+    fn signature() -> CppnSignature<ExternalId> {
+        let mut signature = CppnSignature::new();
+        signature.add_input("x", ExternalId(1));
+        signature.add_input("y", ExternalId(2));
+        signature.add_output("diff", ExternalId(4));
+        signature.add_output("sum", ExternalId(3));
+        signature
+    }
+
+    #[test]
+    fn test_calculate_named_is_independent_of_node_order() {
+        for &reversed in &[false, true] {
+            let g = graph(reversed);
+            let mut cppn = Cppn::new(&g);
+            assert_eq!(
+                Err(CppnError::NoSignature),
+                cppn.calculate_named(&[("x", 1.0)])
+            );
+            cppn.set_signature(&signature()).unwrap();
+
+            assert_eq!(
+                Ok(vec![-2.0, 4.0]),
+                cppn.calculate_named(&[("y", 3.0), ("x", 1.0)])
+            );
+            assert_eq!(Some(4.0), cppn.read_output_named("sum"));
+            assert_eq!(Some(-2.0), cppn.read_output_named("diff"));
+            assert_eq!(None, cppn.read_output_named("x"));
+        }
+    }
+
+    #[test]
+    fn test_calculate_named_errors() {
+        let g = graph(false);
+        let mut cppn = Cppn::new(&g);
+        cppn.set_signature(&signature()).unwrap();
+
+        assert_eq!(
+            Err(CppnError::MissingInput { label: "y".into() }),
+            cppn.calculate_named(&[("x", 1.0)])
+        );
+        assert_eq!(
+            Err(CppnError::UnknownLabel { label: "z".into() }),
+            cppn.calculate_named(&[("x", 1.0), ("z", 1.0)])
+        );
+        assert_eq!(
+            Err(CppnError::DuplicateLabel { label: "x".into() }),
+            cppn.calculate_named(&[("x", 1.0), ("x", 1.0)])
+        );
+    }
+
+    #[test]
+    fn test_bind_errors() {
+        let g = graph(false);
+
+        let mut signature = CppnSignature::new();
+        signature.add_input("x", ExternalId(1));
+        assert_eq!(
+            Err(CppnError::UnlabeledInput { nth: 1 }),
+            signature.bind(&g)
+        );
+
+        signature.add_input("y", ExternalId(3));
+        assert_eq!(
+            Err(CppnError::UnknownNode { label: "y".into() }),
+            signature.bind(&g)
+        );
+
+        let mut signature = CppnSignature::new();
+        signature.add_input("x", ExternalId(1));
+        signature.add_input("y", ExternalId(1));
+        assert_eq!(
+            Err(CppnError::NodeLabeledTwice { label: "y".into() }),
+            signature.bind(&g)
+        );
+
+        let mut signature = CppnSignature::new();
+        signature.add_input("x", ExternalId(1));
+        signature.add_input("x", ExternalId(2));
+        assert_eq!(
+            Err(CppnError::DuplicateLabel { label: "x".into() }),
+            signature.bind(&g)
+        );
+    }
+
+    #[test]
+    fn test_bind_rejects_bias_nodes() {
+        let mut g = graph(false);
+        g.add_node(CppnNode::bias(AF::Linear), ExternalId(5));
+        assert!(signature().bind(&g).is_ok());
+
+        let mut labeled_input = signature();
+        labeled_input.add_input("bias", ExternalId(5));
+        assert_eq!(
+            Err(CppnError::UnknownNode {
+                label: "bias".into()
+            }),
+            labeled_input.bind(&g)
+        );
+
+        let mut labeled_output = signature();
+        labeled_output.add_output("bias", ExternalId(5));
+        assert_eq!(
+            Err(CppnError::UnknownNode {
+                label: "bias".into()
+            }),
+            labeled_output.bind(&g)
+        );
+    }
+}
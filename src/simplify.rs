@@ -0,0 +1,397 @@
+use aggregation::Aggregation;
+use compiled::CompiledCppn;
+use cppn::{CppnGraph, CppnNodeIndex, CppnNodeType};
+use expr::Expr;
+use std::fmt::Debug;
+
+struct WorkLink<EXTID> {
+    source: usize,
+    target: usize,
+    weight: f64,
+    id: EXTID,
+}
+
+/// Returns a smaller graph which computes the same outputs as `graph`, up to rounding errors.
+///
+/// * Disabled links and links with weight zero are dropped.
+/// * Nodes which do not depend on any input are folded into constants: their contribution to
+///   each other node is replaced by a single link from a bias node (or, if there is none, from a
+///   hidden node without incoming links). Assumes the default bias value of 1.0.
+/// * Hidden nodes whose activation function is the identity (like `Linear`) are removed if they
+///   have only one incoming or only one outgoing link, by linking their sources directly to
+///   their targets. This collapses chains of such nodes into a single weighted link.
+/// * Parallel links between the same two nodes are merged.
+/// * Hidden nodes with no path to any output are removed.
+///
+/// Links into nodes with an aggregation other than `Aggregation::Sum` are only rewritten if
+/// each weighted signal the node aggregates stays the same, so constants are not folded into
+/// such nodes and their parallel links and links with weight zero are kept.
+///
+/// Input, bias and output nodes, their order and the external ids of all remaining nodes are
+/// kept, so the simplified graph can be evaluated with the same inputs. New links take the
+/// external id of one of the links they replace.
+pub fn simplify<N, L, EXTID>(graph: &CppnGraph<N, L, EXTID>) -> CppnGraph<N, L, EXTID>
+where
+    N: CppnNodeType,
+    L: Copy + Debug + Send + Sized + Into<f64> + From<f64>,
+    EXTID: Copy + Debug + Send + Sized + Ord,
+{
+    let node_count = graph.nodes().len();
+    let node_type = |node: usize| graph.node(CppnNodeIndex::new(node)).node_type();
+    let is_hidden = |node: usize| {
+        let node_type = node_type(node);
+        !(node_type.is_input_node() || node_type.is_output_node() || node_type.is_bias_node())
+    };
+    let sums = |node: usize| node_type(node).aggregation() == Aggregation::Sum;
+
+    let mut links = Vec::new();
+    graph.each_node_with_index(|_, source| {
+        for (_, link) in graph.link_iter_for_node(source) {
+            let weight = link.weight().into();
+            let target = link.target_node_index().index();
+            if link.is_active() && (weight != 0.0 || !sums(target)) {
+                links.push(WorkLink {
+                    source: source.index(),
+                    target,
+                    weight,
+                    id: link.external_link_id(),
+                });
+            }
+        }
+    });
+
+    let compiled = CompiledCppn::new(graph);
+    fold_constants(&compiled, &mut links, &is_hidden, &sums);
+
+    // Remove identity nodes.
+    let identity = |node: usize| node_type(node).expr(Expr::Input(0)) == Expr::Input(0);
+    for node in (0..node_count).filter(|&node| is_hidden(node) && identity(node)) {
+        let (incoming, rest): (Vec<_>, Vec<_>) =
+            links.into_iter().partition(|link| link.target == node);
+        let (outgoing, mut rest): (Vec<_>, Vec<_>) =
+            rest.into_iter().partition(|link| link.source == node);
+        // a node which does not sum its inputs is only the identity for a single input, and a
+        // target which does not sum its inputs has to receive the same weighted signal from a
+        // single new link.
+        let collapsible = (incoming.len() <= 1 || outgoing.len() <= 1)
+            && (sums(node) || incoming.len() == 1)
+            && outgoing.iter().all(|b| {
+                sums(b.target)
+                    || (incoming.len() == 1
+                        && !rest
+                            .iter()
+                            .any(|l| l.source == incoming[0].source && l.target == b.target))
+            });
+        if collapsible {
+            for a in incoming.iter() {
+                for b in outgoing.iter() {
+                    rest.push(WorkLink {
+                        source: a.source,
+                        target: b.target,
+                        weight: a.weight * b.weight,
+                        // unique, as either `a` or `b` is the only link of its kind.
+                        id: if outgoing.len() == 1 { a.id } else { b.id },
+                    });
+                }
+            }
+        } else {
+            rest.extend(incoming);
+            rest.extend(outgoing);
+        }
+        links = rest;
+    }
+
+    let links = merge_parallel_links(links, &sums);
+
+    // Nodes with a path to an output.
+    let mut needed = vec![false; node_count];
+    let mut stack: Vec<usize> = (0..node_count).filter(|&node| !is_hidden(node)).collect();
+    while let Some(node) = stack.pop() {
+        if needed[node] {
+            continue;
+        }
+        needed[node] = true;
+        stack.extend(links.iter().filter(|l| l.target == node).map(|l| l.source));
+    }
+
+    let mut simplified = CppnGraph::new();
+    let mut new_index = vec![None; node_count];
+    for (node, index) in new_index.iter_mut().enumerate() {
+        if needed[node] {
+            let original = graph.node(CppnNodeIndex::new(node));
+            *index = Some(
+                simplified.add_node(original.node_type().clone(), original.external_node_id()),
+            );
+        }
+    }
+    for link in links.iter() {
+        if let (Some(source), Some(target)) = (new_index[link.source], new_index[link.target]) {
+            simplified.add_link(source, target, L::from(link.weight), link.id);
+        }
+    }
+    simplified
+}
+
+/// Replaces the links from nodes which do not depend on any input by a single link from a
+/// constant node.
+fn fold_constants<N, EXTID, H, S>(
+    compiled: &CompiledCppn<N>,
+    links: &mut Vec<WorkLink<EXTID>>,
+    is_hidden: &H,
+    sums: &S,
+) where
+    N: CppnNodeType,
+    EXTID: Copy,
+    H: Fn(usize) -> bool,
+    S: Fn(usize) -> bool,
+{
+    let node_count = compiled.node_count();
+    let mut incoming = vec![0.0; node_count];
+    let mut outgoing = vec![0.0; node_count];
+    let zeros = vec![0.0; compiled.input_count()];
+    compiled.process(&[&zeros], &mut incoming, &mut outgoing);
+
+    let mut constant = vec![false; node_count];
+    for &node in compiled.order().iter() {
+        constant[node] = !compiled.inputs().contains(&node)
+            && links
+                .iter()
+                .filter(|link| link.target == node)
+                .all(|link| constant[link.source]);
+    }
+
+    // The node the constants are taken from.
+    let has_incoming = |node: usize| links.iter().any(|link| link.target == node);
+    let unit = compiled.biases().first().cloned().or_else(|| {
+        (0..node_count)
+            .find(|&node| is_hidden(node) && !has_incoming(node) && outgoing[node] != 0.0)
+    });
+    let unit = match unit {
+        Some(unit) => unit,
+        None => return,
+    };
+
+    for target in 0..node_count {
+        if (constant[target] && !compiled.outputs().contains(&target)) || !sums(target) {
+            // only needed if it is the unit, or the constants are aggregated one by one.
+            continue;
+        }
+        let mut sum = 0.0;
+        let mut id = None;
+        links.retain(|link| {
+            if link.target == target && constant[link.source] {
+                sum += link.weight * outgoing[link.source];
+                id = id.or(Some(link.id));
+                false
+            } else {
+                true
+            }
+        });
+        if let Some(id) = id {
+            links.push(WorkLink {
+                source: unit,
+                target,
+                weight: sum / outgoing[unit],
+                id,
+            });
+        }
+    }
+}
+
+/// Merges links with the same source and target, and drops links whose weights cancel out,
+/// if the target sums its inputs.
+fn merge_parallel_links<EXTID, S>(links: Vec<WorkLink<EXTID>>, sums: &S) -> Vec<WorkLink<EXTID>>
+where
+    S: Fn(usize) -> bool,
+{
+    let mut merged: Vec<WorkLink<EXTID>> = Vec::with_capacity(links.len());
+    for link in links {
+        match merged.iter_mut().find(|other| {
+            sums(link.target) && other.source == link.source && other.target == link.target
+        }) {
+            Some(other) => other.weight += link.weight,
+            None => merged.push(link),
+        }
+    }
+    merged.retain(|link| link.weight != 0.0 || !sums(link.target));
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::simplify;
+    use activation_function::GeometricActivationFunction as AF;
+    use acyclic_network::ExternalId;
+    use aggregation::Aggregation;
+    use cppn::{Cppn, CppnGraph, CppnNode};
+
+    type Graph = CppnGraph<CppnNode<AF>, f64, ExternalId>;
+
+    fn link_count(g: &Graph) -> usize {
+        let mut count = 0;
+        g.each_link_ref(|_| count += 1);
+        count
+    }
+
+    fn assert_equivalent(g: &Graph, simplified: &Graph) {
+        let mut cppn = Cppn::new(g);
+        let mut simplified = Cppn::new(simplified);
+        for &(x, y) in &[(0.0, 0.0), (0.3, -0.2), (-0.8, 0.5), (1.0, 1.0)] {
+            let expected = cppn.calculate(&[&[x, y]]);
+            let actual = simplified.calculate(&[&[x, y]]);
+            for (a, b) in expected.iter().zip(actual.iter()) {
+                assert!((a - b).abs() < 1e-12, "{} != {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_remove_dead_nodes_and_links() {
+        let mut g = Graph::new();
+        let x = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let y = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let h1 = g.add_node(CppnNode::hidden(AF::Sine), ExternalId(3));
+        let dead = g.add_node(CppnNode::hidden(AF::Gaussian), ExternalId(4));
+        let o = g.add_node(CppnNode::output(AF::Absolute), ExternalId(5));
+        g.add_link(x, h1, 0.5, ExternalId(1));
+        g.add_link(y, h1, 0.0, ExternalId(2));
+        g.add_link(h1, o, 2.0, ExternalId(3));
+        g.add_link(x, dead, 1.0, ExternalId(4));
+        let disabled = g.add_link(y, o, 1.0, ExternalId(5));
+        g.disable_link_index(disabled);
+
+        let simplified = simplify(&g);
+        assert_eq!(4, simplified.node_count());
+        assert_eq!(2, link_count(&simplified));
+        let ids: Vec<_> = simplified
+            .nodes()
+            .iter()
+            .map(|node| node.external_node_id())
+            .collect();
+        assert_eq!(
+            vec![ExternalId(1), ExternalId(2), ExternalId(3), ExternalId(5)],
+            ids
+        );
+        assert_equivalent(&g, &simplified);
+    }
+
+    #[test]
+    fn test_fold_constants() {
+        let mut g = Graph::new();
+        let x = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let y = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let b = g.add_node(CppnNode::bias(AF::Linear), ExternalId(3));
+        let h1 = g.add_node(CppnNode::hidden(AF::Gaussian), ExternalId(4));
+        let h2 = g.add_node(CppnNode::hidden(AF::Sine), ExternalId(5));
+        let h3 = g.add_node(CppnNode::hidden(AF::BipolarSigmoid), ExternalId(6));
+        let o1 = g.add_node(CppnNode::output(AF::Linear), ExternalId(7));
+        let o2 = g.add_node(CppnNode::output(AF::Cosine), ExternalId(8));
+        g.add_link(b, h1, 0.3, ExternalId(1));
+        g.add_link(h1, h2, 0.7, ExternalId(2));
+        g.add_link(b, h2, 0.2, ExternalId(3));
+        g.add_link(h2, h3, -1.5, ExternalId(4));
+        g.add_link(x, h3, 1.0, ExternalId(5));
+        g.add_link(h1, h3, 0.5, ExternalId(6));
+        g.add_link(h3, o1, 1.0, ExternalId(7));
+        g.add_link(b, o1, 0.25, ExternalId(8));
+        g.add_link(h1, o2, 1.0, ExternalId(9));
+        g.add_link(y, o1, 1.0, ExternalId(10));
+
+        let simplified = simplify(&g);
+        // h1 and h2 are folded into the bias node. o2 is constant.
+        assert_eq!(6, simplified.node_count());
+        // b -> h3, x -> h3, h3 -> o1, b -> o1, b -> o2, y -> o1
+        assert_eq!(6, link_count(&simplified));
+        assert_equivalent(&g, &simplified);
+    }
+
+    #[test]
+    fn test_fold_constants_without_bias_node() {
+        let mut g = Graph::new();
+        let x = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let y = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let zero = g.add_node(CppnNode::hidden(AF::Sine), ExternalId(3));
+        let one = g.add_node(CppnNode::hidden(AF::Constant1), ExternalId(4));
+        let h = g.add_node(CppnNode::hidden(AF::Gaussian), ExternalId(5));
+        let o = g.add_node(CppnNode::output(AF::Linear), ExternalId(6));
+        g.add_link(zero, h, 1.0, ExternalId(1));
+        g.add_link(one, h, 0.5, ExternalId(2));
+        g.add_link(h, o, 3.0, ExternalId(3));
+        g.add_link(x, o, 1.0, ExternalId(4));
+        g.add_link(y, o, 1.0, ExternalId(5));
+
+        let simplified = simplify(&g);
+        // `one` is kept as the constant node.
+        assert_eq!(4, simplified.node_count());
+        assert_eq!(3, link_count(&simplified));
+        assert_equivalent(&g, &simplified);
+    }
+
+    #[test]
+    fn test_collapse_linear_chains() {
+        let mut g = Graph::new();
+        let x = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let y = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let l1 = g.add_node(CppnNode::hidden(AF::Linear), ExternalId(3));
+        let l2 = g.add_node(CppnNode::hidden(AF::Linear), ExternalId(4));
+        let l3 = g.add_node(CppnNode::hidden(AF::Linear), ExternalId(5));
+        let s = g.add_node(CppnNode::hidden(AF::Sine), ExternalId(6));
+        let o = g.add_node(CppnNode::output(AF::BipolarGaussian), ExternalId(7));
+        g.add_link(x, l1, 2.0, ExternalId(1));
+        g.add_link(l1, l2, 0.5, ExternalId(2));
+        g.add_link(l2, s, 3.0, ExternalId(3));
+        g.add_link(s, o, 1.0, ExternalId(4));
+        // l3 has several inputs and outputs, but only one of them.
+        g.add_link(x, l3, 1.0, ExternalId(5));
+        g.add_link(y, l3, -1.0, ExternalId(6));
+        g.add_link(l3, o, 0.5, ExternalId(7));
+        // parallel to x -> l1 -> l2 -> s after collapsing.
+        g.add_link(x, s, 1.0, ExternalId(8));
+
+        let simplified = simplify(&g);
+        // x, y, s, o
+        assert_eq!(4, simplified.node_count());
+        // x -> s, s -> o, x -> o, y -> o
+        assert_eq!(4, link_count(&simplified));
+        let mut weights = Vec::new();
+        simplified.each_link_ref(|link| weights.push(link.link().weight()));
+        weights.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(vec![-0.5, 0.5, 1.0, 4.0], weights);
+        assert_equivalent(&g, &simplified);
+    }
+
+    #[test]
+    fn test_keep_aggregated_signals() {
+        let mut g = Graph::new();
+        let x = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let y = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let b = g.add_node(CppnNode::bias(AF::Linear), ExternalId(3));
+        let c = g.add_node(CppnNode::hidden(AF::Linear), ExternalId(4));
+        let l = g.add_node(CppnNode::hidden(AF::Linear), ExternalId(5));
+        let o1 = g.add_node(
+            CppnNode::output(AF::Linear).with_aggregation(Aggregation::Product),
+            ExternalId(6),
+        );
+        let o2 = g.add_node(
+            CppnNode::output(AF::Linear).with_aggregation(Aggregation::Max),
+            ExternalId(7),
+        );
+        g.add_link(b, c, 2.0, ExternalId(1));
+        g.add_link(c, o1, 1.0, ExternalId(2));
+        g.add_link(x, l, 1.0, ExternalId(3));
+        g.add_link(l, o1, 3.0, ExternalId(4));
+        // parallel to x -> l -> o1 after collapsing, so l is kept.
+        g.add_link(x, o1, 0.5, ExternalId(5));
+        g.add_link(y, o1, 0.5, ExternalId(6));
+        // a zero signal still takes part in the maximum.
+        g.add_link(y, o2, 0.0, ExternalId(7));
+        g.add_link(x, o2, 1.0, ExternalId(8));
+
+        let simplified = simplify(&g);
+        // x, y, b, l, o1, o2
+        assert_eq!(6, simplified.node_count());
+        // the constant c is not folded, but collapsed into b -> o1.
+        assert_eq!(7, link_count(&simplified));
+        assert_equivalent(&g, &simplified);
+    }
+}
@@ -1,6 +1,10 @@
 use position::Position;
-use cppn::{Cppn, CppnNodeType};
+use cppn::{Cppn, CppnError, CppnNodeType};
+use evaluator::CppnEvaluator;
+use float::Float;
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
 
 #[derive(Debug, Copy, Clone)]
 pub enum NodeConnectivity {
@@ -59,15 +63,30 @@ where
     pub target: &'a Node<P, T>,
     pub source_idx: (usize, usize), // (layer, node)
     pub target_idx: (usize, usize), // (layer, node)
-    pub outputs: Vec<f64>,
-    pub distance_square: f64,
+    pub outputs: Vec<P::Scalar>,
+    pub distance_square: P::Scalar,
+}
+
+/// Like `Link`, but borrows the outputs of the CPPN instead of owning them.
+#[derive(Clone)]
+pub struct LinkRef<'a, 'b, P, T>
+where
+    P: Position + 'a,
+    T: 'a,
+{
+    pub source: &'a Node<P, T>,
+    pub target: &'a Node<P, T>,
+    pub source_idx: (usize, usize), // (layer, node)
+    pub target_idx: (usize, usize), // (layer, node)
+    pub outputs: &'b [P::Scalar],
+    pub distance_square: P::Scalar,
 }
 
 #[derive(Clone, Debug)]
-struct LayerLink {
+struct LayerLink<F> {
     from_layer: usize,
     to_layer: usize,
-    max_distance_square: Option<f64>,
+    max_distance_square: Option<F>,
 }
 
 #[derive(Clone, Debug)]
@@ -76,7 +95,7 @@ where
     P: Position,
 {
     layers: Vec<Layer<P, T>>,
-    layer_links: Vec<LayerLink>,
+    layer_links: Vec<LayerLink<P::Scalar>>,
 }
 
 impl<P, T> Substrate<P, T>
@@ -104,7 +123,7 @@ where
         &mut self,
         from_layer: usize,
         to_layer: usize,
-        max_distance_square: Option<f64>,
+        max_distance_square: Option<P::Scalar>,
     ) {
         self.layer_links.push(LayerLink {
             from_layer: from_layer,
@@ -113,68 +132,450 @@ where
         });
     }
 
-    /// Iterate over all produced links of Cppn.
+    /// Returns all `(layer link, source node)` pairs whose source node allows outgoing
+    /// connections. These are the units of work of link generation.
+    fn sources(&self) -> Vec<(usize, usize)> {
+        let mut sources = Vec::new();
+        for (layer_link_idx, layer_link) in self.layer_links.iter().enumerate() {
+            for (source_idx, source) in self.layers[layer_link.from_layer].nodes.iter().enumerate()
+            {
+                // Reject invalid connections.
+                match source.node_connectivity {
+                    NodeConnectivity::Out | NodeConnectivity::InOut => {
+                        sources.push((layer_link_idx, source_idx));
+                    }
+                    NodeConnectivity::In => {
+                        // Node does not allow outgoing connections
+                    }
+                }
+            }
+        }
+        sources
+    }
+
+    /// Calls `f` for every target node that can be linked from the `source_idx`-th node of the
+    /// source layer of the `layer_link_idx`-th layer link, together with their squared distance.
+    /// Stops at the first error returned by `f`.
+    fn each_target<'a, F, E>(
+        &'a self,
+        layer_link_idx: usize,
+        source_idx: usize,
+        f: &mut F,
+    ) -> Result<(), E>
+    where
+        F: FnMut((usize, usize), &'a Node<P, T>, (usize, usize), &'a Node<P, T>, P::Scalar)
+            -> Result<(), E>,
+    {
+        let layer_link = &self.layer_links[layer_link_idx];
+        let source = &self.layers[layer_link.from_layer].nodes[source_idx];
+
+        for (target_idx, target) in self.layers[layer_link.to_layer].nodes.iter().enumerate() {
+            // Reject invalid connections.
+            match target.node_connectivity {
+                NodeConnectivity::In | NodeConnectivity::InOut => {}
+                NodeConnectivity::Out => {
+                    // Node does not allow incoming connections
+                    continue;
+                }
+            }
+
+            let distance_sq = source.position.distance_square(&target.position);
+
+            // reject a pair of nodes based on `max_distance_square`.
+            if let Some(max_d_sq) = layer_link.max_distance_square {
+                if distance_sq > max_d_sq {
+                    continue;
+                }
+            }
+
+            f(
+                (layer_link.from_layer, source_idx),
+                source,
+                (layer_link.to_layer, target_idx),
+                target,
+                distance_sq,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Calls `f` for every pair of source and target nodes that can be linked, together with
+    /// their squared distance. Stops at the first error returned by `f`.
+    fn each_node_pair<'a, F, E>(&'a self, mut f: F) -> Result<(), E>
+    where
+        F: FnMut((usize, usize), &'a Node<P, T>, (usize, usize), &'a Node<P, T>, P::Scalar)
+            -> Result<(), E>,
+    {
+        for (layer_link_idx, source_idx) in self.sources() {
+            self.each_target(layer_link_idx, source_idx, &mut f)?;
+        }
+        Ok(())
+    }
 
+    /// Checks that a CPPN with `input_count` inputs takes the coordinates of a source and a
+    /// target position.
+    fn check_cppn_inputs(input_count: usize) -> Result<(), CppnError> {
+        if input_count != 2 * P::dims() {
+            return Err(CppnError::InputCountMismatch {
+                expected: input_count,
+                actual: 2 * P::dims(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Iterate over all produced links of Cppn.
+    ///
+    /// Fails if the CPPN does not take `2 * P::dims()` inputs, or if it produces an invalid
+    /// output for any link (see `Cppn::try_calculate`). In the latter case `callback` has already
+    /// been called for the links preceding the failing one.
     pub fn each_link<'a, N, L, EXTID, F>(
         &'a self,
-        cppn: &'a mut Cppn<'a, N, L, EXTID>,
+        cppn: &'a mut Cppn<'a, N, L, EXTID, P::Scalar>,
         callback: &mut F,
-    ) where
+    ) -> Result<(), CppnError>
+    where
         N: CppnNodeType,
         L: Copy + Debug + Send + Sized + Into<f64> + 'a,
         EXTID: Copy + Debug + Send + Sized + Ord + 'a,
         F: FnMut(Link<'a, P, T>),
     {
-        for layer_link in self.layer_links.iter() {
-            for (source_idx, source) in self.layers[layer_link.from_layer].nodes.iter().enumerate()
-            {
-                // Reject invalid connections.
-                match source.node_connectivity {
-                    NodeConnectivity::Out | NodeConnectivity::InOut => {}
-                    NodeConnectivity::In => {
-                        // Node does not allow outgoing connections
-                        continue;
-                    }
-                }
+        Self::check_cppn_inputs(cppn.input_count())?;
+        self.each_node_pair(|source_idx, source, target_idx, target, distance_sq| {
+            // Calculate the weight between source and target using the CPPN.
+            let inputs_to_cppn = [source.position.coords(), target.position.coords()];
+            let outputs_from_cppn = cppn.try_calculate(&inputs_to_cppn)?;
 
-                for (target_idx, target) in
-                    self.layers[layer_link.to_layer].nodes.iter().enumerate()
-                {
-                    // Reject invalid connections.
-                    match target.node_connectivity {
-                        NodeConnectivity::In | NodeConnectivity::InOut => {}
-                        NodeConnectivity::Out => {
-                            // Node does not allow incoming connections
-                            continue;
-                        }
-                    }
+            let link = Link {
+                source,
+                target,
+                source_idx,
+                target_idx,
+                outputs: outputs_from_cppn,
+                distance_square: distance_sq,
+            };
+            callback(link);
+            Ok(())
+        })
+    }
+
+    /// Iterate over all produced links of Cppn, without allocating the outputs of each link.
+    ///
+    /// The outputs of the CPPN are written into a single buffer which is handed out to
+    /// `callback` as part of a `LinkRef`. Errors are reported as by `each_link`.
+    pub fn each_link_ref<'a, 'g, N, L, EXTID, F>(
+        &'a self,
+        cppn: &mut Cppn<'g, N, L, EXTID, P::Scalar>,
+        callback: &mut F,
+    ) -> Result<(), CppnError>
+    where
+        N: CppnNodeType + 'g,
+        L: Copy + Debug + Send + Sized + Into<f64> + 'g,
+        EXTID: Copy + Debug + Send + Sized + Ord + 'g,
+        F: for<'b> FnMut(LinkRef<'a, 'b, P, T>),
+    {
+        Self::check_cppn_inputs(cppn.input_count())?;
+        let mut outputs = vec![P::Scalar::zero(); cppn.output_count()];
+        self.each_node_pair(|source_idx, source, target_idx, target, distance_sq| {
+            let inputs_to_cppn = [source.position.coords(), target.position.coords()];
+            cppn.try_calculate_into(&inputs_to_cppn, &mut outputs)?;
+
+            callback(LinkRef {
+                source,
+                target,
+                source_idx,
+                target_idx,
+                outputs: &outputs,
+                distance_square: distance_sq,
+            });
+            Ok(())
+        })
+    }
+
+    /// Produces all links of the substrate using `threads` worker threads (or as many as there
+    /// are CPUs if `threads` is 0).
+    ///
+    /// Each worker owns an evaluator forked from `cppn` (see `CppnEvaluator::fork`), so all
+    /// workers share its compiled network, including the bias value. Use `Cppn::evaluator` to
+    /// produce the links of a `Cppn`. The links are returned in the same order as `each_link`
+    /// would produce them, and if evaluation fails, the error is the one `each_link` would
+    /// report.
+    pub fn links_parallel<'a, N>(
+        &'a self,
+        cppn: &CppnEvaluator<N, P::Scalar>,
+        threads: usize,
+    ) -> Result<Vec<Link<'a, P, T>>, CppnError>
+    where
+        P: Sync,
+        T: Sync,
+        N: CppnNodeType + Sync,
+    {
+        let threads = if threads == 0 {
+            thread::available_parallelism().map_or(1, |n| n.get())
+        } else {
+            threads
+        };
 
-                    let distance_sq = source.position.distance_square(&target.position);
+        Self::check_cppn_inputs(cppn.input_count())?;
 
-                    // reject a pair of nodes based on `max_distance_square`.
-                    if let Some(max_d_sq) = layer_link.max_distance_square {
-                        if distance_sq > max_d_sq {
-                            continue;
+        let sources = self.sources();
+        if sources.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Hand out small chunks of work, so that threads which finish early can pick up more.
+        let chunk_size = (sources.len() / (threads * 8)).max(1);
+        let chunks: &[&[(usize, usize)]] = &sources.chunks(chunk_size).collect::<Vec<_>>();
+        let next_chunk = &AtomicUsize::new(0);
+        // Set on the first error. Chunks are handed out in order, so all chunks preceding a
+        // failed one are still completed and the error of the first failed chunk is reported.
+        let failed = &AtomicBool::new(false);
+
+        type ChunkResult<'a, P, T> = Result<Vec<Link<'a, P, T>>, CppnError>;
+        let mut results: Vec<(usize, ChunkResult<'a, P, T>)> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads.min(chunks.len()))
+                .map(|_| {
+                    let mut cppn = cppn.fork();
+                    scope.spawn(move || {
+                        let mut produced = Vec::new();
+                        loop {
+                            if failed.load(Ordering::Relaxed) {
+                                break;
+                            }
+                            let chunk_idx = next_chunk.fetch_add(1, Ordering::Relaxed);
+                            if chunk_idx >= chunks.len() {
+                                break;
+                            }
+                            let mut links = Vec::new();
+                            let result = chunks[chunk_idx].iter().try_for_each(
+                                |&(layer_link_idx, source_idx)| {
+                                    self.each_target(
+                                        layer_link_idx,
+                                        source_idx,
+                                        &mut |source_idx, source, target_idx, target, distance_sq| {
+                                            let inputs_to_cppn = [
+                                                source.position.coords(),
+                                                target.position.coords(),
+                                            ];
+                                            links.push(Link {
+                                                source,
+                                                target,
+                                                source_idx,
+                                                target_idx,
+                                                outputs: cppn.try_calculate(&inputs_to_cppn)?,
+                                                distance_square: distance_sq,
+                                            });
+                                            Ok(())
+                                        },
+                                    )
+                                },
+                            );
+                            if result.is_err() {
+                                failed.store(true, Ordering::Relaxed);
+                            }
+                            produced.push((chunk_idx, result.map(|_| links)));
                         }
-                    }
+                        produced
+                    })
+                })
+                .collect();
 
-                    // Calculate the weight between source and target using the CPPN.
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
 
-                    let inputs_to_cppn = [source.position.coords(), target.position.coords()];
+        results.sort_by_key(|&(chunk_idx, _)| chunk_idx);
+        let mut all_links = Vec::new();
+        for (_, links) in results {
+            all_links.extend(links?);
+        }
+        Ok(all_links)
+    }
+}
 
-                    let outputs_from_cppn = cppn.calculate(&inputs_to_cppn);
+#[cfg(test)]
+mod tests {
+    use super::{Layer, NodeConnectivity, Substrate};
+    use activation_function::GeometricActivationFunction as AF;
+    use acyclic_network::ExternalId;
+    use compiled::EvalBuffers;
+    use cppn::{Cppn, CppnError, CppnGraph, CppnNode};
+    use evaluator::CppnEvaluator;
+    use float::Float;
+    use position::{Position2d, Position3d};
 
-                    let link = Link {
-                        source: source,
-                        target: target,
-                        source_idx: (layer_link.from_layer, source_idx),
-                        target_idx: (layer_link.to_layer, target_idx),
-                        outputs: outputs_from_cppn,
-                        distance_square: distance_sq,
-                    };
-                    callback(link);
-                }
-            }
+    type Graph = CppnGraph<CppnNode<AF>, f64, ExternalId>;
+
+    fn grid_layer<F: Float>(n: usize, y: f64) -> Layer<Position2d<F>, ()> {
+        let mut layer = Layer::new();
+        for i in 0..n {
+            let x = -1.0 + 2.0 * (i as f64) / (n as f64);
+            layer.add_node(
+                Position2d::new(F::from_f64(x), F::from_f64(y)),
+                (),
+                NodeConnectivity::InOut,
+            );
+        }
+        layer
+    }
+
+    fn four_input_graph() -> Graph {
+        let mut g = CppnGraph::new();
+        let x1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let y1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let x2 = g.add_node(CppnNode::input(AF::Linear), ExternalId(3));
+        let y2 = g.add_node(CppnNode::input(AF::Linear), ExternalId(4));
+        let o1 = g.add_node(CppnNode::output(AF::BipolarSigmoid), ExternalId(5));
+        g.add_link(x1, o1, 0.5, ExternalId(1));
+        g.add_link(y1, o1, -0.25, ExternalId(2));
+        g.add_link(x2, o1, 1.0, ExternalId(3));
+        g.add_link(y2, o1, 0.75, ExternalId(4));
+        g
+    }
+
+    #[test]
+    fn test_links_parallel_matches_each_link() {
+        let g = four_input_graph();
+
+        let mut substrate = Substrate::new();
+        let l0 = substrate.add_layer(grid_layer(20, -1.0));
+        let l1 = substrate.add_layer(grid_layer(15, 0.0));
+        let l2 = substrate.add_layer(grid_layer(10, 1.0));
+        substrate.add_layer_link(l0, l1, None);
+        substrate.add_layer_link(l1, l2, Some(0.5));
+
+        let mut expected = Vec::new();
+        let mut cppn = Cppn::new(&g);
+        substrate
+            .each_link(&mut cppn, &mut |link| {
+                expected.push((link.source_idx, link.target_idx, link.outputs));
+            })
+            .unwrap();
+        assert!(!expected.is_empty());
+
+        for &threads in &[1, 3, 0] {
+            let links = substrate.links_parallel(&CppnEvaluator::new(&g), threads).unwrap();
+            let links: Vec<_> = links
+                .into_iter()
+                .map(|link| (link.source_idx, link.target_idx, link.outputs))
+                .collect();
+            assert_eq!(expected, links);
+        }
+    }
+
+    #[test]
+    fn test_links_parallel_keeps_bias_value() {
+        let mut g: Graph = CppnGraph::new();
+        let x1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let y1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let x2 = g.add_node(CppnNode::input(AF::Linear), ExternalId(3));
+        let y2 = g.add_node(CppnNode::input(AF::Linear), ExternalId(4));
+        let b = g.add_node(CppnNode::bias(AF::Linear), ExternalId(5));
+        let o1 = g.add_node(CppnNode::output(AF::BipolarSigmoid), ExternalId(6));
+        g.add_link(x1, o1, 0.5, ExternalId(1));
+        g.add_link(y1, o1, -0.25, ExternalId(2));
+        g.add_link(x2, o1, 1.0, ExternalId(3));
+        g.add_link(y2, o1, 0.75, ExternalId(4));
+        g.add_link(b, o1, 0.5, ExternalId(5));
+
+        let mut substrate = Substrate::new();
+        let l0 = substrate.add_layer(grid_layer(6, -1.0));
+        let l1 = substrate.add_layer(grid_layer(6, 1.0));
+        substrate.add_layer_link(l0, l1, None);
+
+        let mut expected = Vec::new();
+        let mut cppn = Cppn::new(&g);
+        cppn.set_bias_value(-2.0);
+        substrate
+            .each_link(&mut cppn, &mut |link| expected.push(link.outputs))
+            .unwrap();
+
+        let mut evaluator = CppnEvaluator::new(&g);
+        evaluator.set_bias_value(-2.0);
+        let links = substrate.links_parallel(&evaluator, 2).unwrap();
+        let links: Vec<_> = links.into_iter().map(|link| link.outputs).collect();
+        assert_eq!(expected, links);
+
+        let default_bias = substrate.links_parallel(&CppnEvaluator::new(&g), 2).unwrap();
+        assert!(default_bias.iter().zip(&expected).any(|(link, e)| link.outputs != *e));
+    }
+
+    #[test]
+    fn test_f32_substrate() {
+        let g = four_input_graph();
+
+        let mut substrate: Substrate<Position2d<f32>, ()> = Substrate::new();
+        let l0 = substrate.add_layer(grid_layer(4, -1.0));
+        let l1 = substrate.add_layer(grid_layer(4, 1.0));
+        substrate.add_layer_link(l0, l1, Some(4.5));
+
+        let mut cppn = Cppn::with_buffers(&g, EvalBuffers::new());
+        let mut count = 0;
+        substrate
+            .each_link_ref(&mut cppn, &mut |link| {
+                let (x1, y1) = link.source.position.xy();
+                let (x2, y2) = link.target.position.xy();
+                let expected = (0.5 * x1 - 0.25 * y1 + x2 + 0.75 * y2) as f64;
+                let expected = 2.0 / (1.0 + (-4.9 * expected).exp()) - 1.0;
+                assert!((expected - link.outputs[0] as f64).abs() < 1e-5);
+                assert!(link.distance_square <= 4.5f32);
+                count += 1;
+            })
+            .unwrap();
+        assert_eq!(10, count);
+    }
+
+    #[test]
+    fn test_dimensionality_mismatch() {
+        let g = four_input_graph();
+
+        let mut substrate: Substrate<Position3d, ()> = Substrate::new();
+        let mut layer = Layer::new();
+        layer.add_node(Position3d::new(0.0, 0.0, 0.0), (), NodeConnectivity::InOut);
+        let l0 = substrate.add_layer(layer);
+        substrate.add_layer_link(l0, l0, None);
+
+        let expected = CppnError::InputCountMismatch {
+            expected: 4,
+            actual: 6,
+        };
+        let mut cppn = Cppn::new(&g);
+        assert_eq!(Err(expected.clone()), substrate.each_link(&mut cppn, &mut |_| {}));
+        assert_eq!(
+            Err(expected),
+            substrate.links_parallel(&CppnEvaluator::new(&g), 2).map(|links| links.len())
+        );
+    }
+
+    #[test]
+    fn test_non_finite_output() {
+        let g = four_input_graph();
+
+        let mut substrate = Substrate::new();
+        let mut layer = grid_layer(4, 0.0);
+        layer.add_node(
+            Position2d::new(f64::NAN, 0.0),
+            (),
+            NodeConnectivity::InOut,
+        );
+        let l0 = substrate.add_layer(grid_layer(8, -1.0));
+        let l1 = substrate.add_layer(layer);
+        substrate.add_layer_link(l0, l1, None);
+
+        let mut count = 0;
+        let mut cppn = Cppn::new(&g);
+        assert_eq!(
+            Err(CppnError::NonFiniteOutput { nth: 0 }),
+            substrate.each_link_ref(&mut cppn, &mut |_| count += 1)
+        );
+        assert_eq!(4, count);
+        for &threads in &[1, 3] {
+            assert_eq!(
+                Err(CppnError::NonFiniteOutput { nth: 0 }),
+                substrate.links_parallel(&CppnEvaluator::new(&g), threads).map(|links| links.len())
+            );
         }
     }
 }
@@ -0,0 +1,182 @@
+use compiled::CompiledCppn;
+use cppn::{CppnGraph, CppnNodeIndex, CppnNodeType};
+use float::Float;
+use std::fmt::Debug;
+
+/// The signal an active link delivered to its target node.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinkContribution<F = f64>
+where
+    F: Float,
+{
+    pub source: CppnNodeIndex,
+    pub weight: F,
+    /// The output of the source node.
+    pub signal: F,
+}
+
+impl<F> LinkContribution<F>
+where
+    F: Float,
+{
+    /// The weighted signal, i.e. what this link added to the input of its target.
+    pub fn value(&self) -> F {
+        self.weight * self.signal
+    }
+}
+
+/// The recorded state of a single node.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NodeTrace<F = f64>
+where
+    F: Float,
+{
+    /// The aggregated input of the node (including the external input of input nodes).
+    pub input: F,
+    /// The output of the activation function.
+    pub output: F,
+    /// The contributions of all active incoming links.
+    pub contributions: Vec<LinkContribution<F>>,
+}
+
+/// The state of every node of a CPPN after an evaluation.
+///
+/// Tracing is opt-in (see `Cppn::set_tracing`), as it copies the signals of all nodes and links
+/// after every evaluation.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CppnTrace<F = f64>
+where
+    F: Float,
+{
+    nodes: Vec<NodeTrace<F>>,
+}
+
+impl<F> CppnTrace<F>
+where
+    F: Float,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the state of `compiled` from the signal arrays of an evaluation (see
+    /// `CompiledCppn::process`). Allocations of a previous recording are reused.
+    pub fn record<N>(&mut self, compiled: &CompiledCppn<N, F>, incoming: &[F], outgoing: &[F])
+    where
+        N: CppnNodeType,
+    {
+        self.nodes
+            .resize(compiled.node_count(), NodeTrace::default());
+        for (nth, &node) in compiled.order().iter().enumerate() {
+            let trace = &mut self.nodes[node];
+            trace.input = incoming[node];
+            trace.output = outgoing[node];
+            trace.contributions.clear();
+            trace
+                .contributions
+                .extend(
+                    compiled
+                        .incoming_links(nth)
+                        .iter()
+                        .map(|link| LinkContribution {
+                            source: CppnNodeIndex::new(link.source),
+                            weight: link.weight,
+                            signal: outgoing[link.source],
+                        }),
+                );
+        }
+    }
+
+    /// The traces of all nodes, indexed by `CppnNodeIndex::index()`. Empty before the first
+    /// evaluation.
+    pub fn nodes(&self) -> &[NodeTrace<F>] {
+        &self.nodes
+    }
+
+    pub fn node(&self, node: CppnNodeIndex) -> Option<&NodeTrace<F>> {
+        self.nodes.get(node.index())
+    }
+
+    /// Looks up the trace of the node of `graph` with the given `external_id`. `graph` must be
+    /// the graph the traced network was compiled from.
+    pub fn node_by_external_id<N, L, EXTID>(
+        &self,
+        graph: &CppnGraph<N, L, EXTID>,
+        external_id: EXTID,
+    ) -> Option<&NodeTrace<F>>
+    where
+        N: CppnNodeType,
+        L: Copy + Debug + Send + Sized + Into<f64>,
+        EXTID: Copy + Debug + Send + Sized + Ord,
+    {
+        graph
+            .nodes()
+            .iter()
+            .position(|node| node.external_node_id() == external_id)
+            .and_then(|index| self.nodes.get(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use activation_function::GeometricActivationFunction as AF;
+    use acyclic_network::ExternalId;
+    use cppn::{Cppn, CppnGraph, CppnNode};
+
+    #[test]
+    fn test_trace() {
+        let mut g = CppnGraph::new();
+        let i1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let i2 = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let h1 = g.add_node(CppnNode::hidden(AF::Absolute), ExternalId(3));
+        let o1 = g.add_node(CppnNode::output(AF::Linear), ExternalId(4));
+        g.add_link(i1, h1, 2.0, ExternalId(1));
+        g.add_link(i2, h1, 1.0, ExternalId(2));
+        g.add_link(h1, o1, 0.5, ExternalId(3));
+        g.add_link(i2, o1, -1.0, ExternalId(4));
+
+        let mut cppn = Cppn::new(&g);
+        cppn.calculate(&[&[1.0, -3.0]]);
+        assert!(cppn.trace().is_none());
+
+        cppn.set_tracing(true);
+        assert_eq!(vec![5.5], cppn.calculate(&[&[-1.0, -3.0]]));
+        let trace = cppn.trace().unwrap();
+        assert_eq!(4, trace.nodes().len());
+
+        let hidden = trace.node(h1).unwrap();
+        assert_eq!(-5.0, hidden.input);
+        assert_eq!(5.0, hidden.output);
+        let contributions: Vec<_> = hidden.contributions.iter().map(|c| c.value()).collect();
+        assert_eq!(vec![-2.0, -3.0], contributions);
+        assert_eq!(i1, hidden.contributions[0].source);
+        assert_eq!(-1.0, hidden.contributions[0].signal);
+
+        let output = cppn.node_trace(ExternalId(4)).unwrap();
+        assert_eq!(5.5, output.output);
+        assert_eq!(2, output.contributions.len());
+        assert_eq!(Some(output), trace.node(o1));
+        assert!(cppn.node_trace(ExternalId(5)).is_none());
+
+        // the trace follows the latest evaluation.
+        cppn.calculate(&[&[1.0, 0.0]]);
+        assert_eq!(2.0, cppn.node_trace(ExternalId(3)).unwrap().output);
+
+        cppn.set_tracing(false);
+        assert!(cppn.trace().is_none());
+    }
+
+    #[test]
+    fn test_trace_non_finite_output() {
+        let mut g = CppnGraph::new();
+        let i1 = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let o1 = g.add_node(CppnNode::output(AF::Linear), ExternalId(2));
+        g.add_link(i1, o1, 2.0, ExternalId(1));
+
+        let mut cppn = Cppn::new(&g);
+        cppn.set_tracing(true);
+        assert!(cppn.try_calculate(&[&[f64::MAX]]).is_err());
+        assert_eq!(f64::MAX, cppn.trace().unwrap().node(i1).unwrap().output);
+        assert_eq!(f64::INFINITY, cppn.trace().unwrap().node(o1).unwrap().input);
+    }
+}
@@ -0,0 +1,405 @@
+//! Gradient-based fitting of the link weights of a `CppnGraph`.
+//!
+//! The topology of a CPPN is typically found by neuro-evolution. Its weights can then be
+//! fine-tuned by gradient descent: a `Trainer` computes the gradient of a loss with respect to
+//! all link weights by backpropagation, and an `Optimizer` (`Sgd` or `Adam`) updates the
+//! weights. Finally, `Trainer::write_weights` stores the fitted weights back into the graph.
+
+use compiled::{CompiledCppn, EvalBuffers};
+use cppn::{CppnGraph, CppnNodeType};
+use float::Float;
+use std::fmt::Debug;
+use std::mem;
+
+/// Updates parameters given the gradient of a loss with respect to them.
+pub trait Optimizer {
+    /// Performs a single update of `params`. `gradient` has the same length as `params`, which
+    /// must not change between calls.
+    fn step<F: Float>(&mut self, params: &mut [F], gradient: &[F]);
+}
+
+/// Stochastic gradient descent with optional momentum.
+#[derive(Clone, Debug)]
+pub struct Sgd {
+    pub learning_rate: f64,
+    pub momentum: f64,
+    velocity: Vec<f64>,
+}
+
+impl Sgd {
+    pub fn new(learning_rate: f64) -> Self {
+        Self::with_momentum(learning_rate, 0.0)
+    }
+
+    pub fn with_momentum(learning_rate: f64, momentum: f64) -> Self {
+        Sgd {
+            learning_rate,
+            momentum,
+            velocity: Vec::new(),
+        }
+    }
+}
+
+impl Optimizer for Sgd {
+    fn step<F: Float>(&mut self, params: &mut [F], gradient: &[F]) {
+        assert!(params.len() == gradient.len());
+        self.velocity.resize(params.len(), 0.0);
+        for ((param, &g), v) in params
+            .iter_mut()
+            .zip(gradient.iter())
+            .zip(self.velocity.iter_mut())
+        {
+            *v = self.momentum * *v - self.learning_rate * g.to_f64();
+            *param += F::from_f64(*v);
+        }
+    }
+}
+
+/// The Adam optimizer (Kingma and Ba, 2014).
+#[derive(Clone, Debug)]
+pub struct Adam {
+    pub learning_rate: f64,
+    pub beta1: f64,
+    pub beta2: f64,
+    pub epsilon: f64,
+    t: i32,
+    m: Vec<f64>,
+    v: Vec<f64>,
+}
+
+impl Adam {
+    /// Creates an Adam optimizer with the commonly used defaults `beta1 = 0.9`,
+    /// `beta2 = 0.999` and `epsilon = 1e-8`.
+    pub fn new(learning_rate: f64) -> Self {
+        Adam {
+            learning_rate,
+            beta1: 0.9,
+            beta2: 0.999,
+            epsilon: 1e-8,
+            t: 0,
+            m: Vec::new(),
+            v: Vec::new(),
+        }
+    }
+}
+
+impl Optimizer for Adam {
+    fn step<F: Float>(&mut self, params: &mut [F], gradient: &[F]) {
+        assert!(params.len() == gradient.len());
+        self.m.resize(params.len(), 0.0);
+        self.v.resize(params.len(), 0.0);
+        self.t += 1;
+        let correction1 = 1.0 - self.beta1.powi(self.t);
+        let correction2 = 1.0 - self.beta2.powi(self.t);
+        for (i, (param, &g)) in params.iter_mut().zip(gradient.iter()).enumerate() {
+            let g = g.to_f64();
+            self.m[i] = self.beta1 * self.m[i] + (1.0 - self.beta1) * g;
+            self.v[i] = self.beta2 * self.v[i] + (1.0 - self.beta2) * g * g;
+            let m_hat = self.m[i] / correction1;
+            let v_hat = self.v[i] / correction2;
+            *param -= F::from_f64(self.learning_rate * m_hat / (v_hat.sqrt() + self.epsilon));
+        }
+    }
+}
+
+/// Computes gradients of a loss with respect to the link weights of a CPPN and applies an
+/// `Optimizer` to them.
+///
+/// The weights are those of the active links of the graph, in the order of
+/// `CompiledCppn::links()`. A training step usually looks like this:
+///
+/// ```text
+/// for (inputs, targets) in samples {
+///     trainer.accumulate_squared_error(&[inputs], targets);
+/// }
+/// trainer.step(&mut optimizer);
+/// ```
+///
+/// Arbitrary losses can be used by calling `forward` and `backward` directly.
+#[derive(Clone, Debug)]
+pub struct Trainer<N, F = f64>
+where
+    N: CppnNodeType,
+    F: Float,
+{
+    compiled: CompiledCppn<N, F>,
+    buffers: EvalBuffers<F>,
+    outputs: Vec<F>,
+    output_gradients: Vec<F>,
+    node_gradients: Vec<F>,
+    weights: Vec<F>,
+    gradient: Vec<F>,
+}
+
+impl<N> Trainer<N>
+where
+    N: CppnNodeType,
+{
+    /// Creates a trainer for the weights of `graph`, using `f64` signals.
+    pub fn new<L, EXTID>(graph: &CppnGraph<N, L, EXTID>) -> Self
+    where
+        L: Copy + Debug + Send + Sized + Into<f64>,
+        EXTID: Copy + Debug + Send + Sized + Ord,
+    {
+        Self::compile(graph)
+    }
+}
+
+impl<N, F> Trainer<N, F>
+where
+    N: CppnNodeType,
+    F: Float,
+{
+    /// Creates a trainer for the weights of `graph`, using signals of type `F`.
+    pub fn compile<L, EXTID>(graph: &CppnGraph<N, L, EXTID>) -> Self
+    where
+        L: Copy + Debug + Send + Sized + Into<f64>,
+        EXTID: Copy + Debug + Send + Sized + Ord,
+    {
+        let compiled = CompiledCppn::compile(graph);
+        let weights: Vec<F> = compiled.links().iter().map(|link| link.weight).collect();
+        Trainer {
+            buffers: EvalBuffers::new(),
+            outputs: vec![F::zero(); compiled.output_count()],
+            output_gradients: vec![F::zero(); compiled.output_count()],
+            node_gradients: vec![F::zero(); compiled.node_count()],
+            gradient: vec![F::zero(); weights.len()],
+            weights,
+            compiled,
+        }
+    }
+
+    pub fn compiled(&self) -> &CompiledCppn<N, F> {
+        &self.compiled
+    }
+
+    /// The current weights, in the order of `CompiledCppn::links()`.
+    pub fn weights(&self) -> &[F] {
+        &self.weights
+    }
+
+    /// The gradient accumulated since the last `step` (or `zero_gradient`).
+    pub fn gradient(&self) -> &[F] {
+        &self.gradient
+    }
+
+    pub fn zero_gradient(&mut self) {
+        for value in self.gradient.iter_mut() {
+            *value = F::zero();
+        }
+    }
+
+    /// Evaluates the network with the current weights and returns the outputs.
+    pub fn forward(&mut self, inputs: &[&[F]]) -> &[F] {
+        self.compiled.process_with(inputs, &mut self.buffers);
+        self.compiled
+            .read_outputs(self.buffers.outgoing(), &mut self.outputs);
+        &self.outputs
+    }
+
+    /// Adds the gradient of a loss to the accumulated gradient, given the derivatives of the
+    /// loss with respect to the outputs of the last `forward`.
+    pub fn backward(&mut self, output_gradients: &[F]) {
+        self.compiled.backward_with(
+            output_gradients,
+            &mut self.node_gradients,
+            &mut self.gradient,
+            &mut self.buffers,
+        );
+    }
+
+    /// Evaluates the network for `inputs` and accumulates the gradient of the squared error
+    /// `0.5 * sum((output - target)^2)`, which is returned.
+    pub fn accumulate_squared_error(&mut self, inputs: &[&[F]], targets: &[F]) -> F {
+        assert!(targets.len() == self.compiled.output_count());
+        self.forward(inputs);
+        let mut loss = F::zero();
+        for ((gradient, &output), &target) in self
+            .output_gradients
+            .iter_mut()
+            .zip(self.outputs.iter())
+            .zip(targets.iter())
+        {
+            *gradient = output - target;
+            loss += F::from_f64(0.5) * *gradient * *gradient;
+        }
+        let output_gradients = mem::take(&mut self.output_gradients);
+        self.backward(&output_gradients);
+        self.output_gradients = output_gradients;
+        loss
+    }
+
+    /// Updates the weights using the accumulated gradient and resets the gradient.
+    pub fn step<O: Optimizer>(&mut self, optimizer: &mut O) {
+        optimizer.step(&mut self.weights, &self.gradient);
+        for (k, &weight) in self.weights.iter().enumerate() {
+            self.compiled.set_link_weight(k, weight);
+        }
+        self.zero_gradient();
+    }
+
+    /// Stores the current weights in `graph`, which must be the graph the trainer was created
+    /// from.
+    pub fn write_weights<L, EXTID>(&self, graph: &mut CppnGraph<N, L, EXTID>)
+    where
+        L: Copy + Debug + Send + Sized + Into<f64> + From<f64>,
+        EXTID: Copy + Debug + Send + Sized + Ord,
+    {
+        for (&link_index, &weight) in self.compiled.link_indices().iter().zip(self.weights.iter())
+        {
+            graph
+                .link_mut(link_index)
+                .set_weight(L::from(weight.to_f64()));
+        }
+    }
+}
+
+/// Fits the weights of `graph` to minimize the mean squared error over `samples`, which are
+/// pairs of input and target vectors, using full-batch gradient descent for `epochs` epochs.
+///
+/// Returns the mean squared error (as defined by `Trainer::accumulate_squared_error`) of the
+/// last epoch, before its update.
+pub fn fit_squared_error<N, L, EXTID, O>(
+    graph: &mut CppnGraph<N, L, EXTID>,
+    samples: &[(Vec<f64>, Vec<f64>)],
+    optimizer: &mut O,
+    epochs: usize,
+) -> f64
+where
+    N: CppnNodeType,
+    L: Copy + Debug + Send + Sized + Into<f64> + From<f64>,
+    EXTID: Copy + Debug + Send + Sized + Ord,
+    O: Optimizer,
+{
+    let mut trainer = Trainer::new(graph);
+    let scale = 1.0 / (samples.len().max(1) as f64);
+    let mut loss = 0.0;
+    for _ in 0..epochs {
+        loss = 0.0;
+        for (inputs, targets) in samples.iter() {
+            loss += trainer.accumulate_squared_error(&[inputs], targets);
+        }
+        for gradient in trainer.gradient.iter_mut() {
+            *gradient *= scale;
+        }
+        trainer.step(optimizer);
+        loss *= scale;
+    }
+    trainer.write_weights(graph);
+    loss
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fit_squared_error, Adam, Sgd, Trainer};
+    use activation_function::GeometricActivationFunction as AF;
+    use acyclic_network::ExternalId;
+    use aggregation::Aggregation;
+    use cppn::{Cppn, CppnGraph, CppnNode};
+
+    type Graph = CppnGraph<CppnNode<AF>, f64, ExternalId>;
+
+    fn graph(weights: [f64; 6]) -> Graph {
+        graph_with_aggregation(weights, Aggregation::Sum)
+    }
+
+    /// Like `graph`, but the output node and the hidden node with two inputs use `aggregation`.
+    fn graph_with_aggregation(weights: [f64; 6], aggregation: Aggregation) -> Graph {
+        let mut g = CppnGraph::new();
+        let x = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let y = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let h1 = g.add_node(CppnNode::hidden(AF::Gaussian), ExternalId(3));
+        let h2 = g.add_node(
+            CppnNode::hidden(AF::Sine).with_aggregation(aggregation),
+            ExternalId(4),
+        );
+        let o1 = g.add_node(
+            CppnNode::output(AF::BipolarSigmoid).with_aggregation(aggregation),
+            ExternalId(5),
+        );
+        g.add_link(x, h1, weights[0], ExternalId(1));
+        g.add_link(y, h1, weights[1], ExternalId(2));
+        g.add_link(x, h2, weights[2], ExternalId(3));
+        g.add_link(h1, h2, weights[3], ExternalId(4));
+        g.add_link(h1, o1, weights[4], ExternalId(5));
+        g.add_link(h2, o1, weights[5], ExternalId(6));
+        g
+    }
+
+    fn samples(target: &Graph) -> Vec<(Vec<f64>, Vec<f64>)> {
+        let mut cppn = Cppn::new(target);
+        let mut samples = Vec::new();
+        for i in 0..8 {
+            for j in 0..8 {
+                let inputs = vec![-1.0 + 0.25 * (i as f64), -1.0 + 0.25 * (j as f64)];
+                let outputs = cppn.calculate(&[&inputs]);
+                samples.push((inputs, outputs));
+            }
+        }
+        samples
+    }
+
+    fn assert_gradient_matches_finite_difference(g: &Graph) {
+        let mut trainer = Trainer::new(g);
+        let loss = trainer.accumulate_squared_error(&[&[0.3, -0.6]], &[0.25]);
+        let gradient = trainer.gradient().to_vec();
+
+        let h = 1e-6;
+        for (k, &link_index) in trainer.compiled().link_indices().iter().enumerate() {
+            let loss_at = |delta: f64| {
+                let mut g = g.clone();
+                let w = g.link_mut(link_index).weight();
+                g.link_mut(link_index).set_weight(w + delta);
+                let o = Cppn::new(&g).calculate(&[&[0.3, -0.6]])[0];
+                0.5 * (o - 0.25) * (o - 0.25)
+            };
+            assert_eq!(loss, loss_at(0.0));
+            let expected = (loss_at(h) - loss_at(-h)) / (2.0 * h);
+            assert!((gradient[k] - expected).abs() < 1e-8, "link {}", k);
+        }
+    }
+
+    #[test]
+    fn test_gradient_matches_finite_difference() {
+        let weights = [0.5, -0.3, 0.8, 1.1, -0.7, 0.4];
+        for &aggregation in Aggregation::ALL.iter() {
+            let g = graph_with_aggregation(weights, aggregation);
+            assert_gradient_matches_finite_difference(&g);
+        }
+    }
+
+    #[test]
+    fn test_fit_linear_with_sgd() {
+        let mut g = CppnGraph::new();
+        let x = g.add_node(CppnNode::input(AF::Linear), ExternalId(1));
+        let y = g.add_node(CppnNode::input(AF::Linear), ExternalId(2));
+        let b = g.add_node(CppnNode::bias(AF::Linear), ExternalId(3));
+        let o = g.add_node(CppnNode::output(AF::Linear), ExternalId(4));
+        g.add_link(x, o, 0.0, ExternalId(1));
+        g.add_link(y, o, 0.0, ExternalId(2));
+        g.add_link(b, o, 0.0, ExternalId(3));
+
+        let samples: Vec<_> = (0..25)
+            .map(|i| {
+                let (px, py) = ((i % 5) as f64 * 0.5 - 1.0, (i / 5) as f64 * 0.5 - 1.0);
+                (vec![px, py], vec![0.5 * px - 0.3 * py + 0.2])
+            })
+            .collect();
+
+        let loss = fit_squared_error(&mut g, &samples, &mut Sgd::with_momentum(0.5, 0.5), 200);
+        assert!(loss < 1e-12);
+        let mut cppn = Cppn::new(&g);
+        assert!((cppn.calculate(&[&[0.4, 0.1]])[0] - 0.37).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_fit_with_adam() {
+        let target = graph([1.5, -2.0, 0.6, 1.0, -1.2, 0.9]);
+        let samples = samples(&target);
+
+        let mut g = graph([1.0, -1.0, 0.5, 0.5, -0.5, 0.5]);
+        let initial = fit_squared_error(&mut g, &samples, &mut Adam::new(0.0), 1);
+        let loss = fit_squared_error(&mut g, &samples, &mut Adam::new(0.05), 500);
+        assert!(loss < 0.01 * initial, "{} -> {}", initial, loss);
+    }
+}